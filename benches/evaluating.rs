@@ -0,0 +1,87 @@
+//! Evaluation: parsing and evaluating the corpus in `corpus.txt` line by
+//! line, evaluating it again once linked, calls with many arguments, and
+//! linking and dropping a large generated program.
+
+#![allow(nonstandard_style)]
+
+use calc_rs::parsing::*;
+use calc_rs::scanning::*;
+use calc_rs::{evaluate_compiled, link, CompiledExpr, Limits, Number, Registry};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use std::collections::HashMap;
+
+const corpus: &str = include_str!("corpus.txt");
+
+/// Times from an optimized build when each case was added, printed so that
+/// changes have something to compare against.
+const baselines: &[(&str, &str)] = &[
+    ("parse and evaluate corpus", "25 µs"),
+    ("evaluate linked corpus", "2.4 µs"),
+    ("variadic call", "3.7 µs"),
+    ("link and drop large program", "330 µs"),
+];
+
+fn print_baselines() {
+    for (case, time) in baselines {
+        println!("baseline for {}: {}", case, time);
+    }
+}
+
+fn variables() -> HashMap<String, Number> {
+    ["radius", "principal", "rate", "years", "width", "height", "angle"].iter()
+        .zip([2.0, 1000.0, 0.05, 10.0, 3.0, 4.0, 0.5])
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+fn compile(line: &str, variables: &mut HashMap<String, Number>, registry: &Registry) -> CompiledExpr {
+    let expression = parse_with(StringScanner::new(line.into()), variables, registry).unwrap();
+    link(&expression, registry).unwrap()
+}
+
+fn evaluating(c: &mut Criterion) {
+    print_baselines();
+    let mut variables = variables();
+    let registry = Registry::default();
+    let limits = Limits::default();
+
+    c.bench_function("parse and evaluate corpus", |b| b.iter(|| {
+        for line in corpus.lines() {
+            let compiled = compile(line, &mut variables, &registry);
+            black_box(evaluate_compiled(&compiled, &mut variables, &limits).unwrap());
+        }
+    }));
+
+    let compiled = corpus.lines()
+        .map(|line| compile(line, &mut variables, &registry))
+        .collect::<Vec<_>>();
+    c.bench_function("evaluate linked corpus", |b| b.iter(|| {
+        for expression in &compiled {
+            black_box(evaluate_compiled(expression, &mut variables, &limits).unwrap());
+        }
+    }));
+
+    let arguments = (0..1000).map(|argument| argument.to_string()).collect::<Vec<_>>().join(", ");
+    let call = compile(&format!("max({})", arguments), &mut variables, &registry);
+    c.bench_function("variadic call", |b| b.iter(|| {
+        black_box(evaluate_compiled(&call, &mut variables, &limits).unwrap())
+    }));
+
+    // as a machine might write: many names, and a series body in each term
+    let terms = (0..2000)
+        .map(|term| format!("sum(k, 1, 2, k * w{} + {}) * w{}", term % 50, term, term % 7))
+        .collect::<Vec<_>>();
+    let program = format!("max({})", terms.join(", "));
+    for term in 0..50 {
+        variables.insert(format!("w{}", term), term as Number);
+    }
+    let expression = parse_with(StringScanner::new(program), &mut variables, &registry).unwrap();
+    c.bench_function("link and drop large program", |b| b.iter(|| {
+        drop(black_box(link(&expression, &registry).unwrap()))
+    }));
+}
+
+criterion_group!(benches, evaluating);
+criterion_main!(benches);
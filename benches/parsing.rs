@@ -0,0 +1,148 @@
+//! Scanning and parsing: a long line, a deeply nested one, lines of growing
+//! length and the corpus of typical lines in `corpus.txt`, reporting how
+//! many allocations one pass over the corpus makes alongside the timings.
+
+#![allow(nonstandard_style)]
+
+use calc_rs::parsing::*;
+use calc_rs::scanning::*;
+use calc_rs::{Number, Registry};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static allocations: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        allocations.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout)
+    }
+}
+
+#[global_allocator]
+static allocator: Counting = Counting;
+
+const corpus: &str = include_str!("corpus.txt");
+
+/// Times from an optimized build when each case was added, printed so that
+/// changes have something to compare against.
+const baselines: &[(&str, &str)] = &[
+    ("scan long expression", "200 µs"),
+    ("parse nested expression", "60 µs"),
+    ("parse corpus", "29 µs"),
+    ("parse corpus with one parser", "18 µs"),
+    ("parse by length/1000", "72 µs"),
+    ("parse by length/10000", "820 µs"),
+    ("parse by length/100000", "9.9 ms"),
+];
+
+fn print_baselines() {
+    for (case, time) in baselines {
+        println!("baseline for {}: {}", case, time);
+    }
+}
+
+/// A single line of a thousand terms.
+fn long_expression() -> String {
+    (0..1000).map(|term| format!("{} * width", term)).collect::<Vec<_>>().join(" + ")
+}
+
+/// A line nesting parentheses and calls two hundred deep.
+fn nested_expression() -> String {
+    let depth = 200;
+    let mut line = String::new();
+    for level in 0..depth {
+        line += if level % 2 == 0 { "(1 + " } else { "sqrt(" };
+    }
+    line += "width";
+    line += &")".repeat(depth);
+    line
+}
+
+/// A line of about `tokens` tokens, half of them nesting as deeply as a
+/// parser allows by default and half in a flat sum, for parse time to be
+/// seen growing in step with length.
+fn sized_expression(tokens: usize) -> String {
+    let depth = (tokens / 4).min(default_max_nesting / 2);
+    let terms = (tokens - 2 * depth) / 2;
+    "(".repeat(depth) + &"1 + ".repeat(terms) + "1" + &")".repeat(depth)
+}
+
+fn parse_corpus(variables: &mut HashMap<String, Number>) {
+    for line in corpus.lines() {
+        let expression = parse(StringScanner::new(line.to_string()), variables).unwrap();
+        black_box(expression);
+    }
+}
+
+fn parse_corpus_with(parser: &mut Parser, variables: &mut HashMap<String, Number>, registry: &Registry) {
+    for line in corpus.lines() {
+        let expression = parser.parse_line(line, variables, registry).unwrap();
+        black_box(expression);
+    }
+}
+
+fn variables() -> HashMap<String, Number> {
+    ["radius", "principal", "rate", "years", "width", "height", "angle"].iter()
+        .zip([2.0, 1000.0, 0.05, 10.0, 3.0, 4.0, 0.5])
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+fn scanning(c: &mut Criterion) {
+    print_baselines();
+    let line = long_expression();
+    c.bench_function("scan long expression", |b| b.iter(|| {
+        for token in StringScanner::new(line.clone()) {
+            black_box(token.unwrap());
+        }
+    }));
+}
+
+fn parsing(c: &mut Criterion) {
+    let mut variables = variables();
+    let before = allocations.load(Ordering::Relaxed);
+    parse_corpus(&mut variables);
+    let made = allocations.load(Ordering::Relaxed) - before;
+    println!("parsing the corpus once makes {} allocations", made);
+
+    let mut parser = Parser::new();
+    let registry = Registry::default();
+    // the first pass grows the parser's buffers, which later passes reuse
+    parse_corpus_with(&mut parser, &mut variables, &registry);
+    let before = allocations.load(Ordering::Relaxed);
+    parse_corpus_with(&mut parser, &mut variables, &registry);
+    let made = allocations.load(Ordering::Relaxed) - before;
+    println!("parsing the corpus again with one parser makes {} allocations", made);
+
+    let nested = nested_expression();
+    c.bench_function("parse nested expression", |b| b.iter(|| {
+        black_box(parse(StringScanner::new(nested.clone()), &mut variables).unwrap())
+    }));
+
+    let mut group = c.benchmark_group("parse by length");
+    for tokens in [1_000, 10_000, 100_000] {
+        let line = sized_expression(tokens);
+        group.throughput(Throughput::Elements(tokens as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(tokens), &line, |b, line| b.iter(|| {
+            black_box(parser.parse_line(line, &mut variables, &registry).unwrap().len())
+        }));
+    }
+    group.finish();
+
+    c.bench_function("parse corpus", |b| b.iter(|| parse_corpus(&mut variables)));
+    c.bench_function("parse corpus with one parser", |b| b.iter(|| parse_corpus_with(&mut parser, &mut variables, &registry)));
+}
+
+criterion_group!(benches, scanning, parsing);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! `calc!`, which evaluates an expression while compiling, for constants.
+//!
+//! ```
+//! use calc_rs_macros::calc;
+//!
+//! const tau_over_3: f64 = calc!("2*pi/3");
+//! assert_eq!(tau_over_3, 2.0 * std::f64::consts::PI / 3.0);
+//! ```
+#![allow(nonstandard_style)]
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenStream as Tokens};
+use syn::{parse_macro_input, LitStr};
+
+use calc_rs::{evaluate_str, Map};
+
+/// Evaluates the expression in a string literal, which may not use
+/// variables, and gives its value as an unsuffixed float literal, so that
+/// it may be either an `f32` or an `f64`. An expression the calculator
+/// would reject fails to compile with the calculator's message.
+#[proc_macro]
+pub fn calc(input: TokenStream) -> TokenStream {
+    let text = parse_macro_input!(input as LitStr);
+    match evaluate_str::<f64>(&text.value(), &mut Map::new()) {
+        Ok(value) => float(value).into(),
+        Err(error) => syn::Error::new(text.span(), error).to_compile_error().into(),
+    }
+}
+
+/// The tokens of an expression giving `value`, which for the values with no
+/// literal of their own is a division by zero.
+fn float(value: f64) -> Tokens {
+    let (numerator, denominator) = match value {
+        _ if value.is_nan() => (0.0, Some(0.0)),
+        _ if value.is_infinite() => (1.0, Some(0.0)),
+        _ => (value.abs(), None),
+    };
+    let sign = if value.is_sign_negative() && !value.is_nan() { "-" } else { "" };
+    let numerator = Literal::f64_unsuffixed(numerator);
+    let text = match denominator {
+        Some(denominator) => format!("({}{} / {})", sign, numerator, Literal::f64_unsuffixed(denominator)),
+        None => format!("({}{})", sign, numerator),
+    };
+    text.parse().unwrap()
+}
@@ -0,0 +1,8 @@
+//! What `calc!` compiles to, and the errors it fails to compile with.
+
+#[test]
+fn compile() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/compile/*_passes.rs");
+    cases.compile_fail("tests/compile/*_fails.rs");
+}
@@ -0,0 +1,16 @@
+use calc_rs_macros::calc;
+
+const tau_over_3: f64 = calc!("2*pi/3");
+const small: f32 = calc!("-1 / 8");
+const whole: f64 = calc!("min(3, 1, 2) + sum(k, 1, 4, k)");
+
+#[allow(nonstandard_style)]
+fn main() {
+    assert_eq!(tau_over_3, 2.0 * std::f64::consts::PI / 3.0);
+    assert_eq!(small, -0.125);
+    assert_eq!(whole, 11.0);
+    let infinite: f64 = calc!("-10 ^ 400");
+    assert_eq!(infinite, f64::NEG_INFINITY);
+    let undefined: f32 = calc!("sqrt(-1)");
+    assert!(undefined.is_nan());
+}
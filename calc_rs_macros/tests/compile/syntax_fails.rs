@@ -0,0 +1,8 @@
+use calc_rs_macros::calc;
+
+fn main() {
+    let _ = calc!("min(1, 2");
+    let _ = calc!("1 +");
+    let _ = calc!(1 + 2);
+    let _ = calc!("1 / (2 - 2)");
+}
@@ -0,0 +1,5 @@
+use calc_rs_macros::calc;
+
+const value: f64 = calc!("2 * x");
+
+fn main() {}
@@ -0,0 +1,35 @@
+//! Runs the calculator over one end of a Unix socket pair, as a server
+//! would for each connection, and talks to it from the other end.
+//!
+//! ```text
+//! cargo run --example socket
+//! ```
+
+#[cfg(unix)]
+fn main() -> std::io::Result<()> {
+    use calc_rs::repl::{run_repl, ReplOptions};
+    use calc_rs::Session;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let (mut client, server) = UnixStream::pair()?;
+    let calculator = std::thread::spawn(move || {
+        let options = ReplOptions {keep_going: true, ..ReplOptions::default()};
+        let input = BufReader::new(server.try_clone()?);
+        run_repl(&mut Session::new(), input, &server, &server, &options)
+    });
+
+    client.write_all(b"r = 3\npi * r ^ 2\nnope\n")?;
+    client.shutdown(std::net::Shutdown::Write)?;
+    for line in BufReader::new(&client).lines() {
+        println!("received {}", line?);
+    }
+    let summary = calculator.join().unwrap()?;
+    println!("{} lines, {} errors", summary.lines, summary.errors);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    println!("this example needs Unix sockets");
+}
@@ -0,0 +1,30 @@
+//! Decodes arbitrary bytes, and evaluates whatever decodes. Any result is
+//! fine, as long as nothing panics.
+
+#![no_main]
+
+use calc_rs::encoding::decode;
+use calc_rs::{evaluate_compiled, link, Limits, Number, Registry};
+
+use libfuzzer_sys::fuzz_target;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(expression) = decode(data) else {
+        return;
+    };
+    let registry = Registry::default();
+    let Ok(compiled) = link(&expression, &registry) else {
+        return;
+    };
+    let mut variables = HashMap::<String, Number>::from([("x".into(), 2.0)]);
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(100)),
+        max_iterations: Some(10_000),
+        max_depth: Some(1 << 12),
+        ..Limits::default()
+    };
+    let _ = evaluate_compiled(&compiled, &mut variables, &limits);
+});
@@ -0,0 +1,21 @@
+//! Scans, parses and evaluates arbitrary text, with and without grouping.
+//! Any result is fine, as long as nothing panics.
+
+#![no_main]
+
+use calc_rs::Session;
+
+use libfuzzer_sys::fuzz_target;
+
+use std::time::Duration;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut session = Session::new();
+    session.limits.timeout = Some(Duration::from_millis(100));
+    session.limits.max_iterations = Some(10_000);
+    for line in text.lines() {
+        let _ = session.eval_line(line);
+        session.grouping = !session.grouping;
+    }
+});
@@ -0,0 +1,70 @@
+//! Parses and evaluates sequences of well-formed tokens, which reach deeper
+//! into the parser than text mostly rejected by the scanner does.
+
+#![no_main]
+#![allow(nonstandard_style)]
+
+use calc_rs::parsing::*;
+use calc_rs::scanning::*;
+use calc_rs::{evaluate_compiled, link, Limits, Number, Registry};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+const identifiers: &[&str] = &[
+    "x", "y", "i", "_", "pi", "e", "undefined",
+    "sin", "sqrt", "sq", "min", "max", "avg", "percentile", "floorto", "fmod", "choose", "smoothstep", "isprime", "nthprime", "group",
+    "sum", "prod", "diff", "nderiv", "integrate",
+];
+
+const operators: &[&str] = &[
+    "+", "-", "*", "/", "^", "=",
+    "<", ">", "<=", ">=", "==", "!=", "&&", "||",
+];
+
+const punctuation: &[&str] = &["(", ")", ",", ";"];
+
+#[derive(Arbitrary, Debug)]
+enum Piece {
+    whole(u16),
+    fraction(u16, u8),
+    identifier(u8),
+    operator(u8),
+    punctuation(u8),
+}
+
+impl Piece {
+    fn token(&self) -> Token {
+        let pick = |table: &[&'static str], index: u8| table[index as usize % table.len()];
+        let (content, kind) = match self {
+            Self::whole(whole) => (whole.to_string().into(), TokenKind::number),
+            Self::fraction(whole, part) => (format!("{}.{}", whole, part).into(), TokenKind::number),
+            Self::identifier(index) => (pick(identifiers, *index).into(), TokenKind::identifier),
+            Self::operator(index) => (pick(operators, *index).into(), TokenKind::operator),
+            Self::punctuation(index) => (pick(punctuation, *index).into(), TokenKind::punctuation),
+        };
+        Token {content, kind}
+    }
+}
+
+fuzz_target!(|pieces: Vec<Piece>| {
+    let mut variables = HashMap::<String, Number>::from([("x".into(), 2.0), ("y".into(), -0.5)]);
+    let registry = Registry::default();
+    let tokens = pieces.iter().map(|piece| Ok(piece.token()));
+    let Ok(expression) = parse_with(tokens, &mut variables, &registry) else {
+        return;
+    };
+    let Ok(compiled) = link(&expression, &registry) else {
+        return;
+    };
+    let limits = Limits {
+        timeout: Some(Duration::from_millis(100)),
+        max_iterations: Some(10_000),
+        max_depth: Some(1 << 12),
+        ..Limits::default()
+    };
+    let _ = evaluate_compiled(&compiled, &mut variables, &limits);
+});
@@ -0,0 +1,36 @@
+/* C interface to calc_rs, built with `cargo build --release --features ffi`.
+ * Kept by hand in step with src/ffi.rs. All strings are UTF-8. */
+
+#ifndef CALC_H
+#define CALC_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define CALC_OK 0
+#define CALC_ERROR 1
+#define CALC_INVALID_ARGUMENT 2
+#define CALC_PANIC 3
+
+typedef struct CalcSession CalcSession;
+
+/* Returns a new session, owned by the caller, or NULL on failure. */
+CalcSession *calc_session_new(void);
+
+/* Frees a session from calc_session_new. NULL is ignored. */
+void calc_session_free(CalcSession *session);
+
+/* Evaluates one line. On CALC_OK, *out_result holds the formatted value;
+ * otherwise *out_error may hold a message. Either string must be released
+ * with calc_string_free. Variables persist in the session between calls. */
+int calc_eval(CalcSession *session, const char *input, char **out_result, char **out_error);
+
+/* Frees a string from calc_eval. NULL is ignored. */
+void calc_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
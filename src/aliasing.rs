@@ -0,0 +1,77 @@
+use crate::error_handling::*;
+use crate::parsing::*;
+use crate::scanning::{exponent_length, is_identifier, prefixed_length};
+
+use std::collections::HashMap;
+
+/// Names standing for pieces of expression text, substituted into a line
+/// before it is scanned.
+#[derive(Default)]
+pub struct Aliases {
+    aliases: HashMap<String, String>,
+}
+
+fn is_identifier_start(character: char) -> bool {
+    character.is_alphabetic() || character == '_'
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(&mut self, name: &str, text: &str) -> Result<()> {
+        if !is_identifier(name) {
+            return Err(CalcError::did_not_expect(name.into()));
+        }
+        if is_builtin(name) {
+            return Err(CalcError::already_defined(name.into()));
+        }
+        self.aliases.insert(name.into(), text.into());
+        Ok(())
+    }
+
+    /// Every alias and the text it stands for.
+    pub fn definitions(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.aliases.iter()
+    }
+
+    /// Replaces every alias in `line` with its text in parentheses, expanding
+    /// aliases within that text in turn.
+    pub fn expand(&self, line: &str) -> Result<String> {
+        self.expand_within(line, &mut Vec::new())
+    }
+
+    fn expand_within<'a>(&'a self, line: &str, expanding: &mut Vec<&'a str>) -> Result<String> {
+        let mut expanded = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(c) = rest.chars().next() {
+            let length = if is_identifier_start(c) {
+                rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len())
+            } else if let Some((length, _)) = prefixed_length(rest) {
+                length
+            } else if c.is_numeric() {
+                // numbers swallow the letters after them no more than the scanner does
+                let digits = rest.find(|c: char| !(c.is_numeric() || c == '.' || c == '_')).unwrap_or(rest.len());
+                digits + exponent_length(&rest[digits..]).unwrap_or(0)
+            } else {
+                c.len_utf8()
+            };
+            let (word, remainder) = rest.split_at(length);
+            match self.aliases.get_key_value(word) {
+                Some((name, _)) if expanding.contains(&name.as_str()) =>
+                    return Err(CalcError::recursive_alias(name.clone())),
+                Some((name, text)) => {
+                    expanding.push(name);
+                    expanded.push('(');
+                    expanded.push_str(&self.expand_within(text, expanding)?);
+                    expanded.push(')');
+                    expanding.pop();
+                },
+                None => expanded.push_str(word),
+            }
+            rest = remainder;
+        }
+        Ok(expanded)
+    }
+}
@@ -0,0 +1,225 @@
+//! Symbolic differentiation of parsed expressions, behind `diff(body, x)`.
+
+use crate::error_handling::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+
+use alloc::boxed::Box;
+
+/// An expression as a tree, which is easier to differentiate than postfix.
+#[derive(Clone)]
+enum Term<N> {
+    value(N),
+    load(String),
+    unary(Function, Box<Term<N>>),
+    binary(BinaryFunction, Box<Term<N>>, Box<Term<N>>),
+}
+
+/// How deeply a differentiated expression may nest. Differentiating walks
+/// the tree recursively, taking about 5 KiB of stack for each level in an
+/// unoptimized build, so this leaves most of a 2 MiB thread's stack spare.
+const max_depth: usize = 128;
+
+/// The derivative of `expression` with respect to `variable`, as nodes that
+/// compute it. Every other identifier is held constant.
+pub fn differentiate<N: CalcNum>(expression: &[ExprNode<N>], variable: &str) -> Result<Vec<ExprNode<N>>> {
+    let mut output = Vec::new();
+    Term::from_nodes(expression)?.derivative(variable)?.emit(&mut output);
+    Ok(output)
+}
+
+fn not_differentiable(name: &str) -> CalcError {
+    CalcError::not_differentiable(name.into())
+}
+
+fn pop<T>(terms: &mut Vec<T>) -> Result<T> {
+    terms.pop().ok_or_else(|| CalcError::internal("differentiated expression is unbalanced".into()))
+}
+
+impl<N: CalcNum> Term<N> {
+    /// The tree of `expression`, unless it nests deeper than `max_depth`.
+    fn from_nodes(expression: &[ExprNode<N>]) -> Result<Self> {
+        // each term with how deeply it nests
+        let mut terms = Vec::<(Self, usize)>::new();
+        for node in expression {
+            let term = match node {
+                ExprNode::value(value) => (Self::value(*value), 1),
+                ExprNode::load(name) => (Self::load(name.clone()), 1),
+                ExprNode::unary(function) => {
+                    let (operand, depth) = pop(&mut terms)?;
+                    (Self::unary(function.clone(), Box::new(operand)), depth + 1)
+                },
+                ExprNode::binary(function) => {
+                    let (right, right_depth) = pop(&mut terms)?;
+                    let (left, left_depth) = pop(&mut terms)?;
+                    (Self::binary(function.clone(), Box::new(left), Box::new(right)), left_depth.max(right_depth) + 1)
+                },
+                ExprNode::call(name, _) => return Err(not_differentiable(name)),
+                ExprNode::nderiv(..) => return Err(not_differentiable("nderiv")),
+                ExprNode::integrate(..) => return Err(not_differentiable("integrate")),
+                ExprNode::series(series) => return Err(not_differentiable(if series.product { "prod" } else { "sum" })),
+                ExprNode::assign(_) | ExprNode::assign_all(_) => return Err(not_differentiable("=")),
+                // only chained comparisons duplicate values
+                ExprNode::dup | ExprNode::under(_) => {
+                    let relation = expression.iter().find_map(|node| match node {
+                        ExprNode::under(function) => Some(function.symbol()),
+                        _ => None,
+                    });
+                    return Err(not_differentiable(relation.unwrap_or("<")));
+                },
+            };
+            if term.1 > max_depth {
+                return Err(CalcError::nesting_limit(max_depth));
+            }
+            terms.push(term);
+        }
+        pop(&mut terms).map(|(term, _)| term)
+    }
+
+    fn constant(value: f64) -> Self {
+        Self::value(N::from_f64(value))
+    }
+
+    fn is_zero(&self) -> bool {
+        matches!(self, Self::value(value) if *value == N::zero())
+    }
+
+    fn is_nonzero(&self) -> bool {
+        matches!(self, Self::value(value) if *value != N::zero() && !value.is_nan())
+    }
+
+    fn is_one(&self) -> bool {
+        matches!(self, Self::value(value) if *value == N::one())
+    }
+
+    fn derivative(&self, variable: &str) -> Result<Self> {
+        use BinaryFunction::*;
+        use Function::*;
+        Ok(match self {
+            Self::value(_) => Self::constant(0.0),
+            Self::load(name) => Self::constant(if name == variable { 1.0 } else { 0.0 }),
+            Self::unary(function, operand) => {
+                let (u, du) = ((**operand).clone(), operand.derivative(variable)?);
+                let square = |u| pow(u, Self::constant(2.0));
+                match function {
+                    positive | group => du,
+                    negative => neg(du),
+                    sin => mul(apply(cos, u), du),
+                    cos => neg(mul(apply(sin, u), du)),
+                    tan => div(du, square(apply(cos, u))),
+                    asin => div(du, apply(sqrt, sub(Self::constant(1.0), square(u)))),
+                    acos => neg(div(du, apply(sqrt, sub(Self::constant(1.0), square(u))))),
+                    atan => div(du, add(Self::constant(1.0), square(u))),
+                    todeg | torad => apply(function.clone(), du),
+                    log => div(du, mul(u, Self::constant(core::f64::consts::LN_10))),
+                    ln => div(du, u),
+                    sqrt => div(du, mul(Self::constant(2.0), apply(sqrt, u))),
+                    cbrt => div(du, mul(Self::constant(3.0), square(apply(cbrt, u)))),
+                    abs => div(mul(u.clone(), du), apply(abs, u)),
+                    sq => mul(mul(Self::constant(2.0), u), du),
+                    cube => mul(mul(Self::constant(3.0), square(u)), du),
+                    sqsign => mul(mul(Self::constant(2.0), apply(abs, u)), du),
+                    floor | ceil | round | popcount | clz | isprime | nextprime | nthprime => return Err(not_differentiable(function.name())),
+                }
+            },
+            Self::binary(function, left, right) => {
+                let (a, da) = ((**left).clone(), left.derivative(variable)?);
+                let (b, db) = ((**right).clone(), right.derivative(variable)?);
+                match function {
+                    addition => add(da, db),
+                    subtraction => sub(da, db),
+                    multiplication => add(mul(da, b), mul(a, db)),
+                    division => div(sub(mul(da, b.clone()), mul(a, db)), pow(b, Self::constant(2.0))),
+                    // the general rule takes the log of the base, so avoid it
+                    // where the base or exponent is constant
+                    exponentiation if db.is_zero() => mul(mul(b.clone(), pow(a, sub(b, Self::constant(1.0)))), da),
+                    exponentiation if da.is_zero() => mul(mul(pow(a.clone(), b), apply(ln, a)), db),
+                    exponentiation => mul(
+                        pow(a.clone(), b.clone()),
+                        add(mul(db, apply(ln, a.clone())), div(mul(b, da), a)),
+                    ),
+                    _ => return Err(not_differentiable(function.symbol())),
+                }
+            },
+        })
+    }
+
+    fn emit(self, output: &mut Vec<ExprNode<N>>) {
+        match self {
+            Self::value(value) => output.push(ExprNode::value(value)),
+            Self::load(name) => output.push(ExprNode::load(name)),
+            Self::unary(function, operand) => {
+                operand.emit(output);
+                output.push(ExprNode::unary(function));
+            },
+            Self::binary(function, left, right) => {
+                left.emit(output);
+                right.emit(output);
+                output.push(ExprNode::binary(function));
+            },
+        }
+    }
+}
+
+// The constructors below leave out terms that are plainly zero or one, so
+// that derivatives stay small and do not multiply a NaN by zero.
+
+fn apply<N>(function: Function, operand: Term<N>) -> Term<N> {
+    Term::unary(function, Box::new(operand))
+}
+
+fn binary<N>(function: BinaryFunction, left: Term<N>, right: Term<N>) -> Term<N> {
+    Term::binary(function, Box::new(left), Box::new(right))
+}
+
+fn neg<N: CalcNum>(operand: Term<N>) -> Term<N> {
+    if operand.is_zero() { operand } else { apply(Function::negative, operand) }
+}
+
+fn add<N: CalcNum>(left: Term<N>, right: Term<N>) -> Term<N> {
+    if left.is_zero() {
+        right
+    } else if right.is_zero() {
+        left
+    } else {
+        binary(BinaryFunction::addition, left, right)
+    }
+}
+
+fn sub<N: CalcNum>(left: Term<N>, right: Term<N>) -> Term<N> {
+    if right.is_zero() {
+        left
+    } else if left.is_zero() {
+        neg(right)
+    } else {
+        binary(BinaryFunction::subtraction, left, right)
+    }
+}
+
+fn mul<N: CalcNum>(left: Term<N>, right: Term<N>) -> Term<N> {
+    if left.is_zero() || right.is_one() {
+        left
+    } else if right.is_zero() || left.is_one() {
+        right
+    } else {
+        binary(BinaryFunction::multiplication, left, right)
+    }
+}
+
+fn div<N: CalcNum>(left: Term<N>, right: Term<N>) -> Term<N> {
+    // zero over what may be zero is left for evaluating to report
+    if (left.is_zero() && right.is_nonzero()) || right.is_one() {
+        left
+    } else {
+        binary(BinaryFunction::division, left, right)
+    }
+}
+
+fn pow<N: CalcNum>(base: Term<N>, exponent: Term<N>) -> Term<N> {
+    if exponent.is_one() {
+        base
+    } else {
+        binary(BinaryFunction::exponentiation, base, exponent)
+    }
+}
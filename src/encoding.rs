@@ -0,0 +1,266 @@
+//! A compact binary form for parsed expressions.
+//!
+//! An encoding starts with a version byte, followed by a table of the
+//! identifiers the expression uses and then its nodes. Each node is an opcode
+//! byte and its operands: counts and table indices are LEB128 varints,
+//! numbers are little-endian floats and functions are one-byte codes.
+
+use crate::error_handling::*;
+use crate::linking::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+use crate::Map;
+
+/// The version written by `encode`, and the only one `decode` accepts.
+pub const encoding_version: u8 = 1;
+
+/// How deeply series may be nested in an encoding that `decode` accepts.
+const max_depth: u32 = 64;
+
+mod opcode {
+    pub const value: u8 = 0;
+    pub const load: u8 = 1;
+    pub const unary: u8 = 2;
+    pub const binary: u8 = 3;
+    pub const call: u8 = 4;
+    pub const series: u8 = 5;
+    pub const assign: u8 = 6;
+    pub const dup: u8 = 7;
+    pub const under: u8 = 8;
+    pub const nderiv: u8 = 9;
+    pub const integrate: u8 = 10;
+    pub const assign_all: u8 = 11;
+}
+
+pub fn encode(expression: &[ExprNode]) -> Vec<u8> {
+    let mut encoder = Encoder {
+        names: Vec::new(),
+        indices: Map::new(),
+        body: Vec::new(),
+    };
+    encoder.nodes(expression);
+
+    let mut bytes = vec![encoding_version];
+    write_varint(&mut bytes, encoder.names.len() as u32);
+    for name in &encoder.names {
+        write_varint(&mut bytes, name.len() as u32);
+        bytes.extend_from_slice(name.as_bytes());
+    }
+    bytes.extend(encoder.body);
+    bytes
+}
+
+/// Reads an expression written by `encode`. Any input, however mangled,
+/// yields either an expression that is safe to evaluate or an error.
+pub fn decode(bytes: &[u8]) -> Result<Vec<ExprNode>> {
+    let mut reader = Reader {bytes, position: 0, names: Vec::new()};
+    let version = reader.byte()?;
+    if version != encoding_version {
+        return Err(CalcError::unknown_encoding_version(version));
+    }
+
+    for _ in 0..reader.varint()? {
+        let length = reader.varint()? as usize;
+        let name = core::str::from_utf8(reader.take(length)?)
+            .map_err(|_| malformed("identifier is not valid UTF-8"))?;
+        reader.names.push(name.into());
+    }
+
+    let expression = reader.nodes(0)?;
+    if reader.position != bytes.len() {
+        return Err(malformed("unexpected bytes after the expression"));
+    }
+    let balance = check_balance(&expression, malformed).map_err(|error| match error {
+        CalcError::stack_underflow => malformed("a node takes more values than are available"),
+        error => error,
+    })?;
+    if balance == 0 {
+        return Err(malformed("expression leaves no result"));
+    }
+    Ok(expression)
+}
+
+fn malformed(reason: &str) -> CalcError {
+    CalcError::malformed_encoding(reason.into())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Encoder {
+    names: Vec<String>,
+    indices: Map<String, u32>,
+    body: Vec<u8>,
+}
+
+impl Encoder {
+    fn name(&mut self, name: &str) {
+        let index = match self.indices.get(name) {
+            Some(index) => *index,
+            None => {
+                let index = self.names.len() as u32;
+                self.names.push(name.into());
+                self.indices.insert(name.into(), index);
+                index
+            }
+        };
+        write_varint(&mut self.body, index);
+    }
+
+    fn nodes(&mut self, expression: &[ExprNode]) {
+        write_varint(&mut self.body, expression.len() as u32);
+        for node in expression {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &ExprNode) {
+        match node {
+            ExprNode::value(value) => {
+                self.body.push(opcode::value);
+                self.body.extend_from_slice(&value.to_le_bytes());
+            },
+            ExprNode::load(name) => {
+                self.body.push(opcode::load);
+                self.name(name);
+            },
+            ExprNode::unary(function) => {
+                self.body.push(opcode::unary);
+                self.body.push(Function::all.iter().position(|f| f == function).unwrap() as u8);
+            },
+            ExprNode::binary(function) | ExprNode::under(function) => {
+                let code = if let ExprNode::binary(_) = node { opcode::binary } else { opcode::under };
+                self.body.push(code);
+                self.body.push(BinaryFunction::all.iter().position(|f| f == function).unwrap() as u8);
+            },
+            ExprNode::call(name, count) => {
+                self.body.push(opcode::call);
+                self.name(name);
+                write_varint(&mut self.body, *count);
+            },
+            ExprNode::nderiv(name, count) | ExprNode::integrate(name, count) => {
+                let code = if let ExprNode::nderiv(..) = node { opcode::nderiv } else { opcode::integrate };
+                self.body.push(code);
+                self.name(name);
+                write_varint(&mut self.body, *count);
+            },
+            ExprNode::series(series) => {
+                self.body.push(opcode::series);
+                self.name(&series.index);
+                self.body.push(series.product as u8);
+                self.nodes(&series.body);
+            },
+            ExprNode::assign(name) => {
+                self.body.push(opcode::assign);
+                self.name(name);
+            },
+            ExprNode::assign_all(names) => {
+                self.body.push(opcode::assign_all);
+                write_varint(&mut self.body, names.len() as u32);
+                for name in names {
+                    self.name(name);
+                }
+            },
+            ExprNode::dup => self.body.push(opcode::dup),
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    names: Vec<String>,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self.position.checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(CalcError::truncated_encoding)?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Result<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u32;
+            if shift == 28 && bits > 0x0f {
+                break;
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("count does not fit in 32 bits"))
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let index = self.varint()? as usize;
+        self.names.get(index).cloned()
+            .ok_or_else(|| malformed("identifier index is out of range"))
+    }
+
+    fn code<T: Clone>(&mut self, all: &[T]) -> Result<T> {
+        let code = self.byte()? as usize;
+        all.get(code).cloned()
+            .ok_or_else(|| malformed("unknown function code"))
+    }
+
+    fn nodes(&mut self, depth: u32) -> Result<Vec<ExprNode>> {
+        if depth > max_depth {
+            return Err(malformed("series are nested too deeply"));
+        }
+        let count = self.varint()?;
+        let mut expression = Vec::new();
+        for _ in 0..count {
+            expression.push(self.node(depth)?);
+        }
+        Ok(expression)
+    }
+
+    fn node(&mut self, depth: u32) -> Result<ExprNode> {
+        Ok(match self.byte()? {
+            opcode::value => {
+                let bytes = self.take(core::mem::size_of::<Number>())?;
+                ExprNode::value(Number::from_le_bytes(bytes.try_into().unwrap()))
+            },
+            opcode::load => ExprNode::load(self.name()?),
+            opcode::unary => ExprNode::unary(self.code(Function::all)?),
+            opcode::binary => ExprNode::binary(self.code(BinaryFunction::all)?),
+            opcode::call => ExprNode::call(self.name()?, self.varint()?),
+            opcode::series => {
+                let index = self.name()?;
+                let product = match self.byte()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(malformed("series kind must be 0 or 1")),
+                };
+                ExprNode::series(Series {index, body: self.nodes(depth + 1)?, product})
+            },
+            opcode::assign => ExprNode::assign(self.name()?),
+            opcode::assign_all => {
+                let count = self.varint()?;
+                ExprNode::assign_all((0..count).map(|_| self.name()).collect::<Result<_>>()?)
+            },
+            opcode::dup => ExprNode::dup,
+            opcode::under => ExprNode::under(self.code(BinaryFunction::all)?),
+            opcode::nderiv => ExprNode::nderiv(self.name()?, self.varint()?),
+            opcode::integrate => ExprNode::integrate(self.name()?, self.varint()?),
+            _ => return Err(malformed("unknown opcode")),
+        })
+    }
+}
@@ -1,6 +1,35 @@
+//! The errors of every stage, from scanning to evaluating.
+//!
+//! `CalcError` is non-exhaustive, and new variants may come in any release,
+//! so rather than matching on its variants, match on `kind`, whose kinds are
+//! fixed, and read the rest through `code`, `offending_text` and `span`. A
+//! variant's `code` and kind never change once released; its message may.
+
+use crate::prelude::*;
+
+use core::ops::Range;
 use thiserror::Error;
 
+/// The broad sorts of error, every present and future `CalcError` being one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The text is not a well-formed expression.
+    syntax,
+    /// A name refers to nothing, or to the wrong sort of thing.
+    undefined,
+    /// A function was given the wrong number of arguments.
+    arity,
+    /// Evaluating ran past a limit or into something it cannot compute.
+    math,
+    /// The calculator reached a state it never should.
+    internal,
+    /// What came from outside an expression, such as a command, an argument
+    /// or an encoding, is invalid.
+    io,
+}
+
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum CalcError {
     #[error("invalid character, '{0}', enountered")]
     invalid_character(String),
@@ -11,17 +40,233 @@ pub enum CalcError {
     #[error("the '{0}' operator has been misplaced")]
     invalid_operator(String),
 
+    #[error("'=' may only follow a lone variable at the start of a line")]
+    invalid_assignment_target,
+
+    #[error("the call to '{0}' was never closed")]
+    unclosed_function_call(String),
+
+    #[error("'|>' may only be followed by a function of one argument, not '{0}'")]
+    invalid_pipe_target(String),
+
+    /// What opened each parenthesis left open, outermost first: `(`, or a
+    /// call such as `min(`.
+    #[error("{} never closed", describe_unclosed(.0))]
+    unclosed_parentheses(Vec<String>),
+
     #[error("did not expect '{0}'")]
     did_not_expect(String),
 
     #[error("could not find '{0}'")]
     could_not_find(String),
 
+    #[error("'{0}' cannot take {1} arguments")]
+    wrong_argument_count(String, u32),
+
+    #[error("{0} variables cannot be assigned {1} values")]
+    assignment_arity_mismatch(usize, usize),
+
+    #[error("'{0}' is already defined")]
+    already_defined(String),
+
     #[error("identifier, '{0}', is not defined")]
     undefined(String),
 
+    #[error("function '{0}' is not available in this build, which lacks the '{1}' feature")]
+    not_available(String, &'static str),
+
+    #[error("there is no previous result for '_' to refer to")]
+    no_previous_result,
+
+    #[error("there is no assignment to undo")]
+    nothing_to_undo,
+
+    #[error("alias, '{0}', refers to itself")]
+    recursive_alias(String),
+
+    #[error("history entry, '{0}', does not exist")]
+    no_history_entry(String),
+
+    #[error("command, '{0}', is not recognized")]
+    unknown_command(String),
+
+    #[error("mode, '{0}', is not recognized")]
+    unknown_mode(String),
+
+    #[error("argument, '{0}', is not recognized")]
+    unknown_argument(String),
+
+    #[error("'{0}' expects a value")]
+    missing_argument(String),
+
+    #[error("evaluation exceeded the {0:?} time limit")]
+    timeout(core::time::Duration),
+
+    #[error("'{0}' is not a function")]
+    not_a_function(String),
+
+    #[error("'{0}' cannot be differentiated")]
+    not_differentiable(String),
+
+    #[error("a series, integral or prime search may not run more than {0} iterations")]
+    iteration_limit(u64),
+
+    #[error("an expression may not nest more than {0} deep")]
+    depth_limit(usize),
+
+    #[error("evaluation may not be more than {0} series deep at once")]
+    recursion_limit(usize),
+
+    #[error("expression too deeply nested, more than {0} deep")]
+    nesting_limit(usize),
+
+    #[error("expression too long, more than {0} tokens")]
+    token_limit(usize),
+
+    #[error("division by zero")]
+    division_by_zero,
+
+    #[error("'{0}' is not defined for {1}")]
+    outside_domain(String, String),
+
+    #[error("formulas depend on themselves, as in {0}")]
+    dependency_cycle(String),
+
+    #[error("'{0}' is not a valid duration")]
+    invalid_duration(String),
+
+    #[error("expression is malformed: {0}")]
+    malformed_expression(String),
+
+    #[error("encoding version {0} is not supported")]
+    unknown_encoding_version(u8),
+
+    #[error("encoded expression ended abruptly")]
+    truncated_encoding,
+
+    #[error("encoded expression is malformed: {0}")]
+    malformed_encoding(String),
+
     #[error("expression ended abruptly")]
     abrupt_end,
+
+    #[error("an operation found fewer values than it takes")]
+    stack_underflow,
+
+    #[error("expression is empty")]
+    empty_expression,
+
+    #[error("expression left {0} values rather than one")]
+    extra_values(usize),
+
+    /// A state the calculator should never reach, reported rather than
+    /// panicking on input that reaches it anyway.
+    #[error("internal error: {0}")]
+    internal(String),
+}
+
+/// Lists what opened the parentheses, as `'min(' and '('`, after how many
+/// there are.
+fn describe_unclosed(openers: &[String]) -> String {
+    let quoted = openers.iter().map(|opener| String::from("'") + opener + "'").collect::<Vec<_>>();
+    let listed = match quoted.split_last() {
+        Some((last, rest)) if !rest.is_empty() => rest.join(", ") + " and " + last,
+        _ => quoted.concat(),
+    };
+    match openers.len() {
+        1 => String::from("the parenthesis opened by ") + &listed + " was",
+        count => count.to_string() + " parentheses, opened by " + &listed + ", were",
+    }
+}
+
+impl CalcError {
+    pub fn kind(&self) -> ErrorKind {
+        use CalcError::*;
+        match self {
+            invalid_character(_) | invalid_number(_) | invalid_operator(_) | invalid_assignment_target
+                | unclosed_function_call(_) | unclosed_parentheses(_) | invalid_pipe_target(_) | did_not_expect(_) | could_not_find(_)
+                | malformed_expression(_) | abrupt_end | stack_underflow | empty_expression | extra_values(_) => ErrorKind::syntax,
+            already_defined(_) | undefined(_) | no_previous_result | nothing_to_undo | recursive_alias(_)
+                | no_history_entry(_) | not_a_function(_) | not_available(..) => ErrorKind::undefined,
+            wrong_argument_count(..) | assignment_arity_mismatch(..) => ErrorKind::arity,
+            timeout(_) | not_differentiable(_) | iteration_limit(_) | depth_limit(_) | recursion_limit(_)
+                | nesting_limit(_) | token_limit(_) | division_by_zero | outside_domain(..) | dependency_cycle(_) => ErrorKind::math,
+            internal(_) => ErrorKind::internal,
+            unknown_command(_) | unknown_mode(_) | unknown_argument(_) | missing_argument(_) | invalid_duration(_)
+                | unknown_encoding_version(_) | truncated_encoding | malformed_encoding(_) => ErrorKind::io,
+        }
+    }
+
+    /// A name for the particular error, such as `"undefined"`, for tools to
+    /// tell errors apart by.
+    pub fn code(&self) -> &'static str {
+        use CalcError::*;
+        match self {
+            invalid_character(_) => "invalid_character",
+            invalid_number(_) => "invalid_number",
+            invalid_operator(_) => "invalid_operator",
+            invalid_assignment_target => "invalid_assignment_target",
+            unclosed_function_call(_) => "unclosed_function_call",
+            unclosed_parentheses(_) => "unclosed_parentheses",
+            invalid_pipe_target(_) => "invalid_pipe_target",
+            did_not_expect(_) => "did_not_expect",
+            could_not_find(_) => "could_not_find",
+            wrong_argument_count(..) => "wrong_argument_count",
+            assignment_arity_mismatch(..) => "assignment_arity_mismatch",
+            already_defined(_) => "already_defined",
+            undefined(_) => "undefined",
+            not_available(..) => "not_available",
+            no_previous_result => "no_previous_result",
+            nothing_to_undo => "nothing_to_undo",
+            recursive_alias(_) => "recursive_alias",
+            no_history_entry(_) => "no_history_entry",
+            unknown_command(_) => "unknown_command",
+            unknown_mode(_) => "unknown_mode",
+            unknown_argument(_) => "unknown_argument",
+            missing_argument(_) => "missing_argument",
+            timeout(_) => "timeout",
+            not_a_function(_) => "not_a_function",
+            not_differentiable(_) => "not_differentiable",
+            iteration_limit(_) => "iteration_limit",
+            depth_limit(_) => "depth_limit",
+            recursion_limit(_) => "recursion_limit",
+            nesting_limit(_) => "nesting_limit",
+            token_limit(_) => "token_limit",
+            division_by_zero => "division_by_zero",
+            outside_domain(..) => "outside_domain",
+            dependency_cycle(_) => "dependency_cycle",
+            invalid_duration(_) => "invalid_duration",
+            malformed_expression(_) => "malformed_expression",
+            unknown_encoding_version(_) => "unknown_encoding_version",
+            truncated_encoding => "truncated_encoding",
+            malformed_encoding(_) => "malformed_encoding",
+            abrupt_end => "abrupt_end",
+            stack_underflow => "stack_underflow",
+            empty_expression => "empty_expression",
+            extra_values(_) => "extra_values",
+            internal(_) => "internal",
+        }
+    }
+
+    /// The text the error is about, such as the name that is undefined.
+    pub fn offending_text(&self) -> Option<&str> {
+        use CalcError::*;
+        match self {
+            invalid_character(text) | invalid_number(text) | invalid_operator(text) | unclosed_function_call(text)
+                | invalid_pipe_target(text) | did_not_expect(text) | could_not_find(text)
+                | wrong_argument_count(text, _) | not_available(text, _) | already_defined(text) | undefined(text) | recursive_alias(text)
+                | no_history_entry(text) | unknown_command(text) | unknown_mode(text) | unknown_argument(text)
+                | missing_argument(text) | not_a_function(text) | not_differentiable(text)
+                | outside_domain(text, _) | invalid_duration(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The byte range of the input the error is about. No error records
+    /// one yet, so this is always `None` for now.
+    pub fn span(&self) -> Option<Range<usize>> {
+        None
+    }
 }
 
-pub type Result<T> = std::result::Result<T, CalcError>;
\ No newline at end of file
+pub type Result<T> = core::result::Result<T, CalcError>;
\ No newline at end of file
@@ -1,36 +1,467 @@
+use crate::error_handling::*;
+use crate::linking::*;
+use crate::math;
+use crate::numbers::*;
+use crate::packing::*;
 use crate::parsing::*;
+use crate::registry::*;
+use crate::scanning::*;
+use crate::prelude::*;
+use crate::Map;
 
-use std::collections::HashMap;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
-pub fn evaluate(expression: &Vec<ExprNode>, variables: &mut HashMap<String, f32>) -> f32 {
-    let mut slots = Vec::<f32>::new();
-    for node in expression {
-        match node {
-            ExprNode::value(value) => slots.push(*value),
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct Timings {
+    pub scan: Duration,
+    pub parse: Duration,
+    pub eval: Duration,
+}
 
-            ExprNode::cast(cast) => {
-                let value = slots.pop().unwrap();
-                slots.push((cast.action)(value));
-            },
+#[derive(Clone)]
+pub struct Limits {
+    /// How long an evaluation may run. Checking it reads the system clock,
+    /// which `wasm32-unknown-unknown` lacks, so leave it unset there. It is
+    /// not enforced without std.
+    pub timeout: Option<Duration>,
+    /// The most iterations a single `sum` or `prod` may run, the most
+    /// intervals an `integrate` may take, and the most divisions `isprime`,
+    /// `nextprime` or `nthprime` may try.
+    pub max_iterations: Option<u64>,
+    /// The most values an evaluation may hold at once, and the most
+    /// operators a streamed statement may leave waiting on operands.
+    pub max_depth: Option<usize>,
+    /// The most series bodies evaluation may be inside of at once, each
+    /// taking space on the stack however quickly it runs.
+    pub max_recursion: Option<usize>,
+    /// The most a session's line may nest while being parsed; see
+    /// `Parser::max_nesting`.
+    pub max_nesting: Option<usize>,
+    /// The most tokens a session's line may be; see `Parser::max_tokens`.
+    pub max_tokens: Option<usize>,
+    /// Whether an expression, or a series body, that leaves more than one
+    /// value is an error rather than giving the first, as nodes built by
+    /// hand or a parser bug may, and whether the functions reading their
+    /// argument as a whole number, such as `popcount` and `isprime`, refuse
+    /// negatives and fractions rather than giving NaN or 0.
+    pub strict: bool,
+}
 
-            ExprNode::tie(tie) => {
-                let right = slots.pop().unwrap();
-                let left = slots.pop().unwrap();
-                slots.push((tie.action)(left, right));
-            },
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            max_iterations: Some(1_000_000),
+            max_depth: Some(1 << 16),
+            // an unoptimized build with tracing takes about 8 KiB of stack
+            // for each, so this leaves half of a 2 MiB thread's stack spare
+            max_recursion: Some(128),
+            max_nesting: Some(default_max_nesting),
+            max_tokens: Some(default_max_tokens),
+            strict: false,
+        }
+    }
+}
+
+pub fn evaluate<N: CalcNum>(expression: &[ExprNode<N>], variables: &mut Map<String, N>) -> Result<N> {
+    evaluate_limited(expression, variables, &Limits::default())
+}
+
+/// Evaluates an expression that only calls built-in functions. Expressions
+/// calling registered ones must be `link`ed and run with `evaluate_compiled`.
+pub fn evaluate_limited<N: CalcNum>(expression: &[ExprNode<N>], variables: &mut Map<String, N>, limits: &Limits) -> Result<N> {
+    evaluate_compiled(&link(expression, &Registry::default())?, variables, limits)
+}
+
+pub fn evaluate_compiled<N: CalcNum>(expression: &CompiledExpr<N>, variables: &mut Map<String, N>, limits: &Limits) -> Result<N> {
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("evaluate", nodes = expression.packed().root().len()).entered();
+    Evaluation::new(expression, variables, limits).run(expression.packed().root())
+}
+
+/// Evaluates part of an expression over the values earlier parts left in
+/// `slots`, for statements evaluated as they are read.
+#[cfg(feature = "std")]
+pub(crate) fn evaluate_onto<N: CalcNum>(expression: &CompiledExpr<N>, slots: &mut Vec<N>, variables: &mut Map<String, N>, limits: &Limits) -> Result<()> {
+    Evaluation::new(expression, variables, limits).run_onto(expression.packed().root(), slots)
+}
+
+/// An operation as evaluation reduced it, with its operands and result.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step<N = Number> {
+    unary(Function, N, N),
+    binary(BinaryFunction, N, N, N),
+}
+
+/// Evaluates `expression` like `evaluate_compiled`, also returning each
+/// unary and binary operation it reduced in the order it reduced them.
+pub fn evaluate_trace<N: CalcNum>(expression: &CompiledExpr<N>, variables: &mut Map<String, N>, limits: &Limits) -> Result<(N, Vec<Step<N>>)> {
+    evaluate_recorded(expression, variables, limits, true).map(|(value, steps, _)| (value, steps))
+}
+
+/// An assignment as evaluation made it: the name, the value it was given,
+/// and the value it replaced, if the variable was set.
+pub(crate) type Assigned<N> = (String, N, Option<N>);
+
+/// A value, with the operations and the assignments that gave it.
+pub(crate) type Recorded<N> = (N, Vec<Step<N>>, Vec<Assigned<N>>);
+
+/// Evaluates `expression`, also giving the operations it reduced when
+/// `traced`, and each assignment it made in order.
+pub(crate) fn evaluate_recorded<N: CalcNum>(expression: &CompiledExpr<N>, variables: &mut Map<String, N>, limits: &Limits, traced: bool) -> Result<Recorded<N>> {
+    let mut evaluation = Evaluation::new(expression, variables, limits);
+    if traced {
+        evaluation.steps = Some(Vec::new());
+    }
+    let value = evaluation.run(expression.packed().root())?;
+    let assigned = evaluation.effects.into_iter().zip(evaluation.replaced)
+        .map(|((name, value), replaced)| (name, value, replaced))
+        .collect();
+    Ok((value, evaluation.steps.unwrap_or_default(), assigned))
+}
+
+/// Evaluates `expression`, also returning each assignment it made in order.
+pub fn evaluate_with_effects<N: CalcNum>(expression: &[ExprNode<N>], variables: &mut Map<String, N>) -> Result<(N, Vec<(String, N)>)> {
+    let compiled = link(expression, &Registry::default())?;
+    let limits = Limits::default();
+    #[cfg(feature = "trace")]
+    let _span = tracing::debug_span!("evaluate", nodes = compiled.packed().root().len()).entered();
+    let mut evaluation = Evaluation::new(&compiled, variables, &limits);
+    let value = evaluation.run(compiled.packed().root())?;
+    Ok((value, evaluation.effects))
+}
+
+/// Takes the value on top of `slots`, which only a malformed expression
+/// leaves empty.
+fn pop<N>(slots: &mut Vec<N>) -> Result<N> {
+    slots.pop().ok_or(CalcError::stack_underflow)
+}
+
+fn top<N: Copy>(slots: &[N]) -> Result<N> {
+    slots.last().copied().ok_or(CalcError::stack_underflow)
+}
+
+/// Takes the top `count` values of `slots`, in the order they were left.
+fn split_off<N>(slots: &mut Vec<N>, count: usize) -> Result<Vec<N>> {
+    let at = slots.len().checked_sub(count).ok_or(CalcError::stack_underflow)?;
+    Ok(slots.split_off(at))
+}
+
+struct Evaluation<'a, N> {
+    compiled: &'a CompiledExpr<N>,
+    variables: &'a mut Map<String, N>,
+    limits: &'a Limits,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+    effects: Vec<(String, N)>,
+    /// The value each of the `effects` replaced, if the variable was set.
+    replaced: Vec<Option<N>>,
+    /// The operations reduced so far, when they are being traced.
+    steps: Option<Vec<Step<N>>>,
+    /// How many series bodies are being evaluated, one inside the next.
+    recursion: usize,
+}
 
-            ExprNode::knot(knot) => {
-                let mut arguments = Vec::with_capacity(knot.count as usize);
-                for _ in 0..knot.count {
-                    arguments.push(slots.pop().unwrap());
+impl<'a, N: CalcNum> Evaluation<'a, N> {
+    fn new(compiled: &'a CompiledExpr<N>, variables: &'a mut Map<String, N>, limits: &'a Limits) -> Self {
+        Self {
+            compiled,
+            variables,
+            limits,
+            #[cfg(feature = "std")]
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            effects: Vec::new(),
+            replaced: Vec::new(),
+            steps: None,
+            recursion: 0,
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn check_deadline(&self) -> Result<()> {
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn check_deadline(&self) -> Result<()> {
+        match (self.limits.timeout, self.deadline) {
+            (Some(timeout), Some(deadline)) if Instant::now() > deadline => Err(CalcError::timeout(timeout)),
+            _ => Ok(()),
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: N) {
+        let replaced = self.variables.insert(name.into(), value);
+        self.effects.push((name.into(), value));
+        self.replaced.push(replaced);
+    }
+
+    fn run(&mut self, expression: &[PackedNode<N>]) -> Result<N> {
+        let mut slots = Vec::<N>::new();
+        self.run_onto(expression, &mut slots)?;
+        if self.limits.strict && slots.len() > 1 {
+            return Err(CalcError::extra_values(slots.len()));
+        }
+        slots.first().copied().ok_or(CalcError::empty_expression)
+    }
+
+    /// Runs `expression` over the values already in `slots`, leaving what
+    /// it gives there.
+    fn run_onto(&mut self, expression: &[PackedNode<N>], slots: &mut Vec<N>) -> Result<()> {
+        let packed = self.compiled.packed();
+        for node in expression {
+            self.check_deadline()?;
+            if let Some(most) = self.limits.max_depth {
+                if slots.len() > most {
+                    return Err(CalcError::depth_limit(most));
                 }
-                slots.push((knot.action)(arguments));
-            },
+            }
+
+            trace_event!(?node, depth = slots.len(), "evaluating node");
+            match node {
+                PackedNode::value(value) => slots.push(*value),
+
+                PackedNode::load(identifier) => {
+                    let identifier = packed.name(*identifier);
+                    let value = self.variables.get(identifier).copied()
+                        .or_else(|| self.compiled.resolve(identifier))
+                        .ok_or_else(|| CalcError::undefined(identifier.into()))?;
+                    slots.push(value);
+                },
+
+                PackedNode::unary(function) => {
+                    let value = pop(slots)?;
+                    self.check_domain(function, value)?;
+                    let result = match function.searches_primes() {
+                        true => self.search_primes(function, value)?,
+                        false => function.clone().call()(value),
+                    };
+                    self.record(|| Step::unary(function.clone(), value, result));
+                    slots.push(result);
+                },
+
+                PackedNode::binary(function) => {
+                    let right = pop(slots)?;
+                    let left = pop(slots)?;
+                    let result = function.apply(left, right)?;
+                    self.record(|| Step::binary(function.clone(), left, right, result));
+                    slots.push(result);
+                },
+
+                PackedNode::call(name, count) => {
+                    let arguments = split_off(slots, *count as usize)?;
+                    slots.push(self.compiled.function(packed.name(*name))(&arguments)?);
+                },
 
-            ExprNode::assign(identifier) => {
-                variables.insert(identifier.clone(), *slots.first().unwrap());
+                PackedNode::nderiv(name, count) => {
+                    let step = if *count == 2 { slots.pop() } else { None };
+                    let point = pop(slots)?;
+                    slots.push(self.difference(packed.name(*name), point, step.unwrap_or_else(|| default_step(point)))?);
+                },
+
+                PackedNode::integrate(name, count) => {
+                    let intervals = if *count == 3 { slots.pop() } else { None };
+                    let upper = pop(slots)?;
+                    let lower = pop(slots)?;
+                    slots.push(self.integral(packed.name(*name), lower, upper, intervals)?);
+                },
+
+                PackedNode::series {index, body, product} => {
+                    let upper = pop(slots)?;
+                    let lower = pop(slots)?;
+                    slots.push(self.accumulate(packed.name(*index), packed.nodes(*body), *product, lower, upper)?);
+                },
+
+                PackedNode::assign_all(names) => {
+                    let last = top(slots)?;
+                    let values = split_off(slots, packed.names(*names).count())?;
+                    for (name, value) in packed.names(*names).zip(values) {
+                        self.assign(name, value);
+                    }
+                    slots.push(last);
+                },
+
+                PackedNode::dup => {
+                    let value = top(slots)?;
+                    slots.push(value);
+                },
+
+                PackedNode::under(function) => {
+                    let top = pop(slots)?;
+                    let right = pop(slots)?;
+                    let left = pop(slots)?;
+                    let result = function.apply(left, right)?;
+                    self.record(|| Step::binary(function.clone(), left, right, result));
+                    slots.push(result);
+                    slots.push(top);
+                },
+
+                PackedNode::assign(identifier) => {
+                    let identifier = packed.name(*identifier);
+                    let value = top(slots)?;
+                    self.assign(identifier, value);
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn record(&mut self, step: impl FnOnce() -> Step<N>) {
+        if let Some(steps) = &mut self.steps {
+            steps.push(step());
+        }
+    }
+
+    /// Applies the unary function `name`, which is built in or linked.
+    fn apply(&self, name: &str, value: N) -> Result<N> {
+        match Function::from_identifier(name) {
+            Some(function) => {
+                self.check_domain(&function, value)?;
+                match function.searches_primes() {
+                    true => self.search_primes(&function, value),
+                    false => Ok(function.call()(value)),
+                }
             },
+            None => self.compiled.function(name)(&[value]),
+        }
+    }
+
+    /// Refuses, when strict, a value the unary `function` is not defined
+    /// for.
+    fn check_domain(&self, function: &Function, value: N) -> Result<()> {
+        match self.limits.strict && !function.is_defined_for(value) {
+            true => Err(CalcError::outside_domain(function.name().into(), value.to_f64().to_string())),
+            false => Ok(()),
+        }
+    }
+
+    /// Applies one of the prime functions to `value`, counting each trial
+    /// division against the iteration limit.
+    fn search_primes(&self, function: &Function, value: N) -> Result<N> {
+        let mut divisions = 0u64;
+        search_primes(function, value, || {
+            divisions += 1;
+            match self.limits.max_iterations {
+                Some(most) if divisions > most => Err(CalcError::iteration_limit(most)),
+                _ if divisions.is_multiple_of(1024) => self.check_deadline(),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    /// The central difference of the unary function `name` about `point`.
+    fn difference(&self, name: &str, point: N, step: N) -> Result<N> {
+        Ok((self.apply(name, point + step)? - self.apply(name, point - step)?) / (step + step))
+    }
+
+    /// Simpson's rule for the unary function `name` from `lower` to `upper`,
+    /// with the number of intervals rounded up to be even.
+    fn integral(&self, name: &str, lower: N, upper: N, intervals: Option<N>) -> Result<N> {
+        let intervals = intervals.map_or(default_intervals, |intervals| intervals.to_f64());
+        let intervals = (math::double::ceil(intervals / 2.0) * 2.0).max(2.0);
+        if let Some(most) = self.limits.max_iterations {
+            if intervals > most as f64 {
+                return Err(CalcError::iteration_limit(most));
+            }
+        }
+
+        let intervals = intervals as u64;
+        let width = (upper - lower) / N::from_f64(intervals as f64);
+        let mut total = self.apply(name, lower)? + self.apply(name, upper)?;
+        for step in 1..intervals {
+            self.check_deadline()?;
+            let weight = N::from_f64(if step % 2 == 1 { 4.0 } else { 2.0 });
+            total = total + weight * self.apply(name, lower + width * N::from_f64(step as f64))?;
         }
+        Ok(total * width / N::from_f64(3.0))
     }
-    *slots.first().unwrap()
-}
\ No newline at end of file
+
+    /// Sums or multiplies `body` with `index` bound to each whole step from
+    /// `lower` up to `upper`, restoring any variable the index shadowed
+    /// afterwards.
+    fn accumulate(&mut self, index: &str, body: &[PackedNode<N>], product: bool, lower: N, upper: N) -> Result<N> {
+        let iterations = if upper < lower { 0.0 } else { (upper - lower).floor().to_f64() + 1.0 };
+        if let Some(most) = self.limits.max_iterations {
+            if iterations > most as f64 {
+                return Err(CalcError::iteration_limit(most));
+            }
+        }
+
+        if let Some(most) = self.limits.max_recursion {
+            if self.recursion >= most {
+                return Err(CalcError::recursion_limit(most));
+            }
+        }
+
+        let shadowed = self.variables.remove(index);
+        let mut total = if product { N::one() } else { N::zero() };
+        let mut result = Ok(());
+        self.recursion += 1;
+        for step in 0..iterations as u64 {
+            self.variables.insert(index.into(), lower + N::from_f64(step as f64));
+            match self.run(body) {
+                Ok(value) if product => total = total * value,
+                Ok(value) => total = total + value,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                },
+            }
+        }
+        self.recursion -= 1;
+        match shadowed {
+            Some(value) => self.variables.insert(index.into(), value),
+            None => self.variables.remove(index),
+        };
+        result.map(|_| total)
+    }
+}
+
+/// How many intervals `integrate` takes when given no count.
+const default_intervals: f64 = 100.0;
+
+/// The step `nderiv` takes when given none: the cube root of the precision,
+/// which balances rounding against truncation error, scaled to `point`.
+fn default_step<N: CalcNum>(point: N) -> N {
+    N::from_f64(math::double::cbrt(N::epsilon().to_f64())) * point.abs().max(N::one())
+}
+
+pub fn evaluate_str<N: CalcNum>(input: &str, variables: &mut Map<String, N>) -> Result<N> {
+    evaluate_str_limited(input, variables, &Limits::default())
+}
+
+pub fn evaluate_str_limited<N: CalcNum>(input: &str, variables: &mut Map<String, N>, limits: &Limits) -> Result<N> {
+    let expression = parse(StringScanner::new(input.into()), variables)?;
+    evaluate_limited(&expression, variables, limits)
+}
+
+/// Evaluates `input` like `evaluate_str`, except that any identifier that is
+/// not defined reads as `default` rather than being an error, including
+/// those read as a derivative is evaluated.
+pub fn evaluate_str_with_default<N: CalcNum>(input: &str, variables: &mut Map<String, N>, default: N) -> Result<N> {
+    let expression = parse_with_default(StringScanner::new(input.into()), variables, default)?;
+    let mut registry = Registry::default();
+    registry.resolve_with(move |_| Some(default));
+    evaluate_compiled(&link(&expression, &registry)?, variables, &Limits::default())
+}
+
+/// Evaluates `input` like `evaluate_str`, but scans it up front so that each
+/// phase can be timed separately.
+#[cfg(feature = "std")]
+pub fn evaluate_str_timed<N: CalcNum>(input: &str, variables: &mut Map<String, N>) -> Result<(N, Timings)> {
+    let start = Instant::now();
+    let tokens = StringScanner::new(input.into()).collect::<Result<Vec<_>>>()?;
+    let scanned = Instant::now();
+    let expression = parse(tokens.into_iter().map(Ok), variables)?;
+    let parsed = Instant::now();
+    let value = evaluate(&expression, variables)?;
+    let timings = Timings {
+        scan: scanned - start,
+        parse: parsed - scanned,
+        eval: parsed.elapsed(),
+    };
+    Ok((value, timings))
+}
@@ -0,0 +1,95 @@
+//! A C interface to calculator sessions, declared in `include/calc.h`.
+//!
+//! Sessions made by `calc_session_new` belong to the caller until passed to
+//! `calc_session_free`. Strings handed out through `calc_eval` belong to the
+//! caller until passed to `calc_string_free`. No panic unwinds across this
+//! boundary; one is reported as an error instead.
+
+use crate::session::*;
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// `calc_eval` succeeded and set `out_result`.
+pub const CALC_OK: c_int = 0;
+/// The input could not be evaluated and `out_error` describes why.
+pub const CALC_ERROR: c_int = 1;
+/// A pointer argument was null or the input was not UTF-8.
+pub const CALC_INVALID_ARGUMENT: c_int = 2;
+/// The calculator panicked, which is a bug, and `out_error` says so.
+pub const CALC_PANIC: c_int = 3;
+
+#[no_mangle]
+pub extern "C" fn calc_session_new() -> *mut Session {
+    catch_unwind(|| Box::into_raw(Box::new(Session::new()))).unwrap_or(ptr::null_mut())
+}
+
+/// # Safety
+///
+/// `session` must be null or a pointer from `calc_session_new` that has not
+/// yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn calc_session_free(session: *mut Session) {
+    if !session.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(session))));
+    }
+}
+
+/// Evaluates the line `input` in `session`. On success `*out_result` is set
+/// to the formatted value, otherwise `*out_error` is set to a message when it
+/// can be, and the other is set to null.
+///
+/// # Safety
+///
+/// `session` must come from `calc_session_new`, `input` must be a
+/// NUL-terminated string, and the out pointers must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn calc_eval(
+    session: *mut Session,
+    input: *const c_char,
+    out_result: *mut *mut c_char,
+    out_error: *mut *mut c_char,
+) -> c_int {
+    if session.is_null() || input.is_null() || out_result.is_null() || out_error.is_null() {
+        return CALC_INVALID_ARGUMENT;
+    }
+    *out_result = ptr::null_mut();
+    *out_error = ptr::null_mut();
+    let Ok(input) = CStr::from_ptr(input).to_str() else {
+        return CALC_INVALID_ARGUMENT;
+    };
+
+    let session = &mut *session;
+    match catch_unwind(AssertUnwindSafe(|| session.eval_line(input))) {
+        Ok(Ok(value)) => {
+            *out_result = into_c_string(value.to_string());
+            CALC_OK
+        },
+        Ok(Err(e)) => {
+            *out_error = into_c_string(e.to_string());
+            CALC_ERROR
+        },
+        Err(_) => {
+            *out_error = into_c_string("the calculator panicked".into());
+            CALC_PANIC
+        },
+    }
+}
+
+/// # Safety
+///
+/// `string` must be null or a string from `calc_eval` that has not yet been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn calc_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+fn into_c_string(text: String) -> *mut c_char {
+    // Messages never hold NUL bytes, but should one appear it is cut short
+    let text = text.split('\0').next().unwrap_or_default();
+    CString::new(text).map_or(ptr::null_mut(), CString::into_raw)
+}
@@ -0,0 +1,350 @@
+//! Writing expressions and values back out as text, for the REPL and for
+//! embedders alike.
+
+use crate::evaluating::Step;
+use crate::linking::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+
+use alloc::format;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Notation {
+    /// Positional, unless the magnitude is too large or small to read so.
+    automatic,
+    positional,
+    scientific,
+}
+
+impl Notation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "automatic" => Some(Self::automatic),
+            "positional" => Some(Self::positional),
+            "scientific" => Some(Self::scientific),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DisplayOptions {
+    /// How many digits follow the point, or `None` for the fewest that read
+    /// back as the same number.
+    pub precision: Option<usize>,
+    /// Whether whole digits are grouped in threes, as `1,234` is read when
+    /// grouping. Scientific notation is never grouped.
+    pub grouping: bool,
+    pub notation: Notation,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            precision: None,
+            grouping: false,
+            notation: Notation::automatic,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Whether operators are surrounded by spaces and separators followed
+    /// by one.
+    pub spaced: bool,
+    /// How the numbers in the expression are written. When they are grouped,
+    /// arguments are separated by `;` as grouping reads them.
+    pub values: DisplayOptions,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            spaced: true,
+            values: DisplayOptions::default(),
+        }
+    }
+}
+
+/// Writes `value`, with `NaN`, `inf` and `-inf` for the values no number
+/// literal gives, and `-0` for negative zero. Without a precision, every
+/// other value is written with the fewest digits that the scanner reads
+/// back as exactly the same number, in either notation.
+pub fn format_value(value: Number, options: &DisplayOptions) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let scientific = match options.notation {
+        Notation::automatic => value != 0.0 && !(1e-5..1e16).contains(&value.abs()),
+        Notation::positional => false,
+        Notation::scientific => true,
+    };
+    match (scientific, options.precision) {
+        (true, Some(precision)) => format!("{:.*e}", precision, value),
+        (true, None) => format!("{:e}", value),
+        (false, precision) => {
+            let text = match precision {
+                Some(precision) => format!("{:.*}", precision, value),
+                None => value.to_string(),
+            };
+            if options.grouping { group(&text) } else { text }
+        },
+    }
+}
+
+/// Writes an operation evaluation reduced and its result, as `2 + 3 = 5`.
+pub fn format_step(step: &Step, options: &DisplayOptions) -> String {
+    let value = |value: &Number| format_value(*value, options);
+    match step {
+        Step::unary(function, operand, result) => format!("{}({}) = {}", function.name(), value(operand), value(result)),
+        // a negative base is parenthesized, as `^` would otherwise take it first
+        Step::binary(BinaryFunction::exponentiation, left, right, result) if left.is_sign_negative() => {
+            format!("({}) ^ {} = {}", value(left), value(right), value(result))
+        },
+        Step::binary(function, left, right, result) => {
+            format!("{} {} {} = {}", value(left), function.symbol(), value(right), value(result))
+        },
+    }
+}
+
+/// The most digits `format_repeating` writes after the point before giving
+/// up on finding where they repeat.
+const most_repeating_digits: usize = 100;
+
+/// Writes `value` in decimal by long division, with the digits that repeat
+/// forever in parentheses, as `0.1(6)` is one sixth. Values whose digits do
+/// not repeat, or take too long to, are written as fractions, and values
+/// that are not finite as `format_value` writes them.
+pub fn format_repeating(value: Rational) -> String {
+    let (numerator, denominator) = (value.numerator() as i128, value.denominator() as i128);
+    if denominator == 0 {
+        return format_value(value.to_f64() as Number, &DisplayOptions::default());
+    }
+    // only a denominator with a prime factor besides 2 and 5 repeats
+    let mut rest = denominator;
+    for factor in [2, 5] {
+        while rest % factor == 0 {
+            rest /= factor;
+        }
+    }
+    if rest == 1 {
+        return value.to_string();
+    }
+    let sign = if numerator < 0 { "-" } else { "" };
+    let (whole, mut remainder) = (numerator.abs() / denominator, numerator.abs() % denominator);
+    let mut digits = String::new();
+    // the remainder before each digit; a remainder seen before starts the
+    // same digits over
+    let mut remainders = Vec::new();
+    loop {
+        if let Some(start) = remainders.iter().position(|seen| *seen == remainder) {
+            digits.insert(start, '(');
+            digits.push(')');
+            break;
+        }
+        if remainders.len() == most_repeating_digits {
+            return value.to_string();
+        }
+        remainders.push(remainder);
+        remainder *= 10;
+        digits.push(char::from(b'0' + (remainder / denominator) as u8));
+        remainder %= denominator;
+    }
+    format!("{}{}.{}", sign, whole, digits)
+}
+
+/// Puts a comma between each three whole digits of `text`.
+fn group(text: &str) -> String {
+    let (sign, digits) = text.split_at(text.starts_with('-') as usize);
+    let whole = digits.find('.').unwrap_or(digits.len());
+    let mut grouped = String::from(sign);
+    for (index, digit) in digits[..whole].chars().enumerate() {
+        if index > 0 && (whole - index) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped.push_str(&digits[whole..]);
+    grouped
+}
+
+/// Writes `expression` in infix, with only the parentheses it needs to be
+/// read back as the same expression.
+pub fn format_expression(expression: &CompiledExpr, options: FormatOptions) -> String {
+    write(&expression.nodes(), &options)
+}
+
+/// How tightly written text binds, from an assignment, which binds loosest,
+/// through each operator precedence to a name or call, which binds tightest.
+type Rank = u8;
+
+const assignment_rank: Rank = 0;
+const atom_rank: Rank = Rank::MAX;
+
+fn rank(precedence: Precedence) -> Rank {
+    precedence as Rank + 1
+}
+
+/// An operand as written so far.
+#[derive(Clone)]
+struct Written {
+    text: String,
+    rank: Rank,
+    /// Whether this is the copy of a chained comparison's middle operand,
+    /// which the next comparison in the chain begins with.
+    shared: bool,
+    /// Whether this is a comparison a chain may continue.
+    chain: bool,
+    /// What this comparison adds to the chain it continues, as ` < c`.
+    continuation: Option<String>,
+}
+
+impl Written {
+    fn new(text: String, rank: Rank) -> Self {
+        Self {text, rank, shared: false, chain: false, continuation: None}
+    }
+
+    /// The text, in parentheses if it binds looser than `rank`.
+    fn within(&self, rank: Rank) -> String {
+        if self.rank < rank {
+            format!("({})", self.text)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+fn write(expression: &[ExprNode], options: &FormatOptions) -> String {
+    let mut operands = Vec::<Written>::new();
+    let separator = match (options.values.grouping, options.spaced) {
+        (true, true) => "; ",
+        (true, false) => ";",
+        (false, true) => ", ",
+        (false, false) => ",",
+    };
+    let list = |operands: &mut Vec<Written>, count: u32| {
+        let arguments = operands.split_off(operands.len() - count as usize);
+        arguments.iter().map(|argument| argument.within(assignment_rank + 1)).collect::<Vec<_>>().join(separator)
+    };
+
+    for node in expression {
+        let written = match node {
+            ExprNode::value(value) => {
+                let text = format_value(*value, &options.values);
+                let rank = if text.starts_with('-') { rank(Precedence::sign) } else { atom_rank };
+                Written::new(text, rank)
+            },
+            ExprNode::load(name) => Written::new(name.clone(), atom_rank),
+            ExprNode::unary(function @ (Function::positive | Function::negative)) => {
+                let operand = operands.pop().unwrap();
+                // a sign on a sign is parenthesized, so that `--` is not read
+                Written::new(format!("{}{}", function.name(), operand.within(rank(Precedence::sign) + 1)), rank(Precedence::sign))
+            },
+            ExprNode::unary(Function::group) => {
+                let operand = operands.pop().unwrap();
+                Written::new(format!("({})", operand.text), atom_rank)
+            },
+            ExprNode::unary(function) => {
+                let operand = operands.pop().unwrap();
+                Written::new(format!("{}({})", function.name(), operand.text), atom_rank)
+            },
+            ExprNode::binary(function) => {
+                let right = operands.pop().unwrap();
+                let left = operands.pop().unwrap();
+                binary(function, left, right, options)
+            },
+            ExprNode::call(name, count) => Written::new(format!("{}({})", name, list(&mut operands, *count)), atom_rank),
+            ExprNode::nderiv(name, count) => {
+                Written::new(format!("nderiv({}{}{})", name, separator, list(&mut operands, *count)), atom_rank)
+            },
+            ExprNode::integrate(name, count) => {
+                Written::new(format!("integrate({}{}{})", name, separator, list(&mut operands, *count)), atom_rank)
+            },
+            ExprNode::series(series) => {
+                let name = if series.product { "prod" } else { "sum" };
+                let bounds = list(&mut operands, 2);
+                let body = write(&series.body, options);
+                Written::new(format!("{}({}{}{}{}{})", name, series.index, separator, bounds, separator, body), atom_rank)
+            },
+            ExprNode::assign(name) => {
+                let value = operands.pop().unwrap();
+                let equals = if options.spaced { " = " } else { "=" };
+                Written::new(format!("{}{}{}", name, equals, value.text), assignment_rank)
+            },
+            ExprNode::assign_all(names) => {
+                let values = list(&mut operands, names.len() as u32);
+                let equals = if options.spaced { " = " } else { "=" };
+                Written::new(format!("{}{}{}", names.join(separator), equals, values), assignment_rank)
+            },
+            ExprNode::dup => {
+                let top = operands.last().unwrap().clone();
+                Written {shared: true, chain: false, continuation: None, ..top}
+            },
+            ExprNode::under(function) => {
+                let top = operands.pop().unwrap();
+                let right = operands.pop().unwrap();
+                let left = operands.pop().unwrap();
+                operands.push(binary(function, left, right, options));
+                top
+            },
+        };
+        operands.push(written);
+    }
+    operands.into_iter().next().map_or_else(String::new, |written| written.text)
+}
+
+fn binary(function: &BinaryFunction, left: Written, right: Written, options: &FormatOptions) -> Written {
+    use BinaryFunction::*;
+
+    let own = rank(function.precedence());
+    let symbol = if options.spaced { format!(" {} ", function.symbol()) } else { function.symbol().into() };
+    let relation = own == rank(Precedence::relation);
+
+    // `a < b && b < c` was written `a < b < c`, and is written so again
+    if *function == and && left.chain {
+        if let Some(continuation) = &right.continuation {
+            return Written {
+                text: format!("{}{}", left.text, continuation),
+                rank: rank(Precedence::relation),
+                shared: false,
+                chain: true,
+                continuation: left.continuation.map(|own| own + continuation),
+            };
+        }
+    }
+
+    // comparisons do not group, as `a < b < c` is a chain, and `^` groups
+    // to the right; any right operand may begin with a sign, as in `2^-1`
+    let (left_rank, right_rank) = match function {
+        exponentiation => (own + 1, own),
+        _ if relation => (own + 1, own + 1),
+        _ => (own, own + 1),
+    };
+    let right_text = if right.rank == rank(Precedence::sign) { right.text } else { right.within(right_rank) };
+    let continuation = format!("{}{}", symbol, right_text);
+    Written {
+        text: format!("{}{}", left.within(left_rank), continuation),
+        rank: own,
+        shared: false,
+        chain: relation,
+        continuation: (relation && left.shared).then_some(continuation),
+    }
+}
+
+/// Escapes `text` for use inside a JSON string.
+pub fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,56 @@
+use crate::error_handling::*;
+
+pub struct History {
+    entries: Vec<String>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, line: String) {
+        self.entries.push(line);
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &String)> {
+        self.entries.iter().enumerate().map(|(index, entry)| (index + 1, entry))
+    }
+
+    fn entry(&self, designator: &str) -> Result<&String> {
+        let entry = if designator == "!" {
+            self.entries.last()
+        } else {
+            designator.parse::<usize>().ok()
+                .filter(|number| *number > 0)
+                .and_then(|number| self.entries.get(number - 1))
+        };
+        entry.ok_or_else(|| CalcError::no_history_entry(format!("!{}", designator)))
+    }
+
+    /// Replaces a leading `!!` or `!n` with the matching history entry,
+    /// returning `None` when the line does not start with either form.
+    pub fn expand(&self, line: &str) -> Result<Option<String>> {
+        let Some(rest) = line.strip_prefix('!') else {
+            return Ok(None);
+        };
+        let length = if rest.starts_with('!') {
+            1
+        } else {
+            rest.chars().take_while(char::is_ascii_digit).count()
+        };
+        if length == 0 {
+            return Ok(None);
+        }
+        let entry = self.entry(&rest[..length])?;
+        Ok(Some(format!("{}{}", entry, &rest[length..])))
+    }
+}
@@ -0,0 +1,85 @@
+//! A calculator that scans, parses and evaluates arithmetic expressions,
+//! usable as a library through [`Session`] or the lower level modules.
+//!
+//! Without the default `std` feature, the scanning, parsing and evaluating
+//! modules build for targets with only `alloc`, taking floating point
+//! functions from libm instead:
+//!
+//! ```text
+//! cargo check --no-default-features --features libm --target thumbv7em-none-eabihf
+//! ```
+//!
+//! The trigonometric and statistical functions come from the default `trig`
+//! and `stats` features, which builds that must be small can leave out.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(nonstandard_style)]
+#![allow(clippy::unit_arg)]
+
+extern crate alloc;
+
+/// A `tracing` debug event with the `trace` feature, and nothing without.
+macro_rules! trace_event {
+    ($($event:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::debug!($($event)*);
+    };
+}
+
+#[cfg(feature = "std")]
+pub mod aliasing;
+pub mod differentiating;
+pub mod encoding;
+pub mod error_handling;
+pub mod evaluating;
+pub mod formatting;
+#[cfg(feature = "std")]
+pub mod history;
+pub mod linking;
+mod math;
+pub mod normalizing;
+pub mod numbers;
+pub mod packing;
+pub mod parsing;
+pub mod registry;
+#[cfg(feature = "std")]
+pub mod repl;
+pub mod scanning;
+#[cfg(feature = "std")]
+pub mod session;
+pub mod simplifying;
+#[cfg(feature = "std")]
+pub mod streaming;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use error_handling::{CalcError, ErrorKind, Result};
+pub use evaluating::{evaluate, evaluate_compiled, evaluate_str, evaluate_str_with_default, evaluate_trace, evaluate_with_effects, Limits, Step};
+#[cfg(feature = "std")]
+pub use evaluating::{evaluate_str_timed, Timings};
+pub use formatting::{escape_json, format_expression, format_repeating, format_value, DisplayOptions, FormatOptions};
+pub use linking::{link, CompiledExpr};
+pub use numbers::{CalcNum, Number, Rational};
+pub use parsing::{binary_op, functions, operators, precedence_of, unary_op, Associativity, FunctionInfo, OperatorInfo, Parser};
+pub use registry::{Callable, FunctionProvider, Registry, Resolver};
+#[cfg(feature = "std")]
+pub use session::{format_result, ResultFormat, Session, SessionBuilder};
+
+/// The map holding variables and other tables: a `HashMap` with std, and a
+/// `BTreeMap` without.
+#[cfg(feature = "std")]
+pub type Map<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub type Map<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// What the core modules need from the std prelude, so that they also
+/// build without it.
+mod prelude {
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
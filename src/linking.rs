@@ -0,0 +1,161 @@
+use crate::error_handling::*;
+use crate::numbers::*;
+use crate::packing::*;
+use crate::parsing::*;
+use crate::registry::*;
+use crate::simplifying::*;
+use crate::prelude::*;
+use crate::Map;
+
+use alloc::rc::Rc;
+
+/// A parsed expression whose function calls have been resolved, ready to be
+/// evaluated any number of times.
+#[derive(Clone)]
+pub struct CompiledExpr<N = Number> {
+    packed: PackedExpr<N>,
+    functions: Map<String, LinkedAction<N>>,
+    resolver: Option<Resolver<N>>,
+}
+
+impl<N> CompiledExpr<N> {
+    /// The form of the expression that is evaluated.
+    pub fn packed(&self) -> &PackedExpr<N> {
+        &self.packed
+    }
+
+    pub(crate) fn function(&self, name: &str) -> &LinkedAction<N> {
+        &self.functions[name]
+    }
+
+    /// The value the registry's resolver gave `name`, for variables read
+    /// when evaluated.
+    pub(crate) fn resolve(&self, name: &str) -> Option<N> {
+        self.resolver.as_ref().and_then(|resolver| resolver(name))
+    }
+}
+
+impl<N: CalcNum> CompiledExpr<N> {
+    /// The data form of the expression, which is what gets stored and is
+    /// linked again when loaded.
+    pub fn nodes(&self) -> Vec<ExprNode<N>> {
+        self.packed.unpack()
+    }
+
+    /// The expression with its literal operations folded, as `simplify`
+    /// does, which evaluates to the same values in less time.
+    pub fn simplified(&self) -> Self {
+        Self {
+            packed: pack(&simplify(&self.nodes())),
+            functions: self.functions.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+/// Resolves every function `expression` calls against the built-ins and
+/// `registry`, checking that each call has an acceptable number of arguments.
+pub fn link<N: CalcNum>(expression: &[ExprNode<N>], registry: &Registry<N>) -> Result<CompiledExpr<N>> {
+    // nodes built by hand or deserialized have not been checked by a parser
+    if check_balance(expression, malformed)? == 0 {
+        return Err(CalcError::empty_expression);
+    }
+    link_part(expression, registry)
+}
+
+/// Links part of an expression, which may take values left by the parts
+/// before it, so is not checked for balance.
+pub(crate) fn link_part<N: CalcNum>(expression: &[ExprNode<N>], registry: &Registry<N>) -> Result<CompiledExpr<N>> {
+    let mut functions = Map::new();
+    resolve_all(expression, registry, &mut functions)?;
+    Ok(CompiledExpr {
+        packed: pack(expression),
+        functions,
+        resolver: registry.resolver().cloned(),
+    })
+}
+
+/// Calls `action` with the NaN values after the first `from` left out,
+/// giving NaN if none are left.
+fn skip_missing<N: CalcNum>(action: fn(&[N]) -> Result<N>, from: usize, values: &[N]) -> Result<N> {
+    let kept = values.iter().enumerate()
+        .filter(|(index, value)| *index < from || !value.is_nan())
+        .map(|(_, value)| *value)
+        .collect::<Vec<_>>();
+    if kept.len() == from { Ok(N::nan()) } else { action(&kept) }
+}
+
+fn malformed(reason: &str) -> CalcError {
+    CalcError::malformed_expression(reason.into())
+}
+
+fn resolve_all<N: CalcNum>(expression: &[ExprNode<N>], registry: &Registry<N>, functions: &mut Map<String, LinkedAction<N>>) -> Result<()> {
+    for node in expression {
+        match node {
+            // nodes from a build with more functions may name some this one lacks
+            ExprNode::unary(function) if !function.is_available() => return Err(undefined(function.name().into())),
+            ExprNode::call(name, count) => {
+                let action = resolve(name, *count, registry)?;
+                functions.insert(name.clone(), action);
+            },
+            ExprNode::nderiv(name, _) | ExprNode::integrate(name, _) if Function::from_identifier(name).is_none() => {
+                let action = resolve(name, 1, registry)?;
+                functions.insert(name.clone(), action);
+            },
+            ExprNode::series(series) => resolve_all(&series.body, registry, functions)?,
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+fn resolve<N: CalcNum>(name: &str, count: u32, registry: &Registry<N>) -> Result<LinkedAction<N>> {
+    if let Some(function) = VariedFunction::from_identifier(name) {
+        check_arity(name, function.arity(), count)?;
+        let from = function.aggregates_from();
+        let action = function.call();
+        Ok(match from {
+            Some(from) if registry.missing() == Missing::skip => Rc::new(move |values: &[N]| skip_missing(action, from, values)),
+            _ => Rc::new(action),
+        })
+    } else {
+        let callable = registry.function_taking(name, count)
+            .ok_or_else(|| undefined(name.into()))?;
+        check_arity(name, callable.arity(), count)?;
+        let action = callable.into_varied();
+        Ok(Rc::new(move |values: &[N]| Ok(action(values))))
+    }
+}
+
+/// Follows how many values `expression` leaves behind, failing with
+/// `stack_underflow` if any node would take more than are there, and with
+/// the error `malformed` makes if a node is otherwise malformed.
+pub(crate) fn check_balance<N>(expression: &[ExprNode<N>], malformed: fn(&str) -> CalcError) -> Result<usize> {
+    let mut depth = 0usize;
+    for node in expression {
+        let (takes, gives) = match node {
+            ExprNode::value(_) | ExprNode::load(_) => (0, 1),
+            ExprNode::unary(_) => (1, 1),
+            ExprNode::binary(_) => (2, 1),
+            ExprNode::call(_, count) => (*count as usize, 1),
+            ExprNode::nderiv(_, count @ (1 | 2)) => (*count as usize, 1),
+            ExprNode::nderiv(..) => return Err(malformed("nderiv takes a point and an optional step")),
+            ExprNode::integrate(_, count @ (2 | 3)) => (*count as usize, 1),
+            ExprNode::integrate(..) => return Err(malformed("integrate takes two bounds and an optional interval count")),
+            ExprNode::series(series) => {
+                if check_balance(&series.body, malformed)? == 0 {
+                    return Err(malformed("series body leaves no result"));
+                }
+                (2, 1)
+            },
+            ExprNode::assign(_) => (1, 1),
+            ExprNode::assign_all(names) if names.is_empty() => return Err(malformed("an assignment to several variables names none")),
+            ExprNode::assign_all(names) => (names.len(), 1),
+            ExprNode::dup => (1, 2),
+            ExprNode::under(_) => (3, 2),
+        };
+        depth = depth.checked_sub(takes)
+            .ok_or(CalcError::stack_underflow)? + gives;
+    }
+    Ok(depth)
+}
@@ -1,37 +1,221 @@
 #![allow(nonstandard_style)]
+// The REPL needs a terminal, so on the web only the library is of use.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
 
-mod error_handling;
-mod evaluating;
-mod parsing;
-mod scanning;
+use calc_rs::error_handling::*;
+use calc_rs::repl::*;
+use calc_rs::session::*;
+use calc_rs::{escape_json, format_value};
 
-use evaluating::*;
-use parsing::*;
-use scanning::*;
+use std::num::NonZeroUsize;
 
-use std::collections::HashMap;
+/// Parses durations such as `500ms`, `2s` or `1.5m`.
+fn parse_duration(text: &str) -> Result<std::time::Duration> {
+    let invalid = || CalcError::invalid_duration(text.into());
+    let split = text.find(|c: char| c.is_alphabetic()).ok_or_else(invalid)?;
+    let (amount, unit) = text.split_at(split);
+    let amount: f64 = amount.parse().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "us" => amount / 1_000_000.0,
+        "ms" => amount / 1_000.0,
+        "s" => amount,
+        "m" => amount * 60.0,
+        _ => return Err(invalid()),
+    };
+    std::time::Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
 
-fn main() {
-    use std::io::Write;
+struct Options {
+    file: Option<String>,
+    timeout: Option<std::time::Duration>,
+    max_iterations: Option<u64>,
+    max_nesting: Option<usize>,
+    max_tokens: Option<usize>,
+    keep_going: bool,
+    /// The prefix of the environment variables to start with as variables.
+    env_prefix: Option<String>,
+    /// How many threads may evaluate the lines of the file.
+    parallel: Option<NonZeroUsize>,
+    /// The statements given after `eval`, evaluated instead of any input.
+    eval: Option<String>,
+    /// Whether `eval` writes the value of every statement, not just the last.
+    all: bool,
+    /// The variables to write once the input has run, instead of its values.
+    output_vars: Option<Vec<String>>,
+    /// Whether the `output_vars` are written as a JSON object rather than
+    /// as `name=value` lines.
+    json: bool,
+}
+
+fn parse_arguments() -> Result<Options> {
+    let mut options = Options {file: None, timeout: None, max_iterations: None, max_nesting: None, max_tokens: None, keep_going: false, env_prefix: None, parallel: None, eval: None, all: false, output_vars: None, json: false};
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let mut value = || arguments.next().ok_or_else(|| CalcError::missing_argument(argument.clone()));
+        match argument.as_str() {
+            "--file" => options.file = Some(value()?),
+            "--timeout" => options.timeout = Some(parse_duration(&value()?)?),
+            "--max-iterations" => {
+                let text = value()?;
+                options.max_iterations = Some(text.parse().map_err(|_| CalcError::invalid_number(text))?);
+            },
+            "--max-nesting" => {
+                let text = value()?;
+                options.max_nesting = Some(text.parse().map_err(|_| CalcError::invalid_number(text))?);
+            },
+            "--max-tokens" => {
+                let text = value()?;
+                options.max_tokens = Some(text.parse().map_err(|_| CalcError::invalid_number(text))?);
+            },
+            "--keep-going" => options.keep_going = true,
+            "--env" => options.env_prefix = Some(value()?),
+            "--parallel" => options.parallel = Some(std::thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)),
+            _ if argument.starts_with("--parallel=") => {
+                let text = &argument["--parallel=".len()..];
+                options.parallel = Some(text.parse().map_err(|_| CalcError::invalid_number(text.into()))?);
+            },
+            "eval" if options.eval.is_none() => options.eval = Some(value()?),
+            "--all" => options.all = true,
+            "--output-vars" => options.output_vars = Some(value()?.split(',').map(|name| name.trim().to_string()).collect()),
+            "--json" => options.json = true,
+            _ => return Err(CalcError::unknown_argument(argument)),
+        }
+    }
+    Ok(options)
+}
 
-    print!("> ");
-    std::io::stdout().flush().unwrap();
+#[cfg(target_arch = "wasm32")]
+fn main() {}
 
-    let mut variables = HashMap::<String, f32>::new();
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use std::io::IsTerminal;
 
-    for line in std::io::stdin().lines() {
-        let scanner = StringScanner::new(line.unwrap());
+    let options = parse_arguments().unwrap_or_else(|e| {
+        println!("Error, {}", e);
+        std::process::exit(2);
+    });
 
-        if scanner.is_empty() {
-            break;
+    let variables = match &options.env_prefix {
+        Some(prefix) => variables_from_env(prefix, |name, error| {
+            eprintln!("Warning, {} is not set: {}", name, error);
+        }),
+        None => Default::default(),
+    };
+    let new_session = || {
+        let mut session = Session::new();
+        session.limits.timeout = options.timeout;
+        if let Some(max_iterations) = options.max_iterations {
+            session.limits.max_iterations = Some(max_iterations);
+        }
+        if let Some(max_nesting) = options.max_nesting {
+            session.limits.max_nesting = Some(max_nesting);
         }
+        if let Some(max_tokens) = options.max_tokens {
+            session.limits.max_tokens = Some(max_tokens);
+        }
+        session.variables = variables.clone();
+        session
+    };
 
-        match parse(scanner, &mut variables) {
-            Ok(expression) => println!("{}", evaluate(&expression, &mut variables)),
-            Err(e) => println!("Error, {}", e.to_string()),
+    if let Some(line) = &options.eval {
+        let mut session = new_session();
+        match session.show_statements(line) {
+            Ok(_) if options.output_vars.is_some() => write_outputs(&session, &options),
+            Ok(mut shown) => {
+                if !options.all {
+                    shown.drain(..shown.len().saturating_sub(1));
+                }
+                for shown in shown {
+                    println!("{}", shown);
+                }
+            },
+            Err(e) => {
+                println!("Error, {}", e);
+                std::process::exit(1);
+            },
         }
+        return;
+    }
+
+    let repl = ReplOptions {
+        interactive: options.file.is_none() && std::io::stdin().is_terminal(),
+        prompting: options.file.is_none(),
+        keep_going: options.keep_going,
+        source: options.file.clone(),
+    };
+    // with --output-vars, only the variables go to stdout, so errors go to stderr
+    let (output, error): (Box<dyn std::io::Write>, Box<dyn std::io::Write>) = match options.output_vars {
+        Some(_) => (Box::new(std::io::sink()), Box::new(std::io::stderr())),
+        None => (Box::new(std::io::stdout()), Box::new(std::io::stdout())),
+    };
+    let mut session = new_session();
+    let open_failed = |file: &str, e: std::io::Error| -> ! {
+        println!("Error, could not open '{}': {}", file, e);
+        std::process::exit(2);
+    };
+    // errors are written among the values, as they always have been
+    let summary = match (&options.file, options.parallel) {
+        // the lines of a parallel run assign no variables to write
+        (Some(file), Some(threads)) if options.output_vars.is_none() => {
+            let text = std::fs::read(file).unwrap_or_else(|e| open_failed(file, e));
+            match std::str::from_utf8(&text) {
+                Ok(text) if independent_lines(text.lines()) => {
+                    run_parallel(new_session, text, threads, std::io::stdout(), std::io::stdout(), &repl)
+                },
+                decoded => {
+                    if decoded.is_ok() {
+                        eprintln!("Note, '{}' runs commands, assigns variables or reads '_', so its lines are evaluated in order", file);
+                    }
+                    // text that is not UTF-8 fails where reading it line by line would
+                    run_repl(&mut session, text.as_slice(), output, error, &repl)
+                },
+            }
+        },
+        (Some(file), _) => {
+            let input = std::fs::File::open(file).unwrap_or_else(|e| open_failed(file, e));
+            run_repl(&mut session, std::io::BufReader::new(input), output, error, &repl)
+        },
+        (None, parallel) => {
+            if parallel.is_some() {
+                eprintln!("Note, only the lines of a --file are evaluated in parallel");
+            }
+            run_repl(&mut session, std::io::stdin().lock(), output, error, &repl)
+        },
+    };
+    let summary = summary.unwrap_or_else(|e| {
+        println!("Error, {}", e);
+        std::process::exit(2);
+    });
+    if summary.stopped {
+        std::process::exit(1);
+    }
+    if options.output_vars.is_some() {
+        write_outputs(&session, &options);
+    }
+}
 
-        print!("> ");
-        std::io::stdout().flush().unwrap();
+/// Writes the `--output-vars` of `session`, or the error for the first
+/// that is not set, leaving with a failing status.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_outputs(session: &Session, options: &Options) {
+    let names = options.output_vars.iter().flatten().map(String::as_str).collect::<Vec<_>>();
+    let values = session.values_of(&names).unwrap_or_else(|e| {
+        eprintln!("Error, {}", e);
+        std::process::exit(1);
+    });
+    if options.json {
+        // JSON has no NaN or infinities, so they are written as null
+        let members = values.iter()
+            .map(|(name, value)| match value.is_finite() {
+                true => format!("\"{}\": {}", escape_json(name), value),
+                false => format!("\"{}\": null", escape_json(name)),
+            })
+            .collect::<Vec<_>>();
+        println!("{{{}}}", members.join(", "));
+    } else {
+        for (name, value) in values {
+            println!("{}={}", name, format_value(value, &session.display));
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,63 @@
+//! Floating point functions that core lacks, taken from std when it is
+//! available and from libm otherwise.
+
+#[cfg(all(not(feature = "std"), not(feature = "libm")))]
+compile_error!("without the std feature, the libm feature is needed for floating point functions");
+
+macro_rules! functions {
+    ($module:ident, $float:ty; $($name:ident => $libm:ident),* $(,)?) => {
+        pub mod $module {
+            $(
+                #[cfg(feature = "std")]
+                pub fn $name(x: $float) -> $float {
+                    x.$name()
+                }
+
+                #[cfg(not(feature = "std"))]
+                pub fn $name(x: $float) -> $float {
+                    libm::$libm(x)
+                }
+            )*
+        }
+    };
+}
+
+functions! {
+    single, f32;
+    floor => floorf, ceil => ceilf, round => roundf, trunc => truncf,
+    sin => sinf, cos => cosf, tan => tanf,
+    asin => asinf, acos => acosf, atan => atanf,
+    log10 => log10f, ln => logf,
+    sqrt => sqrtf, cbrt => cbrtf,
+    abs => fabsf,
+}
+
+functions! {
+    double, f64;
+    floor => floor, ceil => ceil, round => round, trunc => trunc,
+    sin => sin, cos => cos, tan => tan,
+    asin => asin, acos => acos, atan => atan,
+    log10 => log10, ln => log,
+    sqrt => sqrt, cbrt => cbrt,
+    abs => fabs,
+}
+
+#[cfg(feature = "std")]
+pub fn powf(x: f32, y: f32) -> f32 {
+    x.powf(y)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn powf(x: f32, y: f32) -> f32 {
+    libm::powf(x, y)
+}
+
+#[cfg(feature = "std")]
+pub fn pow(x: f64, y: f64) -> f64 {
+    x.powf(y)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn pow(x: f64, y: f64) -> f64 {
+    libm::pow(x, y)
+}
@@ -0,0 +1,76 @@
+use crate::encoding::*;
+use crate::parsing::*;
+use crate::prelude::*;
+
+/// The operands of a node, as the part of the output that computes it.
+struct Operand {
+    start: usize,
+    /// Whether the operand is a self-contained subexpression that may be
+    /// moved. Values made by `dup` or `under` depend on their neighbours,
+    /// and those assigning variables on what reads them.
+    movable: bool,
+}
+
+/// Whether `expression` assigns a variable, in a series body or otherwise.
+fn assigns(expression: &[ExprNode]) -> bool {
+    expression.iter().any(|node| match node {
+        ExprNode::assign(_) | ExprNode::assign_all(_) => true,
+        ExprNode::series(series) => assigns(&series.body),
+        _ => false,
+    })
+}
+
+/// Rewrites `expression` so that the operands of commutative operations are
+/// in a canonical order, making `2+3` and `3+2` compare and hash alike. The
+/// result evaluates to the same value as the original.
+pub fn normalize(expression: &[ExprNode]) -> Vec<ExprNode> {
+    let mut output = Vec::new();
+    let mut operands = Vec::<Operand>::new();
+    for node in expression {
+        let (takes, commutative) = match node {
+            ExprNode::value(_) | ExprNode::load(_) => (0, false),
+            ExprNode::unary(_) => (1, false),
+            ExprNode::binary(function) => (2, function.is_commutative()),
+            ExprNode::call(name, count) => (*count as usize, matches!(name.as_str(), "min" | "max")),
+            ExprNode::nderiv(_, count) | ExprNode::integrate(_, count) => (*count as usize, false),
+            ExprNode::series(_) => (2, false),
+            // the value assigned, which stays on the stack
+            ExprNode::assign(_) => (1, false),
+            ExprNode::assign_all(names) => (names.len(), false),
+            ExprNode::dup => (1, false),
+            ExprNode::under(_) => (3, false),
+        };
+        let taken = operands.split_off(operands.len().saturating_sub(takes));
+        let start = taken.first().map_or(output.len(), |operand| operand.start);
+        let movable = taken.iter().all(|operand| operand.movable);
+
+        if commutative && movable && taken.len() > 1 {
+            let ends = taken.iter().skip(1).map(|operand| operand.start).chain([output.len()]);
+            let mut parts = taken.iter().zip(ends)
+                .map(|(operand, end)| output[operand.start..end].to_vec())
+                .collect::<Vec<_>>();
+            parts.sort_by_cached_key(|part| encode(part));
+            output.truncate(start);
+            output.extend(parts.into_iter().flatten());
+        }
+
+        match node {
+            ExprNode::series(series) => output.push(ExprNode::series(Series {
+                body: normalize(&series.body),
+                ..series.clone()
+            })),
+            node => output.push(node.clone()),
+        }
+
+        match node {
+            ExprNode::assign(_) | ExprNode::assign_all(_) => operands.push(Operand {start, movable: false}),
+            ExprNode::series(series) if assigns(&series.body) => operands.push(Operand {start, movable: false}),
+            ExprNode::dup | ExprNode::under(_) => {
+                operands.push(Operand {start, movable: false});
+                operands.push(Operand {start: output.len() - 1, movable: false});
+            },
+            _ => operands.push(Operand {start, movable}),
+        }
+    }
+    output
+}
@@ -0,0 +1,412 @@
+//! The numbers expressions compute with.
+//!
+//! Parsing and evaluation work with any `CalcNum`. Everything else, from the
+//! session to the bindings, uses `Number`.
+
+use crate::math;
+
+use core::fmt::Debug;
+use core::ops::{Add, Div, Mul, Neg, Sub};
+
+/// The number type used wherever a concrete one is needed.
+pub type Number = f64;
+
+/// A type that expressions can be evaluated in. Besides arithmetic, an
+/// implementation needs only to convert to and from `f64`; the functions
+/// default to going through `f64`, and may be overridden to be exact.
+pub trait CalcNum:
+    Copy + PartialOrd + Debug
+    + Add<Output = Self> + Sub<Output = Self>
+    + Mul<Output = Self> + Div<Output = Self>
+    + Neg<Output = Self>
+    + 'static
+{
+    /// Converts a constant, count or truth value into a number.
+    fn from_f64(value: f64) -> Self;
+
+    /// The nearest `f64`, used by functions without an exact counterpart.
+    fn to_f64(self) -> f64;
+
+    /// Reads a number literal such as `2`, `0.5` or `1.5e-7`.
+    fn parse(literal: &str) -> Option<Self> {
+        literal.parse().ok().map(Self::from_f64)
+    }
+
+    fn zero() -> Self {
+        Self::from_f64(0.0)
+    }
+
+    fn one() -> Self {
+        Self::from_f64(1.0)
+    }
+
+    /// The difference between one and the next larger number.
+    fn epsilon() -> Self {
+        Self::from_f64(f64::EPSILON)
+    }
+
+    /// The result of a function given an argument outside its domain.
+    fn nan() -> Self {
+        Self::from_f64(f64::NAN)
+    }
+
+    /// Whether the number is NaN, the one value unordered with itself.
+    fn is_nan(self) -> bool {
+        self.partial_cmp(&self).is_none()
+    }
+
+    fn min(self, other: Self) -> Self {
+        if other < self { other } else { self }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other > self { other } else { self }
+    }
+
+    fn powf(self, exponent: Self) -> Self {
+        Self::from_f64(math::pow(self.to_f64(), exponent.to_f64()))
+    }
+
+    /// The remainder of dividing by `divisor`, with the sign of `self`.
+    fn rem(self, divisor: Self) -> Self {
+        Self::from_f64(self.to_f64() % divisor.to_f64())
+    }
+
+    fn floor(self) -> Self { through_f64(self, math::double::floor) }
+    fn ceil(self) -> Self { through_f64(self, math::double::ceil) }
+    fn round(self) -> Self { through_f64(self, math::double::round) }
+    fn trunc(self) -> Self { through_f64(self, math::double::trunc) }
+    fn sin(self) -> Self { through_f64(self, math::double::sin) }
+    fn cos(self) -> Self { through_f64(self, math::double::cos) }
+    fn tan(self) -> Self { through_f64(self, math::double::tan) }
+    fn asin(self) -> Self { through_f64(self, math::double::asin) }
+    fn acos(self) -> Self { through_f64(self, math::double::acos) }
+    fn atan(self) -> Self { through_f64(self, math::double::atan) }
+    fn to_degrees(self) -> Self { through_f64(self, f64::to_degrees) }
+    fn to_radians(self) -> Self { through_f64(self, f64::to_radians) }
+    fn log10(self) -> Self { through_f64(self, math::double::log10) }
+    fn ln(self) -> Self { through_f64(self, math::double::ln) }
+    fn sqrt(self) -> Self { through_f64(self, math::double::sqrt) }
+    fn cbrt(self) -> Self { through_f64(self, math::double::cbrt) }
+    fn abs(self) -> Self { through_f64(self, math::double::abs) }
+}
+
+fn through_f64<N: CalcNum>(value: N, function: fn(f64) -> f64) -> N {
+    N::from_f64(function(value.to_f64()))
+}
+
+macro_rules! float {
+    ($float:ty, $module:ident, $pow:path) => {
+        impl CalcNum for $float {
+            fn from_f64(value: f64) -> Self {
+                value as $float
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn parse(literal: &str) -> Option<Self> {
+                literal.parse().ok()
+            }
+
+            fn epsilon() -> Self {
+                <$float>::EPSILON
+            }
+
+            fn min(self, other: Self) -> Self { <$float>::min(self, other) }
+            fn max(self, other: Self) -> Self { <$float>::max(self, other) }
+            fn powf(self, exponent: Self) -> Self { $pow(self, exponent) }
+            fn rem(self, divisor: Self) -> Self { self % divisor }
+            fn floor(self) -> Self { math::$module::floor(self) }
+            fn ceil(self) -> Self { math::$module::ceil(self) }
+            fn round(self) -> Self { math::$module::round(self) }
+            fn trunc(self) -> Self { math::$module::trunc(self) }
+            fn sin(self) -> Self { math::$module::sin(self) }
+            fn cos(self) -> Self { math::$module::cos(self) }
+            fn tan(self) -> Self { math::$module::tan(self) }
+            fn asin(self) -> Self { math::$module::asin(self) }
+            fn acos(self) -> Self { math::$module::acos(self) }
+            fn atan(self) -> Self { math::$module::atan(self) }
+            fn to_degrees(self) -> Self { <$float>::to_degrees(self) }
+            fn to_radians(self) -> Self { <$float>::to_radians(self) }
+            fn log10(self) -> Self { math::$module::log10(self) }
+            fn ln(self) -> Self { math::$module::ln(self) }
+            fn sqrt(self) -> Self { math::$module::sqrt(self) }
+            fn cbrt(self) -> Self { math::$module::cbrt(self) }
+            fn abs(self) -> Self { math::$module::abs(self) }
+        }
+    };
+}
+
+float!(f32, single, math::powf);
+float!(f64, double, math::pow);
+
+/// An exact fraction, for evaluating without rounding. Only `+`, `-`, `*`,
+/// `/`, whole powers, remainders and rounding to integers are exact; other
+/// functions go through `f64`, and their results read back as the fraction
+/// the `f64` is exactly. So does a result whose numerator or denominator
+/// would not fit in an `i64`. NaN and the infinities have a zero denominator.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "(i64, i64)", into = "(i64, i64)"))]
+pub struct Rational {
+    /// Carries the sign, with no factor in common with the denominator.
+    numerator: i64,
+    /// Positive, unless the value is NaN or infinite.
+    denominator: i64,
+}
+
+/// Reads a numerator and denominator, as `Rational::new` does.
+impl From<(i64, i64)> for Rational {
+    fn from((numerator, denominator): (i64, i64)) -> Self {
+        Self::new(numerator, denominator)
+    }
+}
+
+impl From<Rational> for (i64, i64) {
+    fn from(value: Rational) -> Self {
+        (value.numerator, value.denominator)
+    }
+}
+
+impl Rational {
+    /// The fraction `numerator / denominator` in lowest terms.
+    pub fn new(numerator: i64, denominator: i64) -> Self {
+        Self::reduced(numerator as i128, denominator as i128)
+    }
+
+    pub fn numerator(self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(self) -> i64 {
+        self.denominator
+    }
+
+    fn is_finite(self) -> bool {
+        self.denominator != 0
+    }
+
+    fn reduced(numerator: i128, denominator: i128) -> Self {
+        if denominator == 0 {
+            return Self {numerator: numerator.signum() as i64, denominator: 0};
+        }
+        let divisor = gcd(numerator, denominator) * denominator.signum();
+        let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+        match (i64::try_from(numerator), i64::try_from(denominator)) {
+            (Ok(numerator), Ok(denominator)) if numerator != i64::MIN => Self {numerator, denominator},
+            _ => Self::from_f64(numerator as f64 / denominator as f64),
+        }
+    }
+
+    /// The numerator and denominator, widened so that products fit.
+    fn wide(self) -> (i128, i128) {
+        (self.numerator as i128, self.denominator as i128)
+    }
+
+    /// Combines two finite fractions exactly with `exact`, or else through
+    /// `f64` with `float`.
+    fn combine(self, other: Self, exact: fn(i128, i128, i128, i128) -> (i128, i128), float: fn(f64, f64) -> f64) -> Self {
+        if self.is_finite() && other.is_finite() {
+            let (a, b) = self.wide();
+            let (c, d) = other.wide();
+            let (numerator, denominator) = exact(a, b, c, d);
+            Self::reduced(numerator, denominator)
+        } else {
+            Self::from_f64(float(self.to_f64(), other.to_f64()))
+        }
+    }
+
+    /// Rounds to a whole number with `rounding`, given the quotient and
+    /// remainder of the numerator by the denominator.
+    fn to_whole(self, rounding: fn(i64, i64, i64) -> i64) -> Self {
+        if self.is_finite() {
+            let whole = rounding(self.numerator.div_euclid(self.denominator), self.numerator.rem_euclid(self.denominator), self.denominator);
+            Self {numerator: whole, denominator: 1}
+        } else {
+            self
+        }
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs().max(1)
+}
+
+impl PartialEq for Rational {
+    fn eq(&self, other: &Self) -> bool {
+        // lowest terms make equal fractions identical, except that NaN is
+        // equal to nothing
+        (self.numerator, self.denominator) == (other.numerator, other.denominator)
+            && (self.is_finite() || self.numerator != 0)
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        if self.is_finite() && other.is_finite() {
+            let (a, b) = self.wide();
+            let (c, d) = other.wide();
+            (a * d).partial_cmp(&(c * b))
+        } else {
+            self.to_f64().partial_cmp(&other.to_f64())
+        }
+    }
+}
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.combine(other, |a, b, c, d| (a * d + c * b, b * d), |x, y| x + y)
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.combine(other, |a, b, c, d| (a * d - c * b, b * d), |x, y| x - y)
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        self.combine(other, |a, b, c, d| (a * c, b * d), |x, y| x * y)
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        if other == Self::zero() {
+            return Self::from_f64(self.to_f64() / other.to_f64());
+        }
+        self.combine(other, |a, b, c, d| (a * d, b * c), |x, y| x / y)
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {numerator: -self.numerator, ..self}
+    }
+}
+
+impl core::fmt::Display for Rational {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.denominator {
+            0 => write!(f, "{}", self.to_f64()),
+            1 => write!(f, "{}", self.numerator),
+            denominator => write!(f, "{}/{}", self.numerator, denominator),
+        }
+    }
+}
+
+impl CalcNum for Rational {
+    /// The fraction `value` is exactly, when it fits.
+    fn from_f64(value: f64) -> Self {
+        if !value.is_finite() {
+            let numerator = if value.is_nan() { 0 } else if value < 0.0 { -1 } else { 1 };
+            return Self {numerator, denominator: 0};
+        }
+        let (mut scaled, mut denominator) = (value, 1i64);
+        while math::double::trunc(scaled) != scaled && denominator < 1 << 62 {
+            scaled *= 2.0;
+            denominator *= 2;
+        }
+        if math::double::abs(scaled) < i64::MAX as f64 {
+            Self::new(math::double::round(scaled) as i64, denominator)
+        } else {
+            Self::from_f64(value * f64::INFINITY)
+        }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Reads a decimal literal exactly, so that `0.1` is one tenth and
+    /// `1e-3` one thousandth.
+    fn parse(literal: &str) -> Option<Self> {
+        let (mantissa, exponent) = literal.split_once(['e', 'E']).unwrap_or((literal, "0"));
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = [whole, fraction].concat();
+        // the point moves left for each fraction digit, and right by the exponent
+        let shift = exponent.parse::<i64>().ok().and_then(|exponent| exponent.checked_sub(fraction.len() as i64));
+        let scale = |shift: i64| u32::try_from(shift).ok().and_then(|shift| 10i64.checked_pow(shift));
+        let exact = match (digits.parse::<i64>().ok(), shift) {
+            (Some(digits), Some(shift @ 0..)) => scale(shift).and_then(|scale| digits.checked_mul(scale)).map(|numerator| (numerator, 1)),
+            (Some(digits), Some(shift)) => scale(-shift).map(|denominator| (digits, denominator)),
+            _ => None,
+        };
+        match exact {
+            Some((numerator, denominator)) => Some(Self::new(numerator, denominator)),
+            None => literal.parse().ok().map(Self::from_f64),
+        }
+    }
+
+    fn zero() -> Self {
+        Self {numerator: 0, denominator: 1}
+    }
+
+    fn one() -> Self {
+        Self {numerator: 1, denominator: 1}
+    }
+
+    /// Exact for whole exponents.
+    fn powf(self, exponent: Self) -> Self {
+        if exponent.denominator != 1 || !self.is_finite() {
+            return Self::from_f64(math::pow(self.to_f64(), exponent.to_f64()));
+        }
+        let (mut base, mut power, mut result) = (self, exponent.numerator.unsigned_abs(), Self::one());
+        while power > 0 {
+            if power & 1 == 1 {
+                result = result * base;
+            }
+            base = base * base;
+            power >>= 1;
+        }
+        if exponent.numerator < 0 { Self::one() / result } else { result }
+    }
+
+    fn rem(self, divisor: Self) -> Self {
+        if divisor == Self::zero() {
+            return Self::nan();
+        }
+        self.combine(divisor, |a, b, c, d| ((a * d) % (c * b), b * d), |x, y| x % y)
+    }
+
+    fn floor(self) -> Self {
+        self.to_whole(|quotient, _, _| quotient)
+    }
+
+    fn ceil(self) -> Self {
+        self.to_whole(|quotient, remainder, _| quotient + (remainder != 0) as i64)
+    }
+
+    /// Rounds halves away from zero, as `f64::round` does.
+    fn round(self) -> Self {
+        self.to_whole(|quotient, remainder, denominator| {
+            let twice = remainder as i128 * 2;
+            let up = twice > denominator as i128 || twice == denominator as i128 && quotient >= 0;
+            quotient + up as i64
+        })
+    }
+
+    fn trunc(self) -> Self {
+        if self < Self::zero() { self.ceil() } else { self.floor() }
+    }
+
+    fn abs(self) -> Self {
+        Self {numerator: self.numerator.abs(), ..self}
+    }
+}
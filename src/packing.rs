@@ -0,0 +1,201 @@
+//! Expressions packed into a few contiguous allocations for evaluation:
+//! nodes name variables and functions by index into one table of names,
+//! and series bodies by a span of the same list of nodes, so that a large
+//! program is quick to build and to drop.
+
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+use crate::Map;
+
+use core::ops::Range;
+
+/// A name in the table of a `PackedExpr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Name(u32);
+
+/// A run of the nodes, the names or the text of a `PackedExpr`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    start: u32,
+    length: u32,
+}
+
+impl Span {
+    fn range(self) -> Range<usize> {
+        self.start as usize..(self.start + self.length) as usize
+    }
+}
+
+/// An `ExprNode` whose names and series body are held by its `PackedExpr`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PackedNode<N = Number> {
+    value(N),
+    load(Name),
+    unary(Function),
+    binary(BinaryFunction),
+    call(Name, u32),
+    series {index: Name, body: Span, product: bool},
+    nderiv(Name, u32),
+    integrate(Name, u32),
+    assign(Name),
+    /// Assigns the names the span covers.
+    assign_all(Span),
+    dup,
+    under(BinaryFunction),
+}
+
+/// An expression as `pack` lays it out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedExpr<N = Number> {
+    /// Every distinct name, one after another.
+    text: String,
+    /// Where each name lies in `text`. The names of an `assign_all` are
+    /// repeated here together.
+    names: Vec<Span>,
+    /// Every series body, each before the nodes containing it, and last the
+    /// nodes of the expression itself.
+    nodes: Vec<PackedNode<N>>,
+    root: Span,
+}
+
+impl<N> PackedExpr<N> {
+    /// The nodes of the expression itself, in postfix order.
+    pub fn root(&self) -> &[PackedNode<N>] {
+        self.nodes(self.root)
+    }
+
+    /// The nodes of a series body.
+    pub fn nodes(&self, span: Span) -> &[PackedNode<N>] {
+        &self.nodes[span.range()]
+    }
+
+    pub fn name(&self, name: Name) -> &str {
+        &self.text[self.names[name.0 as usize].range()]
+    }
+
+    /// The names of an `assign_all`.
+    pub fn names(&self, span: Span) -> impl Iterator<Item = &str> {
+        self.names[span.range()].iter().map(|name| &self.text[name.range()])
+    }
+}
+
+impl<N: Clone> PackedExpr<N> {
+    /// The expression as nodes again, which is how it is stored or written.
+    pub fn unpack(&self) -> Vec<ExprNode<N>> {
+        self.unpack_nodes(self.root)
+    }
+
+    fn unpack_nodes(&self, span: Span) -> Vec<ExprNode<N>> {
+        let name = |name: &Name| self.name(*name).to_string();
+        self.nodes(span).iter().map(|node| match node {
+            PackedNode::value(value) => ExprNode::value(value.clone()),
+            PackedNode::load(identifier) => ExprNode::load(name(identifier)),
+            PackedNode::unary(function) => ExprNode::unary(function.clone()),
+            PackedNode::binary(function) => ExprNode::binary(function.clone()),
+            PackedNode::call(identifier, count) => ExprNode::call(name(identifier), *count),
+            PackedNode::series {index, body, product} => ExprNode::series(Series {
+                index: name(index),
+                body: self.unpack_nodes(*body),
+                product: *product,
+            }),
+            PackedNode::nderiv(identifier, count) => ExprNode::nderiv(name(identifier), *count),
+            PackedNode::integrate(identifier, count) => ExprNode::integrate(name(identifier), *count),
+            PackedNode::assign(identifier) => ExprNode::assign(name(identifier)),
+            PackedNode::assign_all(names) => ExprNode::assign_all(self.names(*names).map(String::from).collect()),
+            PackedNode::dup => ExprNode::dup,
+            PackedNode::under(function) => ExprNode::under(function.clone()),
+        }).collect()
+    }
+}
+
+/// Lays `expression` out as a `PackedExpr`, holding each distinct name once.
+pub fn pack<N: Clone>(expression: &[ExprNode<N>]) -> PackedExpr<N> {
+    let mut packer = Packer {
+        text: String::new(),
+        names: Vec::new(),
+        nodes: Vec::with_capacity(expression.len()),
+        interned: Map::new(),
+    };
+    let root = packer.level(expression);
+    PackedExpr {
+        text: packer.text,
+        names: packer.names,
+        nodes: packer.nodes,
+        root,
+    }
+}
+
+struct Packer<'a, N> {
+    text: String,
+    names: Vec<Span>,
+    nodes: Vec<PackedNode<N>>,
+    interned: Map<&'a str, Name>,
+}
+
+fn span(start: usize, end: usize) -> Span {
+    Span {start: start as u32, length: (end - start) as u32}
+}
+
+impl<'a, N: Clone> Packer<'a, N> {
+    fn name(&mut self, name: &'a str) -> Name {
+        if let Some(interned) = self.interned.get(name) {
+            return *interned;
+        }
+        let start = self.text.len();
+        self.text.push_str(name);
+        self.names.push(span(start, self.text.len()));
+        let interned = Name(self.names.len() as u32 - 1);
+        self.interned.insert(name, interned);
+        interned
+    }
+
+    fn names(&mut self, names: &'a [String]) -> Span {
+        // interned first, as interning a new name adds it to the table
+        for name in names {
+            self.name(name);
+        }
+        let start = self.names.len();
+        for name in names {
+            let Name(index) = self.interned[name.as_str()];
+            self.names.push(self.names[index as usize]);
+        }
+        span(start, self.names.len())
+    }
+
+    /// Packs the bodies of the series in `expression`, then `expression`
+    /// itself, giving where it lies.
+    fn level(&mut self, expression: &'a [ExprNode<N>]) -> Span {
+        let mut bodies = expression.iter()
+            .filter_map(|node| match node {
+                ExprNode::series(series) => Some(self.level(&series.body)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let start = self.nodes.len();
+        for node in expression {
+            let packed = match node {
+                ExprNode::value(value) => PackedNode::value(value.clone()),
+                ExprNode::load(name) => PackedNode::load(self.name(name)),
+                ExprNode::unary(function) => PackedNode::unary(function.clone()),
+                ExprNode::binary(function) => PackedNode::binary(function.clone()),
+                ExprNode::call(name, count) => PackedNode::call(self.name(name), *count),
+                ExprNode::series(series) => PackedNode::series {
+                    index: self.name(&series.index),
+                    body: bodies.next().unwrap(),
+                    product: series.product,
+                },
+                ExprNode::nderiv(name, count) => PackedNode::nderiv(self.name(name), *count),
+                ExprNode::integrate(name, count) => PackedNode::integrate(self.name(name), *count),
+                ExprNode::assign(name) => PackedNode::assign(self.name(name)),
+                ExprNode::assign_all(names) => PackedNode::assign_all(self.names(names)),
+                ExprNode::dup => PackedNode::dup,
+                ExprNode::under(function) => PackedNode::under(function.clone()),
+            };
+            self.nodes.push(packed);
+        }
+        span(start, self.nodes.len())
+    }
+}
@@ -1,39 +1,220 @@
+use crate::differentiating::*;
 use crate::scanning::*;
 use crate::error_handling::*;
-use std::collections::HashMap;
+use crate::numbers::*;
+use crate::registry::*;
+use crate::prelude::*;
+use crate::Map;
+use crate::math;
 
-pub struct Cast {
-    pub action: fn(f32) -> f32,
-}
-
-pub struct Tie {
-    pub action: fn(f32, f32) -> f32,
-}
+use alloc::rc::Rc;
+use core::ops::Range;
 
-pub struct Knot {
-    pub action: fn(Vec<f32>) -> f32,
-    pub count: u32,
-}
+pub type UnaryAction<N = Number> = Rc<dyn Fn(N) -> N>;
+pub type BinaryAction<N = Number> = Rc<dyn Fn(N, N) -> N>;
+pub type VariedAction<N = Number> = Rc<dyn Fn(&[N]) -> N>;
+/// A function as linked, failing where it has no value.
+pub(crate) type LinkedAction<N = Number> = Rc<dyn Fn(&[N]) -> Result<N>>;
 
-enum Precedence {
-    low, medium, high,
+#[derive(PartialEq, PartialOrd)]
+pub(crate) enum Precedence {
+    disjunction, conjunction,
+    equality, relation,
+    pipe,
+    low, medium,
+    /// Between `*` and `^`, so that `-2^2` is `-(2^2)` and `2^-1*4` is
+    /// `(2^-1)*4`.
+    sign,
+    high,
 }
 
 impl Precedence {
     fn precedes(&self, other: &Self) -> bool {
+        *self > *other || (*self == *other && other.associativity() == Associativity::left)
+    }
+
+    /// `^` alone groups to the right, so that `2^3^2` is `2^(3^2)`.
+    const fn associativity(&self) -> Associativity {
+        match self {
+            Precedence::high => Associativity::right,
+            _ => Associativity::left,
+        }
+    }
+
+    /// The level `precedence_of` gives, fixed so that it need not change
+    /// when a precedence is added between others.
+    const fn level(&self) -> u8 {
         use Precedence::*;
-        match (self, other) {
-            (_, high) => false,
-            (high, _) => true,
-            (medium, _) => true,
-            (_, medium) => false,
-            _ => true,
+        match self {
+            disjunction => 0,
+            conjunction => 1,
+            equality => 2,
+            relation => 3,
+            pipe => 4,
+            low => 5,
+            medium => 6,
+            sign => 7,
+            high => 8,
         }
     }
 }
 
-#[derive(Clone)]
-enum Function {
+/// How tightly the binary operator `symbol` binds, higher binding tighter:
+/// from `||` at 0 through `|>` at 4, `+` at 5 and `*` at 6 to `^` at 8.
+/// A sign binds at 7, between `*` and `^`. `None` if `symbol` is not an
+/// operator.
+pub fn precedence_of(symbol: &str) -> Option<u8> {
+    match symbol {
+        "|>" => Some(Precedence::pipe.level()),
+        _ => BinaryFunction::from_operator(symbol).ok().map(|function| function.precedence().level()),
+    }
+}
+
+/// Which way a run of operators of the same precedence groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Associativity {
+    left, right,
+}
+
+/// An operator as the parser reads it, for tools such as editors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OperatorInfo {
+    pub symbol: &'static str,
+    /// How tightly it binds, on the scale of `precedence_of`.
+    pub precedence: u8,
+    pub associativity: Associativity,
+    /// 2 for an infix operator and 1 for a prefix one.
+    pub arity: u32,
+}
+
+impl OperatorInfo {
+    const fn binary(symbol: &'static str, function: BinaryFunction) -> Self {
+        Self::new(symbol, function.precedence(), 2)
+    }
+
+    const fn prefix(symbol: &'static str, function: Function) -> Self {
+        Self::new(symbol, function.precedence(), 1)
+    }
+
+    const fn new(symbol: &'static str, precedence: Precedence, arity: u32) -> Self {
+        Self {symbol, precedence: precedence.level(), associativity: precedence.associativity(), arity}
+    }
+}
+
+/// Every operator, loosest first. The levels and associativity come from
+/// the parser's own precedences.
+static operator_table: &[OperatorInfo] = &[
+    OperatorInfo::binary("||", BinaryFunction::or),
+    OperatorInfo::binary("&&", BinaryFunction::and),
+    OperatorInfo::binary("==", BinaryFunction::equal),
+    OperatorInfo::binary("!=", BinaryFunction::not_equal),
+    OperatorInfo::binary("<", BinaryFunction::less),
+    OperatorInfo::binary(">", BinaryFunction::greater),
+    OperatorInfo::binary("<=", BinaryFunction::less_or_equal),
+    OperatorInfo::binary(">=", BinaryFunction::greater_or_equal),
+    OperatorInfo::new("|>", Precedence::pipe, 2),
+    OperatorInfo::binary("+", BinaryFunction::addition),
+    OperatorInfo::binary("-", BinaryFunction::subtraction),
+    OperatorInfo::binary("−", BinaryFunction::subtraction),
+    OperatorInfo::binary("*", BinaryFunction::multiplication),
+    OperatorInfo::binary("×", BinaryFunction::multiplication),
+    OperatorInfo::binary("/", BinaryFunction::division),
+    OperatorInfo::binary("÷", BinaryFunction::division),
+    OperatorInfo::prefix("+", Function::positive),
+    OperatorInfo::prefix("-", Function::negative),
+    OperatorInfo::prefix("−", Function::negative),
+    OperatorInfo::binary("^", BinaryFunction::exponentiation),
+];
+
+/// Every infix and prefix operator, loosest first.
+pub fn operators() -> &'static [OperatorInfo] {
+    operator_table
+}
+
+/// A function, for tools such as editors and `:help`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub name: String,
+    /// The fewest and, when bounded, the most arguments it accepts.
+    pub arity: (u32, Option<u32>),
+    pub description: String,
+}
+
+/// A line on each built-in function, some of which a build may leave out.
+const builtin_descriptions: &[(&str, &str)] = &[
+    ("abs", "absolute value"),
+    ("floor", "rounds down"),
+    ("ceil", "rounds up"),
+    ("round", "rounds to the nearest whole number"),
+    ("floorto", "rounds down to a multiple: floorto(x, step)"),
+    ("ceilto", "rounds up to a multiple: ceilto(x, step)"),
+    ("fmod", "remainder, truncated, floored or Euclidean: fmod(x, d, mode)"),
+    ("sq", "square"),
+    ("cube", "cube"),
+    ("sqsign", "square keeping the sign"),
+    ("sqrt", "square root"),
+    ("cbrt", "cube root"),
+    ("log", "base 10 logarithm"),
+    ("ln", "natural logarithm"),
+    ("sin", "sine, in radians"),
+    ("cos", "cosine, in radians"),
+    ("tan", "tangent, in radians"),
+    ("asin", "inverse sine"),
+    ("acos", "inverse cosine"),
+    ("atan", "inverse tangent"),
+    ("todeg", "radians to degrees"),
+    ("torad", "degrees to radians"),
+    ("popcount", "set bits of a 32-bit whole number"),
+    ("clz", "leading zero bits of a 32-bit whole number"),
+    ("isprime", "1 if prime, else 0"),
+    ("nextprime", "the first prime above"),
+    ("nthprime", "the nth prime"),
+    ("choose", "ways to pick k of n unordered: choose(n, k)"),
+    ("perm", "ways to pick k of n in order: perm(n, k)"),
+    ("min", "least argument"),
+    ("max", "greatest argument"),
+    ("avg", "mean of the arguments"),
+    ("percentile", "percentile of the data: percentile(p, data...)"),
+    ("step", "0 below the edge, else 1: step(edge, x)"),
+    ("smoothstep", "Hermite easing between edges: smoothstep(e0, e1, x)"),
+    ("sum", "sum of a series: sum(k, from, to, body)"),
+    ("prod", "product of a series: prod(k, from, to, body)"),
+    ("diff", "derivative of an expression: diff(body, x)"),
+    ("nderiv", "numeric derivative of a function: nderiv(f, x, step)"),
+    ("integrate", "numeric integral of a function: integrate(f, a, b, intervals)"),
+    ("group", "its argument, as parentheses give it"),
+];
+
+/// The arguments the built-in function `name` accepts, if this build has it.
+fn builtin_arity(name: &str) -> Option<(u32, Option<u32>)> {
+    if Function::from_identifier(name).is_some() {
+        Some((1, Some(1)))
+    } else if let Some(function) = VariedFunction::from_identifier(name) {
+        Some(function.arity())
+    } else if OpenSeries::from_identifier(name).is_some() {
+        Some((OpenSeries::arity, Some(OpenSeries::arity)))
+    } else if OpenDerivative::from_identifier(name).is_some() {
+        Some((OpenDerivative::arity, Some(OpenDerivative::arity)))
+    } else {
+        OpenFunctional::from_identifier(name).map(|functional| functional.arity())
+    }
+}
+
+/// Every built-in function this build has, with the arguments the parser
+/// accepts for it.
+pub fn functions() -> Vec<FunctionInfo> {
+    builtin_descriptions.iter()
+        .filter_map(|(name, description)| Some(FunctionInfo {
+            name: name.to_string(),
+            arity: builtin_arity(name)?,
+            description: description.to_string(),
+        }))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Function {
     positive, negative,
     floor, ceil, round,
     sin, cos, tan,
@@ -42,27 +223,145 @@ enum Function {
     log, ln,
     sqrt, cbrt,
     abs,
+    popcount, clz,
+    sq, cube, sqsign,
+    isprime, nextprime, nthprime,
+    /// Gives its argument, as parentheses would, and is written as them.
+    group,
 }
 
-impl Into<ExprNode> for Function {
-    fn into(self) -> ExprNode {
-        ExprNode::cast(Cast {action: self.call()})
+/// Truncates `value` to the 32-bit unsigned integer that `popcount` and
+/// `clz` inspect, or `None` when it is negative or too large to fit.
+fn to_bits<N: CalcNum>(value: N) -> Option<u32> {
+    let value = value.trunc().to_f64();
+    if value >= 0.0 && value <= u32::MAX as f64 {
+        Some(value as u32)
+    } else {
+        None
+    }
+}
+
+/// `value` as the whole number the prime functions take, or `None` when
+/// it is negative, fractional or too large.
+fn whole_number<N: CalcNum>(value: N) -> Option<u64> {
+    let value = value.to_f64();
+    (value >= 0.0 && value < u64::MAX as f64 && math::double::trunc(value) == value).then_some(value as u64)
+}
+
+/// Whether `n` is prime, by trial division, calling `divide` before each
+/// division so that the caller can stop a long search.
+fn is_prime(n: u64, mut divide: impl FnMut() -> Result<()>) -> Result<bool> {
+    if n < 4 {
+        return Ok(n >= 2);
+    }
+    if n.is_multiple_of(2) {
+        return Ok(false);
+    }
+    let mut divisor = 3;
+    while divisor <= n / divisor {
+        divide()?;
+        if n.is_multiple_of(divisor) {
+            return Ok(false);
+        }
+        divisor += 2;
+    }
+    Ok(true)
+}
+
+/// The first prime above `n`, if there is one below `u64::MAX`.
+fn next_prime(n: u64, mut divide: impl FnMut() -> Result<()>) -> Result<Option<u64>> {
+    let mut candidate = n;
+    while let Some(next) = candidate.checked_add(1) {
+        candidate = next;
+        if is_prime(candidate, &mut divide)? {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// Applies `isprime`, `nextprime` or `nthprime` to `value`, calling
+/// `divide` before each trial division so that the caller can stop a long
+/// search. Values they are not defined for give 0 from `isprime` and NaN
+/// from the others.
+pub(crate) fn search_primes<N: CalcNum>(function: &Function, value: N, mut divide: impl FnMut() -> Result<()>) -> Result<N> {
+    // a prime the number type cannot hold exactly would read as another number
+    let found = |prime: Option<u64>| match prime.map(|prime| (prime, N::from_f64(prime as f64))) {
+        Some((prime, value)) if value.to_f64() as u64 == prime => value,
+        _ => N::nan(),
+    };
+    match function {
+        Function::isprime => Ok(truth(match whole_number(value) {
+            Some(n) => is_prime(n, divide)?,
+            None => false,
+        })),
+        Function::nextprime => {
+            if value < N::zero() {
+                return Ok(N::from_f64(2.0));
+            }
+            match whole_number(value.floor()) {
+                Some(n) => Ok(found(next_prime(n, divide)?)),
+                None => Ok(N::nan()),
+            }
+        },
+        Function::nthprime => {
+            let Some(count) = whole_number(value).filter(|count| *count > 0) else {
+                return Ok(N::nan());
+            };
+            let mut prime = Some(0);
+            for _ in 0..count {
+                prime = match prime {
+                    Some(prime) => next_prime(prime, &mut divide)?,
+                    None => break,
+                };
+            }
+            Ok(found(prime))
+        },
+        _ => Err(CalcError::internal(function.name().to_string() + " does not search for primes")),
+    }
+}
+
+impl<N> From<Function> for ExprNode<N> {
+    fn from(function: Function) -> Self {
+        Self::unary(function)
     }
 }
 
 impl Function {
+    /// Every function, indexed by its code in the binary encoding. New
+    /// functions go at the end so that existing codes keep their meaning.
+    pub(crate) const all: &'static [Self] = {
+        use Function::*;
+        &[
+            positive, negative,
+            floor, ceil, round,
+            sin, cos, tan,
+            asin, acos, atan,
+            todeg, torad,
+            log, ln,
+            sqrt, cbrt,
+            abs,
+            popcount, clz,
+            sq, cube, sqsign,
+            isprime, nextprime, nthprime,
+            group,
+        ]
+    };
+
     fn from_operator(content: &str) -> Result<Self> {
         use Function::*;
         match content {
             "+" => Ok(positive),
-            "-" => Ok(negative),
+            "-" | "−" => Ok(negative),
+            "=" => Err(CalcError::invalid_assignment_target),
             _ => Err(CalcError::invalid_operator(content.into()))
         }
     }
 
-    fn from_identifier(content: &str) -> Option<Self> {
+    /// The function called `content`, if this build has it.
+    pub(crate) fn from_identifier(content: &str) -> Option<Self> {
         use Function::*;
-        match content {
+        let function = match content {
             "floor" => Some(floor),
             "ceil" => Some(ceil),
             "round" => Some(round),
@@ -79,42 +378,132 @@ impl Function {
             "sqrt" => Some(sqrt),
             "cbrt" => Some(cbrt),
             "abs" => Some(abs),
+            "popcount" => Some(popcount),
+            "clz" => Some(clz),
+            "sq" => Some(sq),
+            "cube" => Some(cube),
+            "sqsign" => Some(sqsign),
+            "isprime" => Some(isprime),
+            "nextprime" => Some(nextprime),
+            "nthprime" => Some(nthprime),
+            "group" => Some(group),
             _ => None
+        }?;
+        function.is_available().then_some(function)
+    }
+
+    /// Whether the function searches for primes, which evaluating limits
+    /// like a series.
+    pub(crate) fn searches_primes(&self) -> bool {
+        matches!(self, Self::isprime | Self::nextprime | Self::nthprime)
+    }
+
+    /// Whether `value` is one the function is defined for, which only
+    /// matters for those reading their argument as a whole number: others
+    /// take anything. Strict evaluating refuses the rest.
+    pub(crate) fn is_defined_for<N: CalcNum>(&self, value: N) -> bool {
+        match self {
+            Self::popcount | Self::clz => value.trunc() == value && to_bits(value).is_some(),
+            Self::isprime => whole_number(value).is_some(),
+            Self::nthprime => whole_number(value).is_some_and(|count| count > 0),
+            _ => true,
         }
     }
 
-    fn call(self) -> fn(f32) -> f32 {
+    /// Whether the family the function belongs to is built in, as the
+    /// arithmetic functions always are.
+    pub(crate) fn is_available(&self) -> bool {
+        use Function::*;
+        cfg!(feature = "trig") || !matches!(self, sin | cos | tan | asin | acos | atan | todeg | torad)
+    }
+
+    /// The operator or identifier the function is written as.
+    pub(crate) fn name(&self) -> &'static str {
+        use Function::*;
+        match self {
+            positive => "+",
+            negative => "-",
+            floor => "floor",
+            ceil => "ceil",
+            round => "round",
+            sin => "sin",
+            cos => "cos",
+            tan => "tan",
+            asin => "asin",
+            acos => "acos",
+            atan => "atan",
+            todeg => "todeg",
+            torad => "torad",
+            log => "log",
+            ln => "ln",
+            sqrt => "sqrt",
+            cbrt => "cbrt",
+            abs => "abs",
+            popcount => "popcount",
+            clz => "clz",
+            sq => "sq",
+            cube => "cube",
+            sqsign => "sqsign",
+            isprime => "isprime",
+            nextprime => "nextprime",
+            nthprime => "nthprime",
+            group => "group",
+        }
+    }
+
+    pub(crate) fn call<N: CalcNum>(self) -> fn(N) -> N {
         use Function::*;
         match self {
             positive => |n| n,
             negative => |n| -n,
-            floor => f32::floor,
-            ceil => f32::ceil,
-            round => f32::round,
-            sin => f32::sin,
-            cos => f32::cos,
-            tan => f32::tan,
-            asin => f32::asin,
-            acos => f32::acos,
-            atan => f32::atan,
-            todeg => f32::to_degrees,
-            torad => f32::to_radians,
-            log => f32::log10,
-            ln => f32::ln,
-            sqrt => f32::sqrt,
-            cbrt => f32::cbrt,
-            abs => f32::abs,
-        }
-    }
-
-    fn precedence(&self) -> Precedence {
+            floor => N::floor,
+            ceil => N::ceil,
+            round => N::round,
+            #[cfg(feature = "trig")]
+            sin => N::sin,
+            #[cfg(feature = "trig")]
+            cos => N::cos,
+            #[cfg(feature = "trig")]
+            tan => N::tan,
+            #[cfg(feature = "trig")]
+            asin => N::asin,
+            #[cfg(feature = "trig")]
+            acos => N::acos,
+            #[cfg(feature = "trig")]
+            atan => N::atan,
+            #[cfg(feature = "trig")]
+            todeg => N::to_degrees,
+            #[cfg(feature = "trig")]
+            torad => N::to_radians,
+            // linking refuses them, so that their code is left out
+            #[cfg(not(feature = "trig"))]
+            sin | cos | tan | asin | acos | atan | todeg | torad => |_| N::nan(),
+            log => N::log10,
+            ln => N::ln,
+            sqrt => N::sqrt,
+            cbrt => N::cbrt,
+            abs => N::abs,
+            popcount => |n| to_bits(n).map_or(N::nan(), |bits| N::from_f64(bits.count_ones() as f64)),
+            clz => |n| to_bits(n).map_or(N::nan(), |bits| N::from_f64(bits.leading_zeros() as f64)),
+            sq => |n| n * n,
+            cube => |n| n * n * n,
+            // the square, keeping the sign of `n`
+            sqsign => |n| n * n.abs(),
+            isprime => |n| search_primes(&isprime, n, || Ok(())).unwrap_or_else(|_| N::nan()),
+            nextprime => |n| search_primes(&nextprime, n, || Ok(())).unwrap_or_else(|_| N::nan()),
+            nthprime => |n| search_primes(&nthprime, n, || Ok(())).unwrap_or_else(|_| N::nan()),
+            group => |n| n,
+        }
+    }
+
+    const fn precedence(&self) -> Precedence {
         match self {
-            Self::positive | Self::negative => Precedence::low,
+            Self::positive | Self::negative => Precedence::sign,
             _ => Precedence::high,
         }
     }
 
-    fn preceding(&self, precedence: &Precedence) -> Option<ExprNode> {
+    fn preceding<N>(&self, precedence: &Precedence) -> Option<ExprNode<N>> {
         if self.precedence().precedes(precedence) {
             Some(self.clone().into())
         } else {
@@ -123,54 +512,139 @@ impl Function {
     }
 }
 
-#[derive(Clone)]
-enum BinaryFunction {
+fn truth<N: CalcNum>(condition: bool) -> N {
+    if condition { N::one() } else { N::zero() }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryFunction {
     addition, subtraction,
     multiplication, division,
     exponentiation,
+    less, greater,
+    less_or_equal, greater_or_equal,
+    equal, not_equal,
+    and, or,
 }
 
-impl Into<ExprNode> for BinaryFunction {
-    fn into(self) -> ExprNode {
-        ExprNode::tie(Tie {action: self.call()})
+impl<N> From<BinaryFunction> for ExprNode<N> {
+    fn from(function: BinaryFunction) -> Self {
+        Self::binary(function)
     }
 }
 
 impl BinaryFunction {
+    /// Every binary function, indexed by its code in the binary encoding.
+    pub(crate) const all: &'static [Self] = {
+        use BinaryFunction::*;
+        &[
+            addition, subtraction,
+            multiplication, division,
+            exponentiation,
+            less, greater,
+            less_or_equal, greater_or_equal,
+            equal, not_equal,
+            and, or,
+        ]
+    };
+
     fn from_operator(content: &str) -> Result<Self> {
         use BinaryFunction::*;
 
         match content {
             "+" => Ok(addition),
-            "-" => Ok(subtraction),
-            "*" => Ok(multiplication),
-            "/" => Ok(division),
+            "-" | "−" => Ok(subtraction),
+            "*" | "×" => Ok(multiplication),
+            "/" | "÷" => Ok(division),
             "^" => Ok(exponentiation),
+            "<" => Ok(less),
+            ">" => Ok(greater),
+            "<=" => Ok(less_or_equal),
+            ">=" => Ok(greater_or_equal),
+            "==" => Ok(equal),
+            "!=" => Ok(not_equal),
+            "&&" => Ok(and),
+            "||" => Ok(or),
+            "=" => Err(CalcError::invalid_assignment_target),
             _ => Err(CalcError::invalid_operator(content.into()))
         }
     }
 
-    fn call(self) -> fn(f32, f32) -> f32 {
+    /// The operator the function is written as.
+    pub(crate) fn symbol(&self) -> &'static str {
+        use BinaryFunction::*;
+        match self {
+            addition => "+",
+            subtraction => "-",
+            multiplication => "*",
+            division => "/",
+            exponentiation => "^",
+            less => "<",
+            greater => ">",
+            less_or_equal => "<=",
+            greater_or_equal => ">=",
+            equal => "==",
+            not_equal => "!=",
+            and => "&&",
+            or => "||",
+        }
+    }
+
+    pub(crate) fn call<N: CalcNum>(self) -> fn(N, N) -> N {
         use BinaryFunction::*;
         match self {
             addition => |a, b| a + b,
             subtraction => |a, b| a - b,
             multiplication => |a, b| a * b,
             division => |a, b| a / b,
-            exponentiation => |a, b| a.powf(b),
+            exponentiation => N::powf,
+            less => |a, b| truth(a < b),
+            greater => |a, b| truth(a > b),
+            less_or_equal => |a, b| truth(a <= b),
+            greater_or_equal => |a, b| truth(a >= b),
+            equal => |a, b| truth(a == b),
+            not_equal => |a, b| truth(a != b),
+            and => |a, b| truth(a != N::zero() && b != N::zero()),
+            or => |a, b| truth(a != N::zero() || b != N::zero()),
+        }
+    }
+
+    /// Applies the function, failing where it has no value, as when
+    /// dividing by zero.
+    pub(crate) fn apply<N: CalcNum>(&self, left: N, right: N) -> Result<N> {
+        if *self == BinaryFunction::division && right == N::zero() {
+            return Err(CalcError::division_by_zero);
         }
+        Ok(self.clone().call()(left, right))
     }
 
-    fn precedence(&self) -> Precedence {
+    /// Whether the function compares or combines truth values.
+    pub(crate) fn is_truth_valued(&self) -> bool {
+        use BinaryFunction::*;
+        !matches!(self, addition | subtraction | multiplication | division | exponentiation)
+    }
+
+    /// Whether swapping the operands can never change the result.
+    pub(crate) fn is_commutative(&self) -> bool {
+        use BinaryFunction::*;
+        matches!(self, addition | multiplication | equal | not_equal | and | or)
+    }
+
+    pub(crate) const fn precedence(&self) -> Precedence {
         use BinaryFunction::*;
         match self {
             addition | subtraction => Precedence::low,
             multiplication | division => Precedence::medium,
             exponentiation => Precedence::high,
+            less | greater | less_or_equal | greater_or_equal => Precedence::relation,
+            equal | not_equal => Precedence::equality,
+            and => Precedence::conjunction,
+            or => Precedence::disjunction,
         }
     }
 
-    fn preceding(&self, precedence: &Precedence) -> Option<ExprNode> {
+    fn preceding<N>(&self, precedence: &Precedence) -> Option<ExprNode<N>> {
         if self.precedence().precedes(precedence) {
             Some(self.clone().into())
         } else {
@@ -179,285 +653,1049 @@ impl BinaryFunction {
     }
 }
 
-enum VariedFunction {
-    min, max, avg,
+/// Rounds `value` to a multiple of `step` in the direction given by
+/// `rounding`, regardless of the sign of `step` or of `value`: `floorto`
+/// rounds toward negative infinity, so that `floorto(-17, 5)` is -20, and
+/// `ceilto` toward positive infinity. A zero step leaves the value
+/// untouched.
+fn to_multiple<N: CalcNum>(value: N, step: N, rounding: fn(N) -> N) -> N {
+    if step == N::zero() {
+        value
+    } else {
+        let step = step.abs();
+        rounding(value / step) * step
+    }
+}
+
+/// The number of ways to pick `k` of `n` things, in order unless
+/// `unordered`, with both truncated. It is NaN unless `0 <= k <= n` and `n`
+/// is finite. The product is built a factor at a time, dividing as it goes
+/// when unordered, so that it only overflows when the result would, and
+/// stops there rather than going on multiplying infinity.
+fn arrangements<N: CalcNum>(n: N, k: N, unordered: bool) -> N {
+    let (n, k) = (n.trunc(), k.trunc());
+    if !(N::zero() <= k && k <= n && n.to_f64().is_finite()) {
+        return N::nan();
+    }
+    // picking `k` unordered is the same as leaving out the other `n - k`
+    let k = if unordered { k.min(n - k) } else { k };
+    let mut total = N::one();
+    let mut step = N::one();
+    while step <= k && total.to_f64().is_finite() {
+        total = total * (n - k + step);
+        if unordered {
+            total = total / step;
+        }
+        step = step + N::one();
+    }
+    total
+}
+
+/// The remainder of dividing `value` by `divisor`, whose sign is chosen by
+/// `mode`: 0 truncates the quotient, giving the sign of `value`; 1 floors
+/// it, giving the sign of `divisor`; and 2 is Euclidean, never negative.
+/// Any other mode gives NaN.
+fn remainder<N: CalcNum>(value: N, divisor: N, mode: N) -> N {
+    let truncated = value.rem(divisor);
+    let is_negative = truncated < N::zero();
+    if mode == N::zero() {
+        truncated
+    } else if mode == N::one() {
+        if truncated != N::zero() && is_negative != (divisor < N::zero()) { truncated + divisor } else { truncated }
+    } else if mode == N::from_f64(2.0) {
+        if is_negative { truncated + divisor.abs() } else { truncated }
+    } else {
+        N::nan()
+    }
+}
+
+/// 0 below `edge` and 1 from it on, as the shader function is.
+fn step<N: CalcNum>(edge: N, x: N) -> N {
+    if edge.is_nan() || x.is_nan() {
+        N::nan()
+    } else if x < edge {
+        N::zero()
+    } else {
+        N::one()
+    }
+}
+
+/// Eases from 0 at `low` to 1 at `high` along the Hermite curve `3t² - 2t³`,
+/// holding at 0 and 1 outside them, as the shader function does.
+fn smoothstep<N: CalcNum>(low: N, high: N, x: N) -> N {
+    let t = (x - low) / (high - low);
+    if t.is_nan() {
+        return N::nan();
+    }
+    let t = t.max(N::zero()).min(N::one());
+    t * t * (N::from_f64(3.0) - N::from_f64(2.0) * t)
+}
+
+/// The value `percent` percent of the way through `data` when sorted,
+/// interpolating linearly between the two values either side of it. A
+/// percentage outside `[0, 100]` or NaN among the data gives NaN.
+#[cfg(feature = "stats")]
+fn percentile<N: CalcNum>(percent: N, data: &[N]) -> N {
+    if !(N::zero()..=N::from_f64(100.0)).contains(&percent) || data.iter().any(|value| value.to_f64().is_nan()) {
+        return N::nan();
+    }
+    let mut sorted = data.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let rank = percent / N::from_f64(100.0) * N::from_f64((sorted.len() - 1) as f64);
+    let below = rank.floor();
+    let index = below.to_f64() as usize;
+    match sorted.get(index + 1) {
+        Some(&above) => sorted[index] + (above - sorted[index]) * (rank - below),
+        None => sorted[index],
+    }
+}
+
+pub(crate) enum VariedFunction {
+    #[cfg(feature = "stats")]
+    min,
+    #[cfg(feature = "stats")]
+    max,
+    #[cfg(feature = "stats")]
+    avg,
+    #[cfg(feature = "stats")]
+    percentile,
+    floorto, ceilto,
+    fmod,
+    choose, perm,
+    step, smoothstep,
 }
 
 impl VariedFunction {
-    fn from_identifier(content: &str) -> Option<Self> {
+    pub(crate) fn from_identifier(content: &str) -> Option<Self> {
         use VariedFunction::*;
         match content {
+            #[cfg(feature = "stats")]
             "min" => Some(min),
+            #[cfg(feature = "stats")]
             "max" => Some(max),
+            #[cfg(feature = "stats")]
             "avg" => Some(avg),
+            #[cfg(feature = "stats")]
+            "percentile" => Some(percentile),
+            "floorto" => Some(floorto),
+            "ceilto" => Some(ceilto),
+            "fmod" => Some(fmod),
+            "choose" => Some(choose),
+            "perm" => Some(perm),
+            "step" => Some(step),
+            "smoothstep" => Some(smoothstep),
             _ => None
         }
     }
 
-    fn call(self) -> fn(Vec<f32>) -> f32 {
+    fn name(&self) -> &'static str {
         use VariedFunction::*;
         match self {
-            min => |values| values.iter().fold(f32::MAX, |a, b| a.min(*b)),
-            max => |values| values.iter().fold(f32::MIN, |a, b| a.max(*b)),
-            avg => |values| values.iter().sum::<f32>() / values.len() as f32,
+            #[cfg(feature = "stats")]
+            min => "min",
+            #[cfg(feature = "stats")]
+            max => "max",
+            #[cfg(feature = "stats")]
+            avg => "avg",
+            #[cfg(feature = "stats")]
+            percentile => "percentile",
+            floorto => "floorto",
+            ceilto => "ceilto",
+            fmod => "fmod",
+            choose => "choose",
+            perm => "perm",
+            step => "step",
+            smoothstep => "smoothstep",
         }
     }
+
+    /// The fewest and, when bounded, the most arguments the function accepts.
+    pub(crate) fn arity(&self) -> (u32, Option<u32>) {
+        use VariedFunction::*;
+        match self {
+            #[cfg(feature = "stats")]
+            min | max | avg => (1, None),
+            #[cfg(feature = "stats")]
+            percentile => (2, None),
+            floorto | ceilto | choose | perm | step => (2, Some(2)),
+            smoothstep => (3, Some(3)),
+            fmod => (2, Some(3)),
+        }
+    }
+
+    /// Where the values the function aggregates begin among its arguments,
+    /// if it aggregates them.
+    pub(crate) fn aggregates_from(&self) -> Option<usize> {
+        match self {
+            #[cfg(feature = "stats")]
+            Self::min | Self::max | Self::avg => Some(0),
+            #[cfg(feature = "stats")]
+            Self::percentile => Some(1),
+            _ => None,
+        }
+    }
+
+    /// The function, which expects at least as many values as its arity,
+    /// failing where it has no value, as when `fmod` divides by zero.
+    pub(crate) fn call<N: CalcNum>(self) -> fn(&[N]) -> Result<N> {
+        use VariedFunction::*;
+        match self {
+            #[cfg(feature = "stats")]
+            min => |values| Ok(values.iter().copied().reduce(N::min).unwrap()),
+            #[cfg(feature = "stats")]
+            max => |values| Ok(values.iter().copied().reduce(N::max).unwrap()),
+            #[cfg(feature = "stats")]
+            avg => |values| Ok(values.iter().fold(N::zero(), |a, b| a + *b) / N::from_f64(values.len() as f64)),
+            #[cfg(feature = "stats")]
+            percentile => |values| Ok(self::percentile(values[0], &values[1..])),
+            floorto => |values| Ok(to_multiple(values[0], values[1], N::floor)),
+            ceilto => |values| Ok(to_multiple(values[0], values[1], N::ceil)),
+            fmod => |values| match values[1] == N::zero() {
+                true => Err(CalcError::division_by_zero),
+                false => Ok(remainder(values[0], values[1], values.get(2).copied().unwrap_or_else(N::zero))),
+            },
+            choose => |values| Ok(arrangements(values[0], values[1], true)),
+            perm => |values| Ok(arrangements(values[0], values[1], false)),
+            step => |values| Ok(self::step(values[0], values[1])),
+            smoothstep => |values| Ok(self::smoothstep(values[0], values[1], values[2])),
+        }
+    }
+}
+
+/// An indexed `sum` or `prod`, evaluating `body` once for each whole step
+/// of `index` between the two preceding values.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Series<N = Number> {
+    pub index: String,
+    pub body: Vec<ExprNode<N>>,
+    pub product: bool,
 }
 
-pub enum ExprNode {
-    value(f32),
-    cast(Cast),
-    tie(Tie),
-    knot(Knot),
+/// One step of a parsed expression in postfix order. Nodes are plain data,
+/// naming the functions they call so that they can be stored and later
+/// resolved with `link`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExprNode<N = Number> {
+    value(N),
+    load(String),
+    unary(Function),
+    binary(BinaryFunction),
+    /// Calls the list or registered function of that name with the given
+    /// number of arguments.
+    call(String, u32),
+    series(Series<N>),
+    /// Differentiates the unary function of that name numerically, taking
+    /// the point and, when there are two arguments, the step.
+    nderiv(String, u32),
+    /// Integrates the unary function of that name numerically, taking the
+    /// bounds and, when there are three arguments, the number of intervals.
+    integrate(String, u32),
     assign(String),
+    /// Assigns each variable the value its argument gives, giving the last.
+    assign_all(Vec<String>),
+    dup,
+    under(BinaryFunction),
 }
 
-impl ExprNode {
-    fn varied(function: VariedFunction, count: u32) -> Self {
-        Self::knot(Knot {action: function.call(), count})
+impl core::hash::Hash for ExprNode {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        core::mem::discriminant(self).hash(state);
+        match self {
+            // -0.0 equals 0.0, so they must hash alike
+            Self::value(value) => (value + 0.0).to_bits().hash(state),
+            Self::load(name) | Self::assign(name) => name.hash(state),
+            Self::unary(function) => function.hash(state),
+            Self::binary(function) | Self::under(function) => function.hash(state),
+            Self::call(name, count) | Self::nderiv(name, count) | Self::integrate(name, count) => (name, count).hash(state),
+            Self::series(series) => series.hash(state),
+            Self::assign_all(names) => names.hash(state),
+            Self::dup => (),
+        }
     }
 }
 
-enum StackNode {
-    function(Function),
-    binary_function(BinaryFunction),
-    varied_function(VariedFunction, u32),
-    section(Enclosure),
-    variable(String),
-    assign(String),
+impl core::hash::Hash for Series {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        (&self.index, &self.body, self.product).hash(state);
+    }
 }
 
-type Cause = fn(&Token) -> bool;
-type Effect = fn(&mut Context, &mut Yard, &Token) -> Result<()>;
+/// FNV-1a, which unlike std's hasher is available without std and gives the
+/// same hashes from one run to the next.
+struct Fnv(u64);
 
-struct Rule {
-    cause: Cause,
-    effect: Effect,
+impl core::hash::Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
 }
 
-impl Rule {
-    fn applies(&self, token: &Token) -> Option<Effect> {
-        if (self.cause)(token) {
-            Some(self.effect)
-        } else {
-            None
+/// A hash of the structure of `expression`, so that the same expression
+/// hashes alike however it was spaced. Hashes are only comparable between
+/// builds of the crate for the same pointer width.
+pub fn canonical_hash(expression: &[ExprNode]) -> u64 {
+    use core::hash::{Hash, Hasher};
+
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    expression.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The variables `expression` reads when evaluated, by name in order. The
+/// indices of series are not counted within them.
+pub fn variables_used<N>(expression: &[ExprNode<N>]) -> Vec<String> {
+    let mut names = Vec::new();
+    for node in expression {
+        match node {
+            ExprNode::load(name) => names.push(name.clone()),
+            ExprNode::series(series) => names.extend(variables_used(&series.body).into_iter()
+                .filter(|name| *name != series.index)),
+            _ => (),
         }
     }
+    names.sort();
+    names.dedup();
+    names
 }
 
-const value_placing: Rule = Rule {
-    cause: |token| {
-        if let TokenKind::number = token.kind {
-            true
+/// A series whose argument list is still being read, along with where in
+/// the expression its body begins once the fourth argument is reached.
+struct OpenSeries {
+    product: bool,
+    index: String,
+    body: usize,
+}
+
+impl OpenSeries {
+    /// The index, the bounds and the body.
+    const arity: u32 = 4;
+
+    fn from_identifier(content: &str) -> Option<Self> {
+        let product = match content {
+            "sum" => false,
+            "prod" => true,
+            _ => return None
+        };
+        Some(Self {product, index: String::new(), body: 0})
+    }
+
+    fn name(&self) -> &'static str {
+        if self.product { "prod" } else { "sum" }
+    }
+}
+
+/// A `diff` whose argument list is still being read, along with where in
+/// the expression its body begins.
+struct OpenDerivative {
+    variable: String,
+    body: usize,
+}
+
+impl OpenDerivative {
+    /// The body and the variable.
+    const arity: u32 = 2;
+
+    fn from_identifier(content: &str) -> Option<Self> {
+        match content {
+            "diff" => Some(Self {variable: String::new(), body: 0}),
+            _ => None
+        }
+    }
+}
+
+/// A function whose first argument names a function of one argument for it
+/// to apply, rather than giving a value.
+struct OpenFunctional {
+    integral: bool,
+    function: String,
+}
+
+impl OpenFunctional {
+    fn from_identifier(content: &str) -> Option<Self> {
+        let integral = match content {
+            "nderiv" => false,
+            "integrate" => true,
+            _ => return None
+        };
+        Some(Self {integral, function: String::new()})
+    }
+
+    fn name(&self) -> &'static str {
+        if self.integral { "integrate" } else { "nderiv" }
+    }
+
+    /// The arguments it accepts, the function named first among them.
+    fn arity(&self) -> (u32, Option<u32>) {
+        if self.integral { (3, Some(4)) } else { (2, Some(3)) }
+    }
+
+    /// The node applying the function to the `count` arguments after it.
+    fn close<N>(self, count: u32) -> Result<ExprNode<N>> {
+        let (least, most) = self.arity();
+        if !(least..=most.unwrap_or(least)).contains(&(count + 1)) {
+            return Err(CalcError::wrong_argument_count(self.name().into(), count + 1));
+        }
+        Ok(if self.integral {
+            ExprNode::integrate(self.function, count)
         } else {
-            false
+            ExprNode::nderiv(self.function, count)
+        })
+    }
+}
+
+/// A function taking an argument list, whether built in or registered.
+struct Varied {
+    name: String,
+    arity: (u32, Option<u32>),
+}
+
+impl From<VariedFunction> for Varied {
+    fn from(function: VariedFunction) -> Self {
+        Self {
+            name: function.name().into(),
+            arity: function.arity(),
         }
-    },
-    effect: |context, yard, token| {
-        context.active_ruleset = ActiveRuleset::binding;
-        yard.expression.push(ExprNode::value(token.content.parse()
-            .map_err(|_| CalcError::invalid_number(token.content.clone()))? ));
-        Ok(())
     }
-};
-
-const operator_placing: Rule = Rule {
-    cause: |token| {
-        token.kind == TokenKind::operator
-    },
-    effect: |_context, yard, token| {
-        let operator = Function::from_operator(&token.content)?;
-        Ok(yard.stack.push(StackNode::function(operator)))
-    }
-};
-
-const paren_placing: Rule = Rule {
-    cause: |token| {
-        token.content == "("
-    },
-    effect: |context, yard, _token| {
-        yard.stack.push(StackNode::section(context.enclosure.clone()));
-        context.enclose(Enclosure::nested);
+}
+
+impl Varied {
+    fn check_arity(&self, count: u32) -> Result<()> {
+        check_arity(&self.name, self.arity, count)
+    }
+}
+
+/// Fails unless `count` lies within the fewest and, when bounded, the most
+/// arguments `name` accepts.
+pub(crate) fn check_arity(name: &str, (least, most): (u32, Option<u32>), count: u32) -> Result<()> {
+    if count < least || most.is_some_and(|most| count > most) {
+        Err(CalcError::wrong_argument_count(name.into(), count))
+    } else {
         Ok(())
     }
-};
+}
 
-const paren_binding: Rule = Rule {
-    cause: |token| {
-        token.content == ")"
-    },
-    effect: |context, yard, _token| {
-        while let Some(node) = yard.stack.pop() {
-            match node {
-                StackNode::section(enclosure) => {
-                    context.enclose(enclosure);
-                    break;
-                },
-                StackNode::function(node)  => yard.expression.push(node.into()),
-                StackNode::binary_function(node) => yard.expression.push(node.into()),
-                _ => (),
-            }
+impl<N> ExprNode<N> {
+    fn varied(function: Varied, count: u32) -> Self {
+        Self::call(function.name, count)
+    }
+
+    /// Whether the node always gives 1 or 0, standing for true or false.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn gives_truth(&self) -> bool {
+        matches!(self, Self::binary(function) if function.is_truth_valued())
+            || matches!(self, Self::unary(Function::isprime))
+    }
+}
+
+enum StackNode {
+    function(Function),
+    /// A registered unary function, applied without parentheses.
+    cast(String),
+    binary_function(BinaryFunction),
+    relation(BinaryFunction),
+    varied_function(Varied, u32),
+    series(OpenSeries, u32),
+    derivative(OpenDerivative, u32),
+    functional(OpenFunctional, u32),
+    section(Enclosure),
+    variable(Symbol),
+    assign(Symbol),
+    /// The variables before the `=` of a line assigning several.
+    targets(Vec<Symbol>),
+    /// The variables a line assigns, with how many values have begun.
+    assign_all(Vec<Symbol>, usize),
+}
+
+impl StackNode {
+    /// The name of the function this node calls with an argument list.
+    fn call_name(&self) -> Option<&str> {
+        match self {
+            Self::varied_function(function, _) => Some(&function.name),
+            Self::series(series, _) => Some(series.name()),
+            Self::derivative(..) => Some("diff"),
+            Self::functional(functional, _) => Some(functional.name()),
+            _ => None,
         }
-        Ok(())
     }
-};
-
-const operator_binding: Rule = Rule {
-    cause: |token| {
-        token.kind == TokenKind::operator
-    },
-    effect: |context, yard, token| {
-        context.active_ruleset = ActiveRuleset::placing;
-        let operator = BinaryFunction::from_operator(&token.content)?;
-        let precedence = operator.precedence();
-        while let Some(node) = yard.pop_preceding(&precedence) {
-            yard.expression.push(node)
-        }
-        Ok(yard.stack.push(StackNode::binary_function(operator)))
-    }
-};
-
-const identifier_placing: Rule = Rule {
-    cause: |token| {
-        token.kind == TokenKind::identifier
-    },
-    effect: |context, yard, token| {
-        if let Some(constant) = context.constants.get(&token.content) {
+}
+
+/// Where a line may assign to a variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Assignment {
+    /// Only as the whole line, as in `x = 5`.
+    statement,
+    /// Also inside parentheses and after another assignment, giving the
+    /// value assigned, as in `(x = 5) + 1` or `x = y = 5`.
+    expression,
+}
+
+impl Assignment {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "statement" => Some(Self::statement),
+            "expression" => Some(Self::expression),
+            _ => None
+        }
+    }
+}
+
+/// Whether `name` is taken by a built-in function or constant.
+pub(crate) fn is_builtin(name: &str) -> bool {
+    Function::from_identifier(name).is_some()
+        || VariedFunction::from_identifier(name).is_some()
+        || OpenSeries::from_identifier(name).is_some()
+        || OpenDerivative::from_identifier(name).is_some()
+        || OpenFunctional::from_identifier(name).is_some()
+        || builtin_constant::<Number>(name).is_some()
+}
+
+/// The arithmetic behind a binary operator symbol such as `+` or `<=`.
+/// Unlike the operator in an expression, `/` never fails: dividing by zero
+/// gives an infinity or NaN rather than `division_by_zero`.
+pub fn binary_op<N: CalcNum>(symbol: &str) -> Option<fn(N, N) -> N> {
+    BinaryFunction::from_operator(symbol).ok().map(BinaryFunction::call)
+}
+
+/// The arithmetic behind a prefix operator symbol such as `-`.
+pub fn unary_op<N: CalcNum>(symbol: &str) -> Option<fn(N) -> N> {
+    Function::from_operator(symbol).ok().map(Function::call)
+}
+
+/// Identifier bound to the result of the previous successful line.
+pub const previous_result: &str = "_";
+
+/// The feature providing `name`, when it is a built-in function of a
+/// family this build leaves out.
+fn missing_feature(name: &str) -> Option<&'static str> {
+    match name {
+        "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "todeg" | "torad" if !cfg!(feature = "trig") => Some("trig"),
+        "min" | "max" | "avg" | "percentile" if !cfg!(feature = "stats") => Some("stats"),
+        _ => None,
+    }
+}
+
+/// `error` for `name`, which is not the function it should be, unless it is
+/// one this build leaves out.
+fn not_function(name: String, error: fn(String) -> CalcError) -> CalcError {
+    match missing_feature(&name) {
+        Some(feature) => CalcError::not_available(name, feature),
+        None => error(name),
+    }
+}
+
+/// The error for a name that refers to nothing.
+pub(crate) fn undefined(identifier: String) -> CalcError {
+    if identifier == previous_result {
+        CalcError::no_previous_result
+    } else if let Some(feature) = missing_feature(&identifier) {
+        CalcError::not_available(identifier, feature)
+    } else {
+        CalcError::undefined(identifier)
+    }
+}
+
+type Cause = fn(&Token) -> bool;
+type Effect<N> = fn(&mut Context<N>, &mut Yard<N>, Token) -> Result<()>;
+
+#[derive(Clone, Copy)]
+struct Rule<N> {
+    /// The name the rule is traced by.
+    #[cfg_attr(not(feature = "trace"), allow(dead_code))]
+    name: &'static str,
+    cause: Cause,
+    effect: Effect<N>,
+}
+
+/// Reads a number literal: decimal, hexadecimal after `0x` or binary
+/// after `0b`, with any `_` separating its digits left out.
+fn parse_literal<N: CalcNum>(literal: &str) -> Option<N> {
+    if literal.contains('_') {
+        return parse_literal(&literal.replace('_', ""));
+    }
+    match literal_radix(literal) {
+        Some(radix) => u64::from_str_radix(&literal[2..], radix).ok().map(|value| N::from_f64(value as f64)),
+        None => N::parse(literal),
+    }
+}
+
+/// The rules, as associated constants so that their effects can be
+/// generic over the number type.
+impl<N: CalcNum> Rule<N> {
+    const value_placing: Self = Rule {
+        name: "value_placing",
+        cause: |token| {
+            token.kind == TokenKind::number
+        },
+        effect: |context, yard, token| {
             context.active_ruleset = ActiveRuleset::binding;
-            Ok(yard.expression.push(ExprNode::value(*constant)))
-        } else if let Some(variable) = context.variables.get(&token.content) {
+            yard.expression.push(ExprNode::value(parse_literal(&token.content)
+                .ok_or_else(|| CalcError::invalid_number(token.content.into_owned()))? ));
+            Ok(())
+        }
+    };
+
+    const operator_placing: Self = Rule {
+        name: "operator_placing",
+        cause: |token| {
+            token.kind == TokenKind::operator
+        },
+        effect: |_context, yard, token| {
+            let operator = Function::from_operator(&token.content)?;
+            Ok(yard.stack.push(StackNode::function(operator)))
+        }
+    };
+
+    const paren_placing: Self = Rule {
+        name: "paren_placing",
+        cause: |token| {
+            token.content == "("
+        },
+        effect: |context, yard, _token| {
+            yard.stack.push(StackNode::section(context.enclosure.clone()));
+            context.enclose(Enclosure::nested);
+            context.assignable = context.assignment == Assignment::expression;
+            Ok(())
+        }
+    };
+
+    const paren_binding: Self = Rule {
+        name: "paren_binding",
+        cause: |token| {
+            token.content == ")"
+        },
+        effect: |context, yard, _token| {
+            while let Some(node) = yard.stack.pop() {
+                match node {
+                    StackNode::section(enclosure) => {
+                        context.enclose(enclosure);
+                        // a function written with parentheses applies to
+                        // them alone, so `sin(x)^2` squares the sine
+                        match yard.stack.last() {
+                            Some(StackNode::function(Function::positive | Function::negative)) => (),
+                            Some(StackNode::function(_) | StackNode::cast(_)) => {
+                                if let Some(node) = yard.pop_preceding(&Precedence::low) {
+                                    yard.expression.push(node);
+                                }
+                            },
+                            _ => (),
+                        }
+                        break;
+                    },
+                    StackNode::function(node)  => yard.expression.push(node.into()),
+                    StackNode::cast(name) => yard.expression.push(ExprNode::call(name, 1)),
+                    StackNode::binary_function(node) | StackNode::relation(node) => yard.expression.push(node.into()),
+                    StackNode::assign(identifier) => yard.expression.push(ExprNode::assign(context.symbols.name(identifier).into())),
+                    _ => (),
+                }
+            }
+            Ok(())
+        }
+    };
+
+    const operator_binding: Self = Rule {
+        name: "operator_binding",
+        cause: |token| {
+            token.kind == TokenKind::operator
+        },
+        effect: |context, yard, token| {
+            context.active_ruleset = ActiveRuleset::placing;
+            let operator = BinaryFunction::from_operator(&token.content)?;
+            let precedence = operator.precedence();
+            if precedence == Precedence::relation {
+                return Ok(yard.push_relation(operator));
+            }
+            while let Some(node) = yard.pop_preceding(&precedence) {
+                yard.expression.push(node)
+            }
+            Ok(yard.stack.push(StackNode::binary_function(operator)))
+        }
+    };
+
+    const pipe_binding: Self = Rule {
+        name: "pipe_binding",
+        cause: |token| {
+            token.content == "|>"
+        },
+        effect: |context, yard, _token| {
+            context.active_ruleset = ActiveRuleset::placing;
+            while let Some(node) = yard.pop_preceding(&Precedence::pipe) {
+                yard.expression.push(node)
+            }
+            Ok(context.placing.push(vec![Self::pipe_placing]))
+        }
+    };
+
+    /// Reads the function a `|>` feeds its left side to, which must take
+    /// a single argument.
+    const pipe_placing: Self = Rule {
+        name: "pipe_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            let function = match token.kind {
+                TokenKind::identifier => context.function(&token.content),
+                _ => None,
+            };
+            let node = match function {
+                Some(StackNode::function(function)) => ExprNode::unary(function),
+                Some(StackNode::cast(name)) => ExprNode::call(name, 1),
+                _ => return Err(not_function(token.content.into_owned(), CalcError::invalid_pipe_target)),
+            };
+            context.placing.pop();
             context.active_ruleset = ActiveRuleset::binding;
-            Ok(yard.expression.push(ExprNode::value(*variable)))
-        } else if let Some(function) = Function::from_identifier(&token.content) {
-            Ok(yard.stack.push(StackNode::function(function)))
-        } else if let Some(function) = VariedFunction::from_identifier(&token.content) {
-            context.placing.push(vec![list_placing]);
-            Ok(yard.stack.push(StackNode::varied_function(function, 0)))
-        } else {
-            Err(CalcError::undefined(token.content.clone()))
+            Ok(yard.expression.push(node))
         }
-    }
-};
+    };
 
-const list_placing: Rule = Rule {
-    cause: |_token| {
-        true
-    },
-    effect: |context, yard, token| {
-        if token.content != "(" {
-            Err(CalcError::did_not_expect(token.content.clone()))
-        } else {
+    const identifier_placing: Self = Rule {
+        name: "identifier_placing",
+        cause: |token| {
+            token.kind == TokenKind::identifier
+        },
+        effect: |context, yard, token| {
+            if context.is_bound(&token.content) || context.defers(&token.content) {
+                context.active_ruleset = ActiveRuleset::binding;
+                Ok(yard.expression.push(ExprNode::load(token.content.into_owned())))
+            } else if let Some(constant) = context.constant(&token.content) {
+                context.active_ruleset = ActiveRuleset::binding;
+                Ok(yard.expression.push(ExprNode::value(constant)))
+            } else if let Some(variable) = context.variables.get(token.content.as_ref()) {
+                context.active_ruleset = ActiveRuleset::binding;
+                Ok(yard.expression.push(ExprNode::value(*variable)))
+            } else if let Some(function) = context.function(&token.content) {
+                Ok(context.place_function(yard, function))
+            } else {
+                let identifier = context.symbols.intern(&token.content);
+                let node = context.read_on(identifier)?;
+                context.active_ruleset = ActiveRuleset::binding;
+                Ok(yard.expression.push(node))
+            }
+        }
+    };
+
+    const list_placing: Self = Rule {
+        name: "list_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            if token.content != "(" {
+                Err(CalcError::did_not_expect(token.content.into_owned()))
+            } else {
+                context.placing.reset();
+                yard.stack.push(StackNode::section(context.enclosure.clone()));
+                Ok(context.enclose(Enclosure::listed))
+            }
+        }
+    };
+
+    const series_placing: Self = Rule {
+        name: "series_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            (Self::list_placing.effect)(context, yard, token)?;
+            Ok(context.placing.push(vec![Self::index_placing]))
+        }
+    };
+
+    const index_placing: Self = Rule {
+        name: "index_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            if token.kind != TokenKind::identifier {
+                return Err(CalcError::did_not_expect(token.content.into_owned()));
+            }
+            let index = token.content.into_owned();
+            let section = yard.stack.pop();
+            if let Some(StackNode::series(series, _)) = yard.stack.last_mut() {
+                series.index = index.clone();
+            }
+            yard.stack.extend(section);
+            context.bound.push(context.symbols.intern(&index));
             context.placing.reset();
-            context.enclose(Enclosure::listed);
-            Ok(yard.stack.push(StackNode::section(context.enclosure.clone())))
+            Ok(context.active_ruleset = ActiveRuleset::binding)
         }
-    }
-};
+    };
 
-const arg_binding: Rule = Rule {
-    cause: |token| {
-        token.content == ","
-    },
-    effect: |context, yard, _token| {
-        context.active_ruleset = ActiveRuleset::placing;
-        while let Some(node) = yard.stack.pop() {
-            match node {
-                StackNode::section(enclosure) => {
-                    if let Some(StackNode::varied_function(function, count)) = yard.stack.pop() {
-                        yard.stack.push(StackNode::varied_function(function, count + 1));
+    const functional_placing: Self = Rule {
+        name: "functional_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            (Self::list_placing.effect)(context, yard, token)?;
+            Ok(context.placing.push(vec![Self::reference_placing]))
+        }
+    };
+
+    const reference_placing: Self = Rule {
+        name: "reference_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            if token.kind != TokenKind::identifier {
+                return Err(CalcError::did_not_expect(token.content.into_owned()));
+            }
+            match context.function(&token.content) {
+                Some(StackNode::function(_) | StackNode::cast(_) | StackNode::varied_function(..)) => (),
+                _ => return Err(not_function(token.content.into_owned(), CalcError::not_a_function)),
+            }
+            let section = yard.stack.pop();
+            if let Some(StackNode::functional(functional, _)) = yard.stack.last_mut() {
+                functional.function = token.content.into_owned();
+            }
+            yard.stack.extend(section);
+            context.placing.reset();
+            Ok(context.active_ruleset = ActiveRuleset::binding)
+        }
+    };
+
+    const variable_placing: Self = Rule {
+        name: "variable_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            if token.kind != TokenKind::identifier {
+                return Err(CalcError::did_not_expect(token.content.into_owned()));
+            }
+            let section = yard.stack.pop();
+            if let Some(StackNode::derivative(derivative, _)) = yard.stack.last_mut() {
+                derivative.variable = token.content.into_owned();
+            }
+            yard.stack.extend(section);
+            context.placing.reset();
+            Ok(context.active_ruleset = ActiveRuleset::binding)
+        }
+    };
+
+    const arg_binding: Self = Rule {
+        name: "arg_binding",
+        cause: |token| {
+            token.content == "," || token.content == ";"
+        },
+        effect: |context, yard, _token| {
+            context.active_ruleset = ActiveRuleset::placing;
+            while let Some(node) = yard.stack.pop() {
+                match node {
+                    StackNode::section(enclosure) => {
+                        match yard.stack.last_mut() {
+                            Some(StackNode::varied_function(_, count)) => *count += 1,
+                            Some(StackNode::series(series, count)) => {
+                                *count += 1;
+                                if *count == 3 {
+                                    series.body = yard.expression.len();
+                                }
+                            },
+                            Some(StackNode::functional(_, count)) => *count += 1,
+                            Some(StackNode::derivative(_, count)) => {
+                                *count += 1;
+                                if *count == 1 {
+                                    context.placing.push(vec![Rule::variable_placing]);
+                                }
+                            },
+                            _ => (),
+                        }
                         yard.stack.push(StackNode::section(enclosure));
-                    }
-                    break;
-                },
-                StackNode::function(node)  => yard.expression.push(node.into()),
-                StackNode::binary_function(node) => yard.expression.push(node.into()),
-                _ => (),
+                        break;
+                    },
+                    StackNode::function(node)  => yard.expression.push(node.into()),
+                    StackNode::cast(name) => yard.expression.push(ExprNode::call(name, 1)),
+                    StackNode::binary_function(node) | StackNode::relation(node) => yard.expression.push(node.into()),
+                    _ => (),
+                }
             }
+            Ok(())
         }
-        Ok(())
-    }
-};
+    };
 
-const list_binding: Rule = Rule {
-    cause: |token| {
-        token.content == ")"
-    },
-    effect: |_context, yard, _token| {
-        while let Some(node) = yard.stack.pop() {
-            match node {
-                StackNode::section(_) => {
-                    if let Some(StackNode::varied_function(function, count)) = yard.stack.pop() {
-                        yard.expression.push(ExprNode::varied(function, count + 1));
-                    }
-                    break;
-                },
-                StackNode::function(node)  => yard.expression.push(node.into()),
-                StackNode::binary_function(node) => yard.expression.push(node.into()),
-                _ => (),
+    const list_binding: Self = Rule {
+        name: "list_binding",
+        cause: |token| {
+            token.content == ")"
+        },
+        effect: |context, yard, _token| {
+            while let Some(node) = yard.stack.pop() {
+                match node {
+                    StackNode::section(enclosure) => {
+                        context.enclose(enclosure);
+                        match yard.stack.pop() {
+                            Some(StackNode::varied_function(function, count)) => {
+                                function.check_arity(count + 1)?;
+                                yard.expression.push(ExprNode::varied(function, count + 1));
+                            },
+                            Some(StackNode::series(series, count)) => {
+                                context.bound.pop();
+                                if count + 1 != OpenSeries::arity {
+                                    return Err(CalcError::wrong_argument_count(series.name().into(), count + 1));
+                                }
+                                let body = yard.expression.split_off(series.body);
+                                yard.expression.push(ExprNode::series(Series {
+                                    index: series.index,
+                                    body,
+                                    product: series.product,
+                                }));
+                            },
+                            Some(StackNode::functional(functional, count)) => {
+                                yard.expression.push(functional.close(count)?);
+                            },
+                            Some(StackNode::derivative(derivative, count)) => {
+                                context.differentiating -= 1;
+                                if count + 1 != OpenDerivative::arity {
+                                    return Err(CalcError::wrong_argument_count("diff".into(), count + 1));
+                                }
+                                let body = yard.expression.split_off(derivative.body);
+                                yard.expression.extend(differentiate(&body, &derivative.variable)?);
+                            },
+                            Some(node) => yard.stack.push(node),
+                            None => (),
+                        }
+                        break;
+                    },
+                    StackNode::function(node)  => yard.expression.push(node.into()),
+                    StackNode::cast(name) => yard.expression.push(ExprNode::call(name, 1)),
+                    StackNode::binary_function(node) | StackNode::relation(node) => yard.expression.push(node.into()),
+                    _ => (),
+                }
             }
+            Ok(())
         }
-        Ok(())
-    }
-};
+    };
 
-const assign_placing: Rule = Rule {
-    cause: |token| {
-        token.kind == TokenKind::identifier
-    },
-    effect: |context, yard, token| {
-        if let Some(constant) = context.constants.get(&token.content) {
-            context.active_ruleset = ActiveRuleset::binding;
-            Ok(yard.expression.push(ExprNode::value(*constant)))
-        } else if let Some(function) = Function::from_identifier(&token.content) {
-            Ok(yard.stack.push(StackNode::function(function)))
-        } else if let Some(function) = VariedFunction::from_identifier(&token.content) {
-            context.placing.push(vec![list_placing]);
-            Ok(yard.stack.push(StackNode::varied_function(function, 0)))
-        } else {
-            context.active_ruleset = ActiveRuleset::binding;
-            context.binding.push(vec![assign_binding]);
-            Ok(yard.stack.push(StackNode::variable(token.content.clone())))
+    const assign_placing: Self = Rule {
+        name: "assign_placing",
+        cause: |token| {
+            token.kind == TokenKind::identifier
+        },
+        effect: |context, yard, token| {
+            let name = token.content.as_ref();
+            if context.is_bound(name) || context.defers(name) && !context.was_assigned(name) {
+                (Self::identifier_placing.effect)(context, yard, token)
+            } else if let Some(constant) = context.constant(&token.content) {
+                context.active_ruleset = ActiveRuleset::binding;
+                Ok(yard.expression.push(ExprNode::value(constant)))
+            } else if let Some(function) = context.function(&token.content) {
+                Ok(context.place_function(yard, function))
+            } else {
+                context.active_ruleset = ActiveRuleset::binding;
+                context.binding.push(vec![Self::assign_binding]);
+                Ok(yard.stack.push(StackNode::variable(context.symbols.intern(&token.content))))
+            }
         }
-    }
-};
+    };
 
-const assign_binding: Rule = Rule {
-    cause: |token| {
-        token.kind == TokenKind::operator
-    },
-    effect: |context, yard, token| {
-        if let Some(StackNode::variable(identifier)) = yard.stack.pop() {
-            if token.content == "=" {
+    const assign_binding: Self = Rule {
+        name: "assign_binding",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            let Some(StackNode::variable(identifier)) = yard.stack.pop() else {
+                return Err(CalcError::invalid_assignment_target);
+            };
+            context.binding.pop();
+            let listing = matches!(yard.stack.last(), Some(StackNode::targets(_)));
+            if token.content == "," && (listing || yard.stack.is_empty() && context.enclosure == Enclosure::open) {
+                // the first of several variables assigned at once
                 context.active_ruleset = ActiveRuleset::placing;
-                yard.stack.push(StackNode::assign(identifier));
-                Ok(context.binding.reset())
-            } else if let Some(value) = context.variables.get(&identifier) {
-                yard.expression.push(ExprNode::value(*value));
-                (operator_binding.effect)(context, yard, token)
+                context.placing.push(vec![Self::target_placing]);
+                match yard.stack.last_mut() {
+                    Some(StackNode::targets(targets)) => targets.push(identifier),
+                    _ => yard.stack.push(StackNode::targets(vec![identifier])),
+                }
+                Ok(())
+            } else if token.content == "=" && listing {
+                let Some(StackNode::targets(mut targets)) = yard.stack.pop() else {
+                    unreachable!("the targets were just seen");
+                };
+                targets.push(identifier);
+                context.active_ruleset = ActiveRuleset::placing;
+                Ok(yard.stack.push(StackNode::assign_all(targets, 1)))
+            } else if listing {
+                Err(CalcError::could_not_find("=".into()))
+            } else if token.content == "=" {
+                context.active_ruleset = ActiveRuleset::placing;
+                if context.assignment == Assignment::expression {
+                    context.assignable = true;
+                    context.assigned.push(identifier);
+                }
+                Ok(yard.stack.push(StackNode::assign(identifier)))
             } else {
-                Err(CalcError::undefined(identifier))
+                // not an assignment after all, so the token binds as usual
+                yard.expression.push(context.read_on(identifier)?);
+                context.apply(yard, token)
             }
-        } else {
-            panic!("Expected variable at top of stack");
         }
-    }
-};
+    };
+    /// Reads a variable after the first of several assigned at once.
+    const target_placing: Self = Rule {
+        name: "target_placing",
+        cause: |_token| {
+            true
+        },
+        effect: |context, yard, token| {
+            let name = token.content.as_ref();
+            if token.kind != TokenKind::identifier || context.constant(name).is_some() || context.function(name).is_some() {
+                return Err(CalcError::invalid_assignment_target);
+            }
+            context.placing.pop();
+            context.active_ruleset = ActiveRuleset::binding;
+            context.binding.push(vec![Self::assign_binding]);
+            Ok(yard.stack.push(StackNode::variable(context.symbols.intern(name))))
+        }
+    };
+
+    /// Ends one of the values of a line assigning several at once.
+    const value_binding: Self = Rule {
+        name: "value_binding",
+        cause: |token| {
+            token.content == ","
+        },
+        effect: |context, yard, token| {
+            if context.enclosure != Enclosure::open || !matches!(yard.stack.first(), Some(StackNode::assign_all(..))) {
+                return Err(CalcError::did_not_expect(token.content.into_owned()));
+            }
+            context.active_ruleset = ActiveRuleset::placing;
+            while yard.stack.len() > 1 {
+                yard.pop_onto_expression(context)?;
+            }
+            if let Some(StackNode::assign_all(_, values)) = yard.stack.last_mut() {
+                *values += 1;
+            }
+            Ok(())
+        }
+    };
+}
 
-struct Ruleset {
-    rules: Vec<Vec<Rule>>,
+struct Ruleset<N> {
+    rules: Vec<Vec<Rule<N>>>,
 }
 
-impl Ruleset {
+impl<N: CalcNum> Ruleset<N> {
     fn placing() -> Self {
         Self {
             rules: vec![
                 vec![
-                    value_placing,
-                    operator_placing,
-                    paren_placing,
-                    identifier_placing,
+                    Rule::value_placing,
+                    Rule::operator_placing,
+                    Rule::paren_placing,
+                    Rule::identifier_placing,
                 ],
-                vec![assign_placing],
             ]
         }
     }
@@ -466,28 +1704,39 @@ impl Ruleset {
         Self {
             rules: vec![
                 vec![
-                    operator_binding,
+                    Rule::pipe_binding,
+                    Rule::operator_binding,
+                    Rule::value_binding,
                 ]
             ]
         }
     }
 
-    fn applies(&self, token: &Token) -> Result<Effect> {
-        for rule in self.rules.iter().rev().flatten() {
-            if let Some(effect) = rule.applies(token) {
-                return Ok(effect);
-            }
-        }
-        Err(CalcError::did_not_expect(token.content.clone().into()))
+    fn applies(&self, token: &Token) -> Result<Rule<N>> {
+        self.rules.iter().rev().flatten()
+            .find(|rule| (rule.cause)(token))
+            .copied()
+            .ok_or_else(|| CalcError::did_not_expect(token.content.to_string()))
     }
 
     fn reset(&mut self) {
         self.rules.truncate(1);
     }
 
-    fn push(&mut self, rules: Vec<Rule>) {
+    fn push(&mut self, rules: Vec<Rule<N>>) {
         self.rules.push(rules);
     }
+
+    fn pop(&mut self) {
+        self.rules.pop();
+    }
+
+    /// Drops the rules at `level`, keeping any pushed on top of them.
+    fn remove(&mut self, level: usize) {
+        if level < self.rules.len() {
+            self.rules.remove(level);
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq)]
@@ -500,41 +1749,162 @@ enum Enclosure {
     open, nested, listed
 }
 
-struct Context<'a> {
-    placing: Ruleset,
-    binding: Ruleset,
+struct Context<'a, N> {
+    placing: &'a mut Ruleset<N>,
+    binding: &'a mut Ruleset<N>,
     active_ruleset: ActiveRuleset,
-    constants: HashMap<String, f32>,
-    variables: &'a mut HashMap<String, f32>,
+    variables: &'a mut Map<String, N>,
+    registry: &'a Registry<N>,
     enclosure: Enclosure,
+    /// Indices of the series being read, which resolve when evaluated.
+    bound: &'a mut Vec<Symbol>,
+    /// The value of any identifier that is not defined, if they are allowed.
+    fallback: Option<N>,
+    /// How many `diff` bodies are being read, in which variables resolve
+    /// when evaluated.
+    differentiating: u32,
+    assignment: Assignment,
+    late_binding: bool,
+    split_identifiers: bool,
+    /// Whether the next token may begin an assignment.
+    assignable: bool,
+    /// The variables assigned so far, which resolve when evaluated.
+    assigned: &'a mut Vec<Symbol>,
+    /// The first identifier read that is not defined, which is an error
+    /// once the line ends unless the line holds a misplaced '='.
+    undefined: &'a mut Option<String>,
+    symbols: &'a mut Symbols,
 }
 
-fn create_constants() -> HashMap<String, f32> {
-    HashMap::from([
-        ("pi".into(), std::f32::consts::PI),
-        ("e".into(), std::f32::consts::E)
-    ])
+/// The built-in constants, kept as `f64` so that one table serves every
+/// number type without being rebuilt for each line.
+pub(crate) const builtin_constants: &[(&str, f64)] = &[
+    ("pi", core::f64::consts::PI),
+    ("e", core::f64::consts::E),
+];
+
+fn builtin_constant<N: CalcNum>(name: &str) -> Option<N> {
+    builtin_constants.iter()
+        .find(|(constant, _)| *constant == name)
+        .map(|(_, value)| N::from_f64(*value))
 }
 
-impl<'a> Context<'a> {
-    fn new(variables: &'a mut HashMap<String, f32>) -> Self {
-        Self {
-            placing: Ruleset::placing(),
-            binding: Ruleset::binding(),
-            active_ruleset: ActiveRuleset::placing,
-            constants: create_constants(),
-            variables: variables,
-            enclosure: Enclosure::open,
+impl<'a, N: CalcNum> Context<'a, N> {
+    /// The value of the variable `name`, or the fallback when it is undefined.
+    fn variable(&self, name: &str) -> Result<N> {
+        self.variables.get(name).copied()
+            .or_else(|| self.registry.resolve(name))
+            .or_else(|| self.letters(name))
+            .or(self.fallback)
+            .ok_or_else(|| undefined(name.into()))
+    }
+
+    /// The product of the letters of `name` when splitting identifiers, if
+    /// it has several and each is a constant or a variable.
+    fn letters(&self, name: &str) -> Option<N> {
+        if !self.split_identifiers || name.chars().nth(1).is_none() {
+            return None;
+        }
+        name.chars().try_fold(N::one(), |product, letter| {
+            let letter = letter.encode_utf8(&mut [0; 4]).to_string();
+            let value = self.constant(&letter)
+                .or_else(|| self.variables.get(&letter).copied())
+                .or_else(|| self.registry.resolve(&letter))?;
+            Some(product * value)
+        })
+    }
+
+    /// Whether `name` is the index of a series being read.
+    fn is_bound(&self, name: &str) -> bool {
+        self.symbols.find(name).is_some_and(|index| self.bound.contains(&index))
+    }
+
+    /// Whether `name` is a variable to be read when evaluated rather than now.
+    fn defers(&self, name: &str) -> bool {
+        (self.differentiating > 0 || self.late_binding) && self.constant(name).is_none() && self.function(name).is_none()
+            || self.was_assigned(name)
+    }
+
+    /// Whether `name` was assigned earlier in the line, which is only
+    /// tracked when assignments are expressions.
+    fn was_assigned(&self, name: &str) -> bool {
+        self.symbols.find(name).is_some_and(|assigned| self.assigned.contains(&assigned))
+    }
+
+    /// The node reading the variable `name`, now or when evaluated.
+    fn read(&self, name: &str) -> Result<ExprNode<N>> {
+        if self.was_assigned(name) {
+            Ok(ExprNode::load(name.into()))
+        } else {
+            Ok(ExprNode::value(self.variable(name)?))
         }
     }
 
-    fn apply(&mut self, yard: &mut Yard, token: Token) -> Result<()> {
-        let effect = match self.active_ruleset.clone() {
+    /// Reads `identifier` like `read`, except that when assignments are
+    /// statements an undefined name reads as NaN until the line ends, as a
+    /// misplaced '=' later in the line is the error to report.
+    fn read_on(&mut self, identifier: Symbol) -> Result<ExprNode<N>> {
+        let name = self.symbols.name(identifier);
+        match self.read(name) {
+            Err(_) if self.assignment == Assignment::statement => {
+                self.undefined.get_or_insert_with(|| name.into());
+                Ok(ExprNode::value(N::nan()))
+            },
+            read => read,
+        }
+    }
+
+    /// Looks `name` up among the built-in constants, then the registered ones.
+    fn constant(&self, name: &str) -> Option<N> {
+        builtin_constant(name).or_else(|| self.registry.constant(name).copied())
+    }
+
+    /// Looks `name` up among the built-in functions, then the registered ones.
+    fn function(&self, name: &str) -> Option<StackNode> {
+        if let Some(function) = Function::from_identifier(name) {
+            Some(StackNode::function(function))
+        } else if let Some(function) = VariedFunction::from_identifier(name) {
+            Some(StackNode::varied_function(function.into(), 0))
+        } else if let Some(series) = OpenSeries::from_identifier(name) {
+            Some(StackNode::series(series, 0))
+        } else if let Some(derivative) = OpenDerivative::from_identifier(name) {
+            Some(StackNode::derivative(derivative, 0))
+        } else if let Some(functional) = OpenFunctional::from_identifier(name) {
+            Some(StackNode::functional(functional, 0))
+        } else {
+            match self.registry.function(name)? {
+                Callable::unary(_) => Some(StackNode::cast(name.into())),
+                callable => Some(StackNode::varied_function(Varied {
+                    name: name.into(),
+                    arity: callable.arity(),
+                }, 0)),
+            }
+        }
+    }
+
+    fn place_function(&mut self, yard: &mut Yard<N>, mut function: StackNode) {
+        match &mut function {
+            StackNode::varied_function(..) => self.placing.push(vec![Rule::list_placing]),
+            StackNode::series(..) => self.placing.push(vec![Rule::series_placing]),
+            StackNode::functional(..) => self.placing.push(vec![Rule::functional_placing]),
+            StackNode::derivative(derivative, _) => {
+                derivative.body = yard.expression.len();
+                self.differentiating += 1;
+                self.placing.push(vec![Rule::list_placing]);
+            },
+            _ => (),
+        }
+        yard.stack.push(function);
+    }
+
+    fn apply(&mut self, yard: &mut Yard<N>, token: Token) -> Result<()> {
+        let rule = match self.active_ruleset.clone() {
             ActiveRuleset::placing => self.placing.applies(&token),
             ActiveRuleset::binding => self.binding.applies(&token),
         }?;
 
-        effect(self, yard, &token)
+        trace_event!(rule = rule.name, token = %token.content, "applying rule");
+        (rule.effect)(self, yard, token)
     }
 
     fn enclose(&mut self, enclosure: Enclosure) {
@@ -542,21 +1912,62 @@ impl<'a> Context<'a> {
             self.placing.reset();
             self.binding.reset();
             if enclosure == Enclosure::nested {
-                self.binding.push(vec![paren_binding])
+                self.binding.push(vec![Rule::paren_binding])
             } else if enclosure == Enclosure::listed {
-                self.binding.push(vec![arg_binding, list_binding])
+                self.binding.push(vec![Rule::arg_binding, Rule::list_binding])
             }
             self.enclosure = enclosure;
         }
     }
 }
 
-struct Yard {
-    expression: Vec<ExprNode>,
+/// The id an identifier is interned as, which a parser's stack holds in
+/// its place.
+type Symbol = u32;
+
+/// The most identifiers a parser keeps interned between lines before it
+/// starts afresh.
+const most_symbols: usize = 4096;
+
+/// The identifiers a parser has read, each interned once so that its
+/// stack holds and compares their ids rather than copies of the names. The
+/// nodes it outputs still name them, as `pack` later numbers them again for
+/// evaluating.
+#[derive(Default)]
+struct Symbols {
+    /// Each name, shared with `ids` rather than allocated for both.
+    names: Vec<Rc<str>>,
+    ids: Map<Rc<str>, Symbol>,
+}
+
+impl Symbols {
+    fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(id) = self.find(name) {
+            return id;
+        }
+        let id = self.names.len() as Symbol;
+        let name = Rc::<str>::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+
+    /// The id of `name`, if it has been interned.
+    fn find(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).copied()
+    }
+
+    fn name(&self, id: Symbol) -> &str {
+        &self.names[id as usize]
+    }
+}
+
+struct Yard<N> {
+    expression: Vec<ExprNode<N>>,
     stack: Vec<StackNode>,
 }
 
-impl Yard {
+impl<N: CalcNum> Yard<N> {
     fn new() -> Self {
         Self {
             expression: Vec::new(),
@@ -564,11 +1975,18 @@ impl Yard {
         }
     }
 
-    fn get_preceding(&mut self, precedence: &Precedence) -> Option<ExprNode> {
+    fn clear(&mut self) {
+        self.expression.clear();
+        self.stack.clear();
+    }
+
+    fn get_preceding(&mut self, precedence: &Precedence) -> Option<ExprNode<N>> {
         if let Some(node) = self.stack.last() {
             match node {
                 StackNode::function(function) => function.preceding(precedence),
-                StackNode::binary_function(function) => function.preceding(precedence),
+                StackNode::cast(name) => Precedence::high.precedes(precedence)
+                    .then(|| ExprNode::call(name.clone(), 1)),
+                StackNode::binary_function(function) | StackNode::relation(function) => function.preceding(precedence),
                 _ => None
             }
         } else {
@@ -576,7 +1994,7 @@ impl Yard {
         }
     }
 
-    fn pop_preceding(&mut self, precedence: &Precedence) -> Option<ExprNode> {
+    fn pop_preceding(&mut self, precedence: &Precedence) -> Option<ExprNode<N>> {
         if let Some(node) = self.get_preceding(precedence) {
             self.stack.pop();
             Some(node)
@@ -585,40 +2003,385 @@ impl Yard {
         }
     }
 
-    pub fn finalize(&mut self, context: &Context) -> Result<()> {
+    /// Pushes a relational operator, desugaring a chain like `a < b < c` into
+    /// `a < b && b < c`. The shared operand `b` is duplicated rather than
+    /// recomputed, and the earlier relation is applied beneath the copy.
+    fn push_relation(&mut self, operator: BinaryFunction) {
+        while let Some(node) = self.pop_preceding(&Precedence::low) {
+            self.expression.push(node)
+        }
+        if let Some(StackNode::relation(..)) = self.stack.last() {
+            if let Some(StackNode::relation(previous)) = self.stack.pop() {
+                self.expression.push(ExprNode::dup);
+                self.expression.push(ExprNode::under(previous));
+                self.stack.push(StackNode::binary_function(BinaryFunction::and));
+            }
+        }
+        self.stack.push(StackNode::relation(operator));
+    }
+
+    pub fn finalize(&mut self, context: &Context<N>) -> Result<()> {
         if context.active_ruleset == ActiveRuleset::placing {
             return Err(CalcError::abrupt_end);
         }
-        while let Some(node) = self.stack.pop() {
-            match node {
-                StackNode::section{..} => return Err(CalcError::could_not_find(")".into())),
-                StackNode::function(function) => self.expression.push(function.into()),
-                StackNode::binary_function(function) => self.expression.push(function.into()),
-                StackNode::varied_function(..) => panic!("did not expect varied function"),
-                StackNode::variable(identifier) =>
-                    self.expression.push(
-                        ExprNode::value(*context.variables.get(&identifier)
-                            .ok_or_else(|| CalcError::undefined(identifier.clone()))?)),
-                StackNode::assign(identifier) => self.expression.push(ExprNode::assign(identifier)),
+        let unclosed = self.unclosed();
+        if !unclosed.is_empty() {
+            return Err(CalcError::unclosed_parentheses(unclosed));
+        }
+        while !self.stack.is_empty() {
+            self.pop_onto_expression(context)?;
+        }
+        Ok(())
+    }
+
+    /// What opened each section still on the stack, outermost first: `(`,
+    /// or the call whose arguments it holds, as `min(`.
+    fn unclosed(&self) -> Vec<String> {
+        let mut below = None;
+        let mut unclosed = Vec::new();
+        for node in &self.stack {
+            if let StackNode::section(_) = node {
+                unclosed.push(below.and_then(StackNode::call_name).unwrap_or("").to_string() + "(");
             }
+            below = Some(node);
+        }
+        unclosed
+    }
+
+    /// Moves the top of the stack onto the expression, as at the end of the
+    /// line, where anything left open is an error.
+    fn pop_onto_expression(&mut self, context: &Context<N>) -> Result<()> {
+        let Some(node) = self.stack.pop() else {
+            return Ok(());
+        };
+        match node {
+            // a call's arguments sit in a section above it
+            StackNode::section{..} => return Err(match self.stack.pop().as_ref().and_then(StackNode::call_name) {
+                Some(name) => CalcError::unclosed_function_call(name.into()),
+                None => CalcError::could_not_find(")".into()),
+            }),
+            StackNode::function(function) => self.expression.push(function.into()),
+            StackNode::cast(name) => self.expression.push(ExprNode::call(name, 1)),
+            StackNode::binary_function(function) | StackNode::relation(function) => self.expression.push(function.into()),
+            StackNode::varied_function(..) | StackNode::series(..) | StackNode::derivative(..) | StackNode::functional(..) =>
+                return Err(CalcError::internal("a call was left without its arguments".into())),
+            StackNode::variable(identifier) =>
+                self.expression.push(context.read(context.symbols.name(identifier))?),
+            StackNode::assign(identifier) => self.expression.push(ExprNode::assign(context.symbols.name(identifier).into())),
+            StackNode::targets(_) => return Err(CalcError::could_not_find("=".into())),
+            StackNode::assign_all(targets, values) if values != targets.len() =>
+                return Err(CalcError::assignment_arity_mismatch(targets.len(), values)),
+            StackNode::assign_all(targets, _) =>
+                self.expression.push(ExprNode::assign_all(targets.into_iter().map(|target| context.symbols.name(target).into()).collect())),
         }
         Ok(())
     }
 }
 
-pub fn parse<T: Iterator<Item = Result<Token>>>(scanner: T, variables: &mut HashMap<String, f32>) -> Result<Vec<ExprNode>> {
-    let mut yard = Yard::new();
-    let mut context = Context::new(variables);
+/// Parses line after line, keeping the buffers it works in, and the
+/// identifiers it has interned, so that later lines need not allocate them
+/// again. Nothing else carries over.
+pub struct Parser<N = Number> {
+    pub assignment: Assignment,
+    /// Whether variables are read when the expression is evaluated rather
+    /// than when it is parsed, so that it follows later changes to them.
+    pub late_binding: bool,
+    /// Whether an unknown identifier is read as its letters multiplied
+    /// together, so that `xy` is `x * y`.
+    pub split_identifiers: bool,
+    /// The most operators and parentheses a line may leave waiting at once,
+    /// as `((((` or `----` do.
+    pub max_nesting: Option<usize>,
+    /// The most tokens a line may be.
+    pub max_tokens: Option<usize>,
+    placing: Ruleset<N>,
+    binding: Ruleset<N>,
+    bound: Vec<Symbol>,
+    assigned: Vec<Symbol>,
+    undefined: Option<String>,
+    symbols: Symbols,
+    yard: Yard<N>,
+    progress: Progress<N>,
+}
 
-    let mut is_first_token = true;
-    for token in scanner {
-        context.apply(&mut yard, token?)?;
-        if is_first_token {
-            context.placing.reset();
-            is_first_token = false;
+/// Where a line stands between one token and the next.
+#[derive(Clone)]
+struct Progress<N> {
+    active_ruleset: ActiveRuleset,
+    enclosure: Enclosure,
+    fallback: Option<N>,
+    differentiating: u32,
+    assignable: bool,
+    tokens: usize,
+}
+
+/// How deeply a line may nest unless a parser is told otherwise, far past
+/// what anyone writes by hand.
+pub const default_max_nesting: usize = 10_000;
+
+/// How many tokens a line may be unless a parser is told otherwise.
+pub const default_max_tokens: usize = 1_000_000;
+
+impl<N: CalcNum> Default for Parser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: CalcNum> Parser<N> {
+    pub fn new() -> Self {
+        Self {
+            assignment: Assignment::statement,
+            late_binding: false,
+            split_identifiers: false,
+            max_nesting: Some(default_max_nesting),
+            max_tokens: Some(default_max_tokens),
+            placing: Ruleset::placing(),
+            binding: Ruleset::binding(),
+            bound: Vec::new(),
+            assigned: Vec::new(),
+            undefined: None,
+            symbols: Symbols::default(),
+            yard: Yard::new(),
+            progress: Progress {
+                active_ruleset: ActiveRuleset::placing,
+                enclosure: Enclosure::open,
+                fallback: None,
+                differentiating: 0,
+                assignable: true,
+                tokens: 0,
+            },
         }
     }
-    yard.finalize(&context)?;
 
-    Ok(yard.expression)
+    /// Parses like `parse_with`, lending out the expression until the next
+    /// parse. Callers keeping it should copy it with `to_vec`.
+    pub fn parse<T: Iterator<Item = Result<Token>>>(&mut self, scanner: T, variables: &mut Map<String, N>, registry: &Registry<N>) -> Result<&[ExprNode<N>]> {
+        self.run(scanner, variables, registry, None)?;
+        Ok(&self.yard.expression)
+    }
+
+    pub fn parse_line(&mut self, line: &str, variables: &mut Map<String, N>, registry: &Registry<N>) -> Result<&[ExprNode<N>]> {
+        self.parse(StringScanner::new(line.into()), variables, registry)
+    }
+
+    fn run<T: Iterator<Item = Result<Token>>>(&mut self, scanner: T, variables: &mut Map<String, N>, registry: &Registry<N>, fallback: Option<N>) -> Result<()> {
+        #[cfg(feature = "trace")]
+        let span = tracing::debug_span!("parse", tokens = tracing::field::Empty, nodes = tracing::field::Empty).entered();
+        #[cfg(feature = "trace")]
+        let mut tokens = 0usize;
+        #[cfg(feature = "trace")]
+        let scanner = scanner.inspect(|_| tokens += 1);
+        self.begin(fallback);
+        for token in scanner {
+            self.feed(token?, variables, registry)?;
+        }
+        self.finish(variables, registry)?;
+        #[cfg(feature = "trace")]
+        span.record("tokens", tokens).record("nodes", self.yard.expression.len());
+        Ok(())
+    }
+
+    /// Starts a new line, forgetting whatever was left of the last.
+    pub(crate) fn begin(&mut self, fallback: Option<N>) {
+        self.placing.reset();
+        self.binding.reset();
+        self.bound.clear();
+        self.assigned.clear();
+        self.undefined = None;
+        if self.symbols.names.len() > most_symbols {
+            self.symbols = Symbols::default();
+        }
+        self.yard.clear();
+        self.progress = Progress {
+            active_ruleset: ActiveRuleset::placing,
+            enclosure: Enclosure::open,
+            fallback,
+            differentiating: 0,
+            assignable: true,
+            tokens: 0,
+        };
+    }
+
+    pub(crate) fn feed(&mut self, token: Token, variables: &mut Map<String, N>, registry: &Registry<N>) -> Result<()> {
+        let tokens = self.progress.tokens + 1;
+        if let Some(most) = self.max_tokens.filter(|most| tokens > *most) {
+            return Err(CalcError::token_limit(most));
+        }
+        let max_nesting = self.max_nesting;
+        let (mut context, yard) = self.resume(variables, registry);
+
+        // only the first token, and those where `assignable` was set, may
+        // begin an assignment
+        let level = core::mem::take(&mut context.assignable).then(|| {
+            context.placing.push(vec![Rule::assign_placing]);
+            context.placing.rules.len() - 1
+        });
+        if let Err(error) = context.apply(yard, token) {
+            return Err(first_error(context.undefined, error));
+        }
+        if let Some(level) = level {
+            context.placing.remove(level);
+        }
+        if let Some(most) = max_nesting.filter(|most| yard.stack.len() > *most) {
+            return Err(CalcError::nesting_limit(most));
+        }
+
+        let progress = Progress {
+            active_ruleset: context.active_ruleset,
+            enclosure: context.enclosure,
+            fallback: context.fallback,
+            differentiating: context.differentiating,
+            assignable: context.assignable,
+            tokens,
+        };
+        self.progress = progress;
+        Ok(())
+    }
+
+    /// A context picking the line up where the last token left it.
+    fn resume<'a>(&'a mut self, variables: &'a mut Map<String, N>, registry: &'a Registry<N>) -> (Context<'a, N>, &'a mut Yard<N>) {
+        let context = Context {
+            placing: &mut self.placing,
+            binding: &mut self.binding,
+            active_ruleset: self.progress.active_ruleset.clone(),
+            variables,
+            registry,
+            enclosure: self.progress.enclosure.clone(),
+            bound: &mut self.bound,
+            fallback: self.progress.fallback,
+            differentiating: self.progress.differentiating,
+            assignment: self.assignment,
+            late_binding: self.late_binding,
+            split_identifiers: self.split_identifiers,
+            assignable: self.progress.assignable,
+            assigned: &mut self.assigned,
+            undefined: &mut self.undefined,
+            symbols: &mut self.symbols,
+        };
+        (context, &mut self.yard)
+    }
+
+    /// Ends the line, leaving the rest of its expression to be taken.
+    pub(crate) fn finish(&mut self, variables: &mut Map<String, N>, registry: &Registry<N>) -> Result<()> {
+        let (context, yard) = self.resume(variables, registry);
+        if let Err(error) = yard.finalize(&context) {
+            return Err(first_error(context.undefined, error));
+        }
+        match context.undefined.take() {
+            Some(name) => Err(undefined(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Takes the nodes output so far, unless a series or `diff` body is
+    /// being read, whose nodes must stay until it closes.
+    #[cfg(feature = "std")]
+    pub(crate) fn take_settled(&mut self) -> Option<vec::Drain<'_, ExprNode<N>>> {
+        (self.bound.is_empty() && self.progress.differentiating == 0 && self.undefined.is_none())
+            .then(|| self.yard.expression.drain(..))
+    }
+
+    /// How many operators and calls are waiting on their operands.
+    #[cfg(feature = "std")]
+    pub(crate) fn depth(&self) -> usize {
+        self.yard.stack.len()
+    }
+}
+
+/// The error to report for a line that ran into `error`: the `undefined`
+/// identifier read before it, unless `error` is a misplaced '='.
+fn first_error(undefined: &mut Option<String>, error: CalcError) -> CalcError {
+    match undefined.take() {
+        Some(name) if !matches!(error, CalcError::invalid_assignment_target) => self::undefined(name),
+        _ => error,
+    }
+}
+
+/// How much of an expression a partial input makes, as an editor needs to
+/// match brackets or a prompt needs to ask for another line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Completeness {
+    /// How many parentheses and argument lists are left open.
+    pub depth: usize,
+    pub status: Status,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The input is a whole expression.
+    complete,
+    /// The input is the start of an expression, and needs more.
+    incomplete,
+    /// Nothing added to the input could make it an expression.
+    erroneous,
+}
+
+/// Reads `input` by the real grammar to tell how deeply it is nested when
+/// it ends and whether it is complete. Every identifier counts as defined.
+pub fn analyze_completeness(input: &str) -> Completeness {
+    let mut parser = Parser::<Number>::new();
+    let mut variables = Map::new();
+    let registry = Registry::default();
+    parser.begin(Some(0.0));
+    let fed = StringScanner::new(input.into())
+        .try_for_each(|token| parser.feed(token?, &mut variables, &registry));
+    let depth = parser.yard.stack.iter()
+        .filter(|node| matches!(node, StackNode::section(_)))
+        .count();
+    let status = match fed {
+        Err(_) => Status::erroneous,
+        Ok(()) if depth > 0 || parser.progress.active_ruleset == ActiveRuleset::placing => Status::incomplete,
+        Ok(()) => match parser.finish(&mut variables, &registry) {
+            Ok(()) => Status::complete,
+            Err(_) => Status::erroneous,
+        },
+    };
+    Completeness {depth, status}
+}
+
+/// Reads `input` by the real grammar, like `analyze_completeness`, giving
+/// the byte range of what opened each parenthesis still open where it ends
+/// or stops making sense, outermost first: the `(`, or the call it begins,
+/// as `min(`.
+pub fn find_unclosed(input: &str) -> Vec<Range<usize>> {
+    let mut parser = Parser::<Number>::new();
+    let mut variables = Map::new();
+    let registry = Registry::default();
+    parser.begin(Some(0.0));
+    let mut opened = Vec::new();
+    let mut previous = 0;
+    for span in tokenize_spans(input).unwrap_or_default() {
+        if parser.feed(span.token, &mut variables, &registry).is_err() {
+            break;
+        }
+        let sections = parser.yard.stack.iter().enumerate()
+            .filter(|(_, node)| matches!(node, StackNode::section(_)))
+            .collect::<Vec<_>>();
+        opened.truncate(sections.len());
+        if let Some((index, _)) = sections.get(opened.len()) {
+            let call = index.checked_sub(1).and_then(|below| parser.yard.stack[below].call_name()).is_some();
+            opened.push(if call { previous } else { span.start }..span.end);
+        }
+        previous = span.start;
+    }
+    opened
+}
+
+pub fn parse<N: CalcNum, T: Iterator<Item = Result<Token>>>(scanner: T, variables: &mut Map<String, N>) -> Result<Vec<ExprNode<N>>> {
+    parse_with(scanner, variables, &Registry::default())
+}
+
+/// Parses like `parse`, also resolving the functions held by `registry`.
+pub fn parse_with<N: CalcNum, T: Iterator<Item = Result<Token>>>(scanner: T, variables: &mut Map<String, N>, registry: &Registry<N>) -> Result<Vec<ExprNode<N>>> {
+    let mut parser = Parser::new();
+    parser.run(scanner, variables, registry, None)?;
+    Ok(parser.yard.expression)
+}
+
+/// Parses like `parse`, but reads every undefined identifier as `default`.
+pub fn parse_with_default<N: CalcNum, T: Iterator<Item = Result<Token>>>(scanner: T, variables: &mut Map<String, N>, default: N) -> Result<Vec<ExprNode<N>>> {
+    let mut parser = Parser::new();
+    parser.run(scanner, variables, &Registry::default(), Some(default))?;
+    Ok(parser.yard.expression)
 }
\ No newline at end of file
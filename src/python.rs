@@ -0,0 +1,59 @@
+//! A Python module, `calc_rs`, for evaluating untrusted formulas without
+//! Python's own `eval`. Build it with maturin, enabling this feature along
+//! with `pyo3/extension-module`; `tests/python` says how to test it.
+
+use crate::error_handling::*;
+use crate::numbers::Number;
+use crate::session;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use std::collections::HashMap;
+
+create_exception!(calc_rs, CalcException, PyException, "Any error raised by the calculator.");
+create_exception!(calc_rs, ParseError, CalcException, "The text is not a well-formed expression.");
+create_exception!(calc_rs, EvalError, CalcException, "The expression could not be evaluated.");
+
+fn to_python(error: CalcError) -> PyErr {
+    match error.kind() {
+        ErrorKind::syntax => ParseError::new_err(error.to_string()),
+        _ => EvalError::new_err(error.to_string()),
+    }
+}
+
+#[pyclass(unsendable)]
+struct Session {
+    session: session::Session,
+}
+
+#[pymethods]
+impl Session {
+    #[new]
+    fn new() -> Self {
+        Self {session: session::Session::new()}
+    }
+
+    fn eval(&mut self, text: &str) -> PyResult<Number> {
+        self.session.eval_line(text).map_err(to_python)
+    }
+
+    #[getter]
+    fn vars(&self) -> HashMap<String, Number> {
+        self.session.variables.clone()
+    }
+
+    fn define(&mut self, name: String, value: Number) {
+        self.session.variables.insert(name, value);
+    }
+}
+
+#[pymodule]
+fn calc_rs(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Session>()?;
+    module.add("CalcException", module.py().get_type::<CalcException>())?;
+    module.add("ParseError", module.py().get_type::<ParseError>())?;
+    module.add("EvalError", module.py().get_type::<EvalError>())?;
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use crate::error_handling::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+use crate::scanning::is_identifier;
+use crate::Map;
+
+use alloc::rc::Rc;
+
+/// A function supplied by the embedding application.
+#[derive(Clone)]
+pub enum Callable<N = Number> {
+    unary(UnaryAction<N>),
+    binary(BinaryAction<N>),
+    /// Takes at least the given number of arguments.
+    variadic(u32, VariedAction<N>),
+}
+
+impl<N: CalcNum> Callable<N> {
+    /// The fewest and, when bounded, the most arguments the function accepts.
+    pub fn arity(&self) -> (u32, Option<u32>) {
+        match self {
+            Self::unary(_) => (1, Some(1)),
+            Self::binary(_) => (2, Some(2)),
+            Self::variadic(least, _) => (*least, None),
+        }
+    }
+
+    /// The function as one taking its arguments as a slice.
+    pub fn into_varied(self) -> VariedAction<N> {
+        match self {
+            Self::unary(action) => Rc::new(move |values| action(values[0])),
+            Self::binary(action) => Rc::new(move |values| action(values[0], values[1])),
+            Self::variadic(_, action) => action,
+        }
+    }
+}
+
+/// Supplies the values of variables the embedding application keeps itself,
+/// consulted for names that are neither variables nor constants.
+pub type Resolver<N = Number> = Rc<dyn Fn(&str) -> Option<N>>;
+
+/// A pack of functions looked up by name, consulted after the built-in and
+/// individually registered functions.
+pub trait FunctionProvider<N = Number> {
+    /// The function called `name`, if the pack has one. The parser looks
+    /// names up before reading their arguments, with `arity_hint` `None`,
+    /// and linking looks them up again with the number of arguments passed.
+    fn lookup(&self, name: &str, arity_hint: Option<u32>) -> Option<Callable<N>>;
+
+    /// The name and a one-line description of each function in the pack.
+    fn describe(&self) -> Vec<(String, String)>;
+}
+
+/// How the built-in aggregates, `min`, `max`, `avg` and the values of
+/// `percentile`, treat NaN arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Missing {
+    /// NaN is a value like any other, so that `avg(1, nan)` is NaN.
+    keep,
+    /// NaN is missing data and left out, so that `avg(1, nan, 3)` is `2`.
+    /// With every value missing, the result is NaN.
+    skip,
+}
+
+impl Missing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(Self::keep),
+            "skip" => Some(Self::skip),
+            _ => None
+        }
+    }
+}
+
+/// Functions and constants registered on top of the built-in ones.
+#[derive(Clone)]
+pub struct Registry<N = Number> {
+    functions: Map<String, Callable<N>>,
+    constants: Map<String, N>,
+    providers: Vec<Rc<dyn FunctionProvider<N>>>,
+    resolver: Option<Resolver<N>>,
+    missing: Missing,
+}
+
+impl<N> Default for Registry<N> {
+    fn default() -> Self {
+        Self {
+            functions: Map::new(),
+            constants: Map::new(),
+            providers: Vec::new(),
+            resolver: None,
+            missing: Missing::keep,
+        }
+    }
+}
+
+impl<N: CalcNum> Registry<N> {
+    fn check_available(&self, name: &str) -> Result<()> {
+        if !is_identifier(name) {
+            return Err(CalcError::did_not_expect(name.into()));
+        }
+        // `_` is always the previous result
+        if name == previous_result || is_builtin(name) || self.functions.contains_key(name) || self.constants.contains_key(name) {
+            Err(CalcError::already_defined(name.into()))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn register(&mut self, name: &str, callable: Callable<N>) -> Result<()> {
+        self.check_available(name)?;
+        self.functions.insert(name.into(), callable);
+        Ok(())
+    }
+
+    pub fn register_constant(&mut self, name: &str, value: N) -> Result<()> {
+        self.check_available(name)?;
+        self.constants.insert(name.into(), value);
+        Ok(())
+    }
+
+    pub fn provide(&mut self, provider: impl FunctionProvider<N> + 'static) {
+        self.providers.push(Rc::new(provider));
+    }
+
+    /// Consults `resolver` for the names no variable or constant has, in
+    /// place of any resolver set before.
+    pub fn resolve_with(&mut self, resolver: impl Fn(&str) -> Option<N> + 'static) {
+        self.resolver = Some(Rc::new(resolver));
+    }
+
+    /// Has the built-in aggregates linked from now on treat NaN as `missing`
+    /// says.
+    pub fn set_missing(&mut self, missing: Missing) {
+        self.missing = missing;
+    }
+
+    pub fn missing(&self) -> Missing {
+        self.missing
+    }
+
+    pub(crate) fn resolver(&self) -> Option<&Resolver<N>> {
+        self.resolver.as_ref()
+    }
+
+    /// The value the resolver gives `name`, if there is a resolver.
+    pub fn resolve(&self, name: &str) -> Option<N> {
+        self.resolver.as_ref().and_then(|resolver| resolver(name))
+    }
+
+    /// The function registered as `name`, or else the first provider's
+    /// function of that name.
+    pub fn function(&self, name: &str) -> Option<Callable<N>> {
+        self.lookup(name, None)
+    }
+
+    /// The function `function` gives, telling providers that it is to be
+    /// called with `count` arguments.
+    pub fn function_taking(&self, name: &str, count: u32) -> Option<Callable<N>> {
+        self.lookup(name, Some(count))
+    }
+
+    fn lookup(&self, name: &str, arity_hint: Option<u32>) -> Option<Callable<N>> {
+        self.functions.get(name).cloned().or_else(|| {
+            self.providers.iter().find_map(|provider| provider.lookup(name, arity_hint))
+        })
+    }
+
+    /// The descriptions of every provided function, in registration order.
+    pub fn descriptions(&self) -> Vec<(String, String)> {
+        self.providers.iter().flat_map(|provider| provider.describe()).collect()
+    }
+
+    /// Every provided function, as `descriptions` gives them, with the
+    /// arguments it accepts. A provider's description of a function it has
+    /// no function for is left out, as are those of functions a built-in,
+    /// a registered function or an earlier provider's hides. Functions
+    /// registered one by one have no description, and come first in order
+    /// of name.
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        let mut registered = self.functions.iter()
+            .map(|(name, callable)| FunctionInfo {name: name.clone(), arity: callable.arity(), description: String::new()})
+            .collect::<Vec<_>>();
+        registered.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut provided = Vec::<FunctionInfo>::new();
+        for (index, provider) in self.providers.iter().enumerate() {
+            for (name, description) in provider.describe() {
+                let hidden = is_builtin(&name) || self.functions.contains_key(&name)
+                    || self.providers[..index].iter().any(|earlier| earlier.lookup(&name, None).is_some());
+                if let Some(callable) = provider.lookup(&name, None).filter(|_| !hidden) {
+                    provided.push(FunctionInfo {name, arity: callable.arity(), description});
+                }
+            }
+        }
+        registered.into_iter().chain(provided).collect()
+    }
+
+    pub fn constant(&self, name: &str) -> Option<&N> {
+        self.constants.get(name)
+    }
+
+    pub fn constants(&self) -> impl Iterator<Item = (&String, &N)> {
+        self.constants.iter()
+    }
+}
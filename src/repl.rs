@@ -0,0 +1,320 @@
+//! The read-evaluate-print loop, over any reader and writers, so that it can
+//! be embedded in another program or driven by tests as well as run on the
+//! standard streams.
+
+use crate::error_handling::*;
+use crate::formatting::*;
+use crate::parsing::{analyze_completeness, previous_result, Assignment, Completeness, Status};
+use crate::registry::Missing;
+use crate::scanning::*;
+use crate::session::*;
+
+use std::io::{self, BufRead, Write};
+use std::num::NonZeroUsize;
+use std::thread;
+
+/// How `run_repl` treats its input.
+#[derive(Clone, Debug, Default)]
+pub struct ReplOptions {
+    /// Whether a person is typing, who may refer back to history with `!`,
+    /// may leave a parenthesis open to go on to the next line, and is not
+    /// told on which line an error happened.
+    pub interactive: bool,
+    /// Whether to write a prompt before reading each line.
+    pub prompting: bool,
+    /// Whether to go on after an error when not interactive, rather than stop.
+    pub keep_going: bool,
+    /// The name of the input, such as a file, for the location of errors.
+    pub source: Option<String>,
+}
+
+/// What a run of `run_repl` did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionSummary {
+    /// How many lines were read, including commands and empty lines.
+    pub lines: usize,
+    /// How many lines ended in an error.
+    pub errors: usize,
+    /// Whether the run stopped at an error rather than at the end of the
+    /// input or a `:quit`.
+    pub stopped: bool,
+}
+
+/// What a command asks of the loop.
+enum Reply {
+    proceed(Vec<String>),
+    quit,
+}
+
+fn prompt(output: &mut impl Write, marker: &str) -> io::Result<()> {
+    write!(output, "{} ", marker)?;
+    output.flush()
+}
+
+fn run_command(session: &mut Session, command: &str) -> Result<Reply> {
+    let words = command.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        [":quit"] | [":exit"] => return Ok(Reply::quit),
+        [":mode", "emptyline", mode] => session.empty_line = EmptyLine::from_name(mode)
+            .ok_or_else(|| CalcError::unknown_mode(mode.to_string()))?,
+        [":mode", "assignment", mode] => session.assignment = Assignment::from_name(mode)
+            .ok_or_else(|| CalcError::unknown_mode(mode.to_string()))?,
+        [":mode", "arithmetic", mode] => session.arithmetic = Arithmetic::from_name(mode)
+            .ok_or_else(|| CalcError::unknown_mode(mode.to_string()))?,
+        [":mode", "missing", mode] => session.missing = Missing::from_name(mode)
+            .ok_or_else(|| CalcError::unknown_mode(mode.to_string()))?,
+        [":mode", "split", "on"] => session.split_identifiers = true,
+        [":mode", "split", "off"] => session.split_identifiers = false,
+        [":mode", "grouping", "on"] => (session.grouping, session.display.grouping) = (true, true),
+        [":mode", "grouping", "off"] => (session.grouping, session.display.grouping) = (false, false),
+        [":mode", "percent", "on"] => session.percentages = true,
+        [":mode", "percent", "off"] => session.percentages = false,
+        [":mode", "notation", notation] => session.display.notation = Notation::from_name(notation)
+            .ok_or_else(|| CalcError::unknown_mode(notation.to_string()))?,
+        [":precision", "off"] => session.display.precision = None,
+        [":precision", digits] => session.display.precision = Some(digits.parse()
+            .map_err(|_| CalcError::invalid_number(digits.to_string()))?),
+        [":bools", "on"] => session.bools = true,
+        [":bools", "off"] => session.bools = false,
+        [":repeating", "on"] => session.repeating = true,
+        [":repeating", "off"] => session.repeating = false,
+        [":trace", "on"] => session.tracing = true,
+        [":trace", "off"] => session.tracing = false,
+        [":max-iterations", "off"] => session.limits.max_iterations = None,
+        [":max-iterations", count] => session.limits.max_iterations = Some(count.parse()
+            .map_err(|_| CalcError::invalid_number(count.to_string()))?),
+        [":strict", "on"] => session.limits.strict = true,
+        [":strict", "off"] => session.limits.strict = false,
+        [":watch", texts @ ..] if !texts.is_empty() => session.watches.extend(texts.iter().map(|text| text.to_string())),
+        [":unwatch"] => session.watches.clear(),
+        [":unwatch", texts @ ..] => session.watches.retain(|watch| !texts.contains(&watch.as_str())),
+        _ => return run_simple_command(session, command).map(Reply::proceed),
+    }
+    Ok(Reply::proceed(Vec::new()))
+}
+
+/// Runs a command taking no words or arbitrary text, giving the lines it shows.
+fn run_simple_command(session: &mut Session, command: &str) -> Result<Vec<String>> {
+    let shown = match command {
+        ":history" => session.history.entries()
+            .map(|(number, entry)| format!("{:>4}  {}", number, entry))
+            .collect(),
+        ":constants" => session.constants().into_iter()
+            .map(|(name, value)| format!("{} = {}", name, format_value(value, &session.display)))
+            .collect(),
+        ":help" => session.functions().into_iter()
+            .map(|function| format!("{:<12}{}", function.name, function.description))
+            .collect(),
+        ":clear" => {
+            session.clear();
+            Vec::new()
+        },
+        ":undo" => match session.undo()? {
+            (name, Some(value)) => vec![format!("{} = {}", name, format_value(value, &session.display))],
+            (name, None) => vec![format!("{} is no longer set", name)],
+        },
+        ":reset" => {
+            session.reset();
+            Vec::new()
+        },
+        // there is no init file to run again
+        _ if command.starts_with(":reset ") => {
+            return Err(CalcError::unknown_argument(command[":reset".len()..].trim().into()));
+        },
+        _ if command.starts_with(":alias ") => {
+            let definition = command[":alias".len()..].trim_start();
+            let (name, text) = definition.split_once(char::is_whitespace)
+                .ok_or_else(|| CalcError::missing_argument(definition.into()))?;
+            session.aliases.define(name, text.trim())?;
+            Vec::new()
+        },
+        _ if command.starts_with(":fmt ") => vec![session.format_line(&command[":fmt".len()..], false)?],
+        _ if command.starts_with(":simplify ") => vec![session.format_line(&command[":simplify".len()..], true)?],
+        _ if command.starts_with(":time ") => {
+            let (value, timings) = session.time_line(&command[":time".len()..])?;
+            vec![
+                format_value(value, &session.display),
+                format!("scan {:?}, parse {:?}, eval {:?}", timings.scan, timings.parse, timings.eval),
+            ]
+        },
+        _ => return Err(CalcError::unknown_command(command.into())),
+    };
+    Ok(shown)
+}
+
+/// Shows the watched expressions of `session` on one line, each failing
+/// one with the code of its error in place of a value.
+fn show_watches(session: &mut Session) -> String {
+    let watched = session.watched().into_iter()
+        .map(|(text, value)| match value {
+            Ok(value) => format!("{} = {}", text, format_value(value, &session.display)),
+            Err(failure) => format!("{} = ({})", text, failure.code()),
+        })
+        .collect::<Vec<_>>();
+    format!("watch: {}", watched.join(", "))
+}
+
+/// Writes `error`, after the line it happened on when not interactive.
+fn report(error: &mut impl Write, failure: CalcError, options: &ReplOptions, line: usize) -> io::Result<()> {
+    if options.interactive {
+        return writeln!(error, "Error, {}", failure);
+    }
+    match &options.source {
+        Some(source) => writeln!(error, "{}: line {}: Error, {}", source, line, failure),
+        None => writeln!(error, "line {}: Error, {}", line, failure),
+    }
+}
+
+/// Evaluates a line that is neither a command nor empty, giving what is
+/// shown for its last statement.
+fn respond(session: &mut Session, line: String) -> Result<String> {
+    session.history.record(line.clone());
+    let mut shown = session.aliases.expand(&line).and_then(|line| session.show_statements(&line))?;
+    Ok(shown.pop().unwrap_or_default())
+}
+
+/// Reads lines from `input` into `session` until the input ends or a
+/// `:quit`, writing prompts and values to `output` and errors to `error`.
+pub fn run_repl(session: &mut Session, input: impl BufRead, mut output: impl Write, mut error: impl Write, options: &ReplOptions) -> io::Result<SessionSummary> {
+    let mut summary = SessionSummary::default();
+    let mut lines = input.lines().enumerate();
+
+    loop {
+        if options.prompting {
+            prompt(&mut output, ">")?;
+        }
+
+        let Some((index, line)) = lines.next() else {
+            break;
+        };
+        let mut line = line?;
+        let number = index + 1;
+        summary.lines += 1;
+
+        if options.interactive {
+            match session.history.expand(&line) {
+                Ok(Some(expanded)) => {
+                    writeln!(output, "{}", expanded)?;
+                    line = expanded;
+                },
+                Ok(None) => (),
+                Err(failure) => {
+                    summary.errors += 1;
+                    report(&mut error, failure, options, number)?;
+                    continue;
+                },
+            }
+        }
+
+        if line.trim_start().starts_with(':') {
+            match run_command(session, line.trim()) {
+                Ok(Reply::quit) => break,
+                Ok(Reply::proceed(shown)) => {
+                    for shown in shown {
+                        writeln!(output, "{}", shown)?;
+                    }
+                },
+                Err(failure) => {
+                    summary.errors += 1;
+                    report(&mut error, failure, options, number)?;
+                },
+            }
+            continue;
+        }
+
+        // an open bracket carries the expression on to the next line
+        while options.interactive && matches!(analyze_completeness(&line), Completeness {status: Status::incomplete, depth} if depth > 0) {
+            if options.prompting {
+                prompt(&mut output, "...")?;
+            }
+            let Some((_, more)) = lines.next() else {
+                break;
+            };
+            summary.lines += 1;
+            line.push(' ');
+            line.push_str(&more?);
+        }
+
+        if StringScanner::new(line.clone()).is_empty() {
+            match session.empty_line {
+                EmptyLine::skip => continue,
+                EmptyLine::quit => break,
+            }
+        }
+
+        match respond(session, line) {
+            Ok(value) => {
+                writeln!(output, "{}", value)?;
+                if !session.watches.is_empty() {
+                    writeln!(output, "{}", show_watches(session))?;
+                }
+            },
+            Err(failure) => {
+                summary.errors += 1;
+                report(&mut error, failure, options, number)?;
+                if !options.interactive && !options.keep_going {
+                    summary.stopped = true;
+                    break;
+                }
+            },
+        }
+    }
+    output.flush()?;
+    error.flush()?;
+    Ok(summary)
+}
+
+/// Whether `lines` may be evaluated in any order, as none of them runs a
+/// command, assigns a variable or reads the previous result.
+pub fn independent_lines<'a>(mut lines: impl Iterator<Item = &'a str>) -> bool {
+    lines.all(|line| {
+        !line.trim_start().starts_with(':') && tokenize(line).into_iter().flatten()
+            .all(|token| token.kind != TokenClass::assign && token.text != previous_result)
+    })
+}
+
+/// Evaluates the lines of `input` as `run_repl` does when not interactive,
+/// but on as many as `threads` threads, each evaluating a run of the lines
+/// in a session of its own from `session`. When the lines are
+/// `independent_lines`, what is written is just what `run_repl` writes.
+pub fn run_parallel(session: impl Fn() -> Session + Sync, input: &str, threads: NonZeroUsize, mut output: impl Write, mut error: impl Write, options: &ReplOptions) -> io::Result<SessionSummary> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let run = lines.len().div_ceil(threads.get()).max(1);
+    let session = &session;
+    let results = thread::scope(|scope| {
+        let workers = lines.chunks(run)
+            .map(|chunk| scope.spawn(move || {
+                let mut session = session();
+                chunk.iter()
+                    .map(|line| {
+                        let empty = StringScanner::new(line.to_string()).is_empty();
+                        (!empty).then(|| respond(&mut session, line.to_string()))
+                    })
+                    .collect::<Vec<_>>()
+            }))
+            .collect::<Vec<_>>();
+        workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    let empty_line = session().empty_line;
+    let mut summary = SessionSummary::default();
+    for (index, result) in results.into_iter().enumerate() {
+        summary.lines += 1;
+        match result {
+            None if empty_line == EmptyLine::quit => break,
+            None => (),
+            Some(Ok(value)) => writeln!(output, "{}", value)?,
+            Some(Err(failure)) => {
+                summary.errors += 1;
+                report(&mut error, failure, options, index + 1)?;
+                if !options.keep_going {
+                    summary.stopped = true;
+                    break;
+                }
+            },
+        }
+    }
+    output.flush()?;
+    error.flush()?;
+    Ok(summary)
+}
@@ -1,4 +1,8 @@
 use crate::error_handling::*;
+use crate::prelude::*;
+
+use alloc::borrow::Cow;
+use core::ops::Range;
 
 #[derive(Clone, PartialEq, Eq)]
 pub enum TokenKind {
@@ -7,37 +11,189 @@ pub enum TokenKind {
 
 #[derive(Clone)]
 pub struct Token {
-    pub content: String,
+    /// The text of the token, borrowed for operators and punctuation so
+    /// that scanning them does not allocate.
+    pub content: Cow<'static, str>,
     pub kind: TokenKind,
 }
 
 impl Token {
-    fn new(content: String, kind: TokenKind) -> Self {
-        Self{content, kind}
+    fn new(content: impl Into<Cow<'static, str>>, kind: TokenKind) -> Self {
+        Self{content: content.into(), kind}
     }
 }
 
+/// A token with the byte range of the input it was read from, which for a
+/// grouped number includes its commas.
+#[derive(Clone)]
+pub struct TokenSpan {
+    pub token: Token,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// What a token is, told apart finely enough to color each differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    identifier, number, open_paren, close_paren, comma,
+    plus, minus, times, divide, power, assign,
+    less, greater, less_equal, greater_equal, equal, not_equal,
+    and, or, pipe,
+}
+
+impl TokenClass {
+    fn of(token: &Token) -> Self {
+        use TokenClass::*;
+        match token.kind {
+            TokenKind::identifier => return identifier,
+            TokenKind::number => return number,
+            _ => (),
+        }
+        match &*token.content {
+            "(" => open_paren,
+            ")" => close_paren,
+            "," => comma,
+            "+" => plus,
+            "-" | "−" => minus,
+            "*" | "×" => times,
+            "/" | "÷" => divide,
+            "^" => power,
+            "=" => assign,
+            "<" => less,
+            ">" => greater,
+            "<=" => less_equal,
+            ">=" => greater_equal,
+            "==" => equal,
+            "!=" => not_equal,
+            "&&" => and,
+            "||" => or,
+            "|>" => pipe,
+            content => unreachable!("the scanner does not read '{}' as a symbol", content),
+        }
+    }
+}
+
+/// A token as `tokenize` reads it, with the byte range it was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpannedToken {
+    pub kind: TokenClass,
+    pub text: String,
+    pub span: Range<usize>,
+}
+
+/// An error `tokenize` met, with the byte range of the text it is about.
+#[derive(Debug)]
+pub struct SpannedError {
+    pub error: CalcError,
+    pub span: Range<usize>,
+}
+
 pub struct StringScanner {
     string: String,
     index: usize,
+    grouping: bool,
+    percentages: bool,
 }
 
+const compound_operators: [&str; 7] = ["<=", ">=", "==", "!=", "&&", "||", "|>"];
+
+/// Every operator and punctuation character, for single character tokens
+/// to borrow from.
+const single_characters: &str = "+-*/^=<>(),;×÷−";
+
+/// Takes the typographic `×`, `÷` and minus sign `−` as well, as text
+/// pasted from documents has them.
 fn is_operator(character: char) -> bool {
-    match character {
-        '+' | '-' | '*' | '/' | '^' | '=' => true,
-        _ => false
-    }
+    matches!(character, '+' | '-' | '*' | '/' | '^' | '=' | '<' | '>' | '×' | '÷' | '−')
 }
 
 fn is_punctuation(character: char) -> bool {
-    match character {
-        '(' | ')' | ',' => true,
-        _ => false
-    }
+    matches!(character, '(' | ')' | ',')
 }
 
+fn is_identifier_character(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+/// Whether `text` is scanned as a single identifier.
+pub(crate) fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    let begins_well = match characters.next() {
+        Some('_') => !text[1..].starts_with(|c: char| c.is_numeric()),
+        first => first.is_some_and(char::is_alphabetic),
+    };
+    begins_well && characters.all(is_identifier_character)
+}
+
+/// Also takes the `_` that may separate digits, as in `1_000`.
 fn is_digit_or_dot(character: char) -> bool {
-    character.is_numeric() || character == '.'
+    character.is_numeric() || character == '.' || character == '_'
+}
+
+/// Whether every `_` in `digits` comes between two digits in `radix`, so
+/// that none leads, trails, is doubled or touches a point.
+fn separated_well(digits: &str, radix: u32) -> bool {
+    let is_digit = |c: Option<char>| c.is_some_and(|c| c.is_digit(radix));
+    digits.char_indices()
+        .filter(|(_, c)| *c == '_')
+        .all(|(at, _)| is_digit(digits[..at].chars().next_back()) && is_digit(digits[at + 1..].chars().next()))
+}
+
+/// The length of the exponent `text` begins with, as `e-7` in `1.5e-7`,
+/// which must have a digit so that `2e` is not read as one.
+pub(crate) fn exponent_length(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix(['e', 'E'])?;
+    let sign = rest.starts_with(['+', '-']) as usize;
+    let digits = rest[sign..].chars().take_while(char::is_ascii_digit).count();
+    (digits > 0).then_some(1 + sign + digits)
+}
+
+/// The radix of the prefix a literal in it has, as 16 for `0x` and 2 for
+/// `0b`, if it has one.
+pub(crate) fn literal_radix(literal: &str) -> Option<u32> {
+    match literal.get(..2)? {
+        "0x" | "0X" => Some(16),
+        "0b" | "0B" => Some(2),
+        _ => None,
+    }
+}
+
+/// The length and radix of the prefixed literal `text` begins with, as
+/// `0xFF` or `0b101`, taking every letter and digit after the prefix so that
+/// a bad digit, as in `0xFG` or `0b102`, makes the whole of it invalid
+/// rather than starting a name or another number.
+pub(crate) fn prefixed_length(text: &str) -> Option<(usize, u32)> {
+    let radix = literal_radix(text)?;
+    let rest = &text[2..];
+    Some((2 + rest.find(|c| !is_identifier_character(c)).unwrap_or(rest.len()), radix))
+}
+
+/// The length of the exponent `text` begins without finishing, as `e+` in
+/// `1e+`. An `e` with neither sign nor digits is only one when no letter
+/// follows it, so that `2ex` is left to be read as a number and a name.
+fn broken_exponent_length(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix(['e', 'E'])?;
+    if rest.starts_with(['+', '-']) {
+        Some(2)
+    } else if !rest.starts_with(is_identifier_character) {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+/// The literal for `number` hundredths, written with an exponent, as
+/// `25e-2` for `25`, so that it reads as exactly as `number` does.
+fn hundredths(number: &str) -> String {
+    let (mantissa, exponent) = match number.find(['e', 'E']) {
+        Some(at) => (&number[..at], number[at + 1..].parse::<i64>().ok()),
+        None => (number, Some(0)),
+    };
+    match exponent {
+        Some(exponent) => String::from(mantissa) + "e" + &(exponent - 2).to_string(),
+        // too large to shift, and so left for reading it to fail
+        None => number.into(),
+    }
 }
 
 impl StringScanner {
@@ -45,13 +201,33 @@ impl StringScanner {
         let mut scanner = Self {
             string,
             index: 0,
+            grouping: false,
+            percentages: false,
         };
         scanner.skip_whitespace();
         scanner
     }
 
+    /// Reads numbers grouped by commas, such as `1,234,567`, as one number.
+    /// Since commas then belong to numbers, arguments are separated by `;`.
+    pub fn with_grouping(mut self) -> Self {
+        self.grouping = true;
+        self
+    }
+
+    /// Reads a number directly followed by `%` as that many hundredths, so
+    /// that `25%` is `0.25`. A `%` after a space is left alone.
+    pub fn with_percentages(mut self) -> Self {
+        self.percentages = true;
+        self
+    }
+
+    /// The length in bytes, not characters, of the longest start of the
+    /// view made of characters matching `predicate`.
     fn count_while<P: Fn(char) -> bool>(&self, predicate: P) -> usize {
-        self.view().chars().take_while(|c| predicate(*c)).count()
+        self.view().char_indices()
+            .find(|(_, c)| !predicate(*c))
+            .map_or(self.view().len(), |(index, _)| index)
     }
 
     fn view(&self) -> &str {
@@ -79,29 +255,89 @@ impl StringScanner {
     }
 
     fn slice_once_as(&mut self, predicate: fn(char) -> bool, kind: TokenKind) -> Option<Token> {
-        if self.view().starts_with(predicate) {
-            let slice = self.view()[..1].to_string();
+        let character = self.view().chars().next().filter(|c| predicate(*c))?;
+        let position = single_characters.find(character)?;
+        let length = character.len_utf8();
+        self.index += length;
+        Some(Token::new(&single_characters[position..position + length], kind))
+    }
+
+    fn peel_number(&mut self) -> Option<Result<Token>> {
+        if let Some((length, radix)) = prefixed_length(self.view()) {
+            let literal = self.view()[..length].to_string();
+            self.index += length;
+            let digits = &literal[2..];
+            return Some(match digits.chars().all(|c| c.is_digit(radix) || c == '_') && separated_well(digits, radix) && length > 2 {
+                true => Ok(Token::new(literal, TokenKind::number)),
+                false => Err(CalcError::invalid_number(literal)),
+            });
+        }
+        // a leading `_` begins a name, such as `_` itself, unless it comes
+        // before a digit, as a separator misplaced at the start of a number
+        if let Some(rest) = self.view().strip_prefix('_') {
+            if !rest.starts_with(|c: char| c.is_numeric()) {
+                return None;
+            }
+            let number = self.slice_while(is_identifier_character);
+            return Some(Err(CalcError::invalid_number(number)));
+        }
+        let mut token = self.slice_many_as(is_digit_or_dot, TokenKind::number)?;
+        if self.grouping && !token.content.contains('.') {
+            while let Some(group) = self.peel_group() {
+                token.content.to_mut().push_str(&group);
+            }
+            token.content.to_mut().push_str(&self.slice_while(is_digit_or_dot));
+        }
+        if !separated_well(&token.content, 10) {
+            return Some(Err(CalcError::invalid_number(token.content.into_owned())));
+        }
+        if let Some(length) = exponent_length(self.view()) {
+            token.content.to_mut().push_str(&self.view()[..length]);
+            self.index += length;
+        } else if let Some(length) = broken_exponent_length(self.view()) {
+            let number = token.content.into_owned() + &self.view()[..length];
+            self.index += length;
+            return Some(Err(CalcError::invalid_number(number)));
+        }
+        if self.percentages && self.view().starts_with('%') {
             self.index += 1;
-            Some(Token::new(slice, kind))
-        } else {
-            None
+            token.content = hundredths(&token.content).into();
         }
+        Some(Ok(token))
     }
 
-    fn peel_number(&mut self) -> Option<Token> {
-        self.slice_many_as(is_digit_or_dot, TokenKind::number)
+    /// Peels a `,` followed by exactly three digits.
+    fn peel_group(&mut self) -> Option<String> {
+        let rest = self.view().strip_prefix(',')?;
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        if digits == 3 {
+            let group = rest[..3].to_string();
+            self.index += 4;
+            Some(group)
+        } else {
+            None
+        }
     }
 
     fn peel_operator(&mut self) -> Option<Token> {
-        self.slice_once_as(is_operator, TokenKind::operator)
+        if let Some(operator) = compound_operators.iter().find(|operator| self.view().starts_with(*operator)) {
+            self.index += operator.len();
+            Some(Token::new(*operator, TokenKind::operator))
+        } else {
+            self.slice_once_as(is_operator, TokenKind::operator)
+        }
     }
 
     fn peel_punctuation(&mut self) -> Option<Token> {
-        self.slice_once_as(is_punctuation, TokenKind::punctuation)
+        if self.grouping {
+            self.slice_once_as(|c| matches!(c, '(' | ')' | ';'), TokenKind::punctuation)
+        } else {
+            self.slice_once_as(is_punctuation, TokenKind::punctuation)
+        }
     }
 
     fn peel_identifier(&mut self) -> Option<Token> {
-        self.slice_many_as(char::is_alphabetic, TokenKind::identifier)
+        self.slice_many_as(is_identifier_character, TokenKind::identifier)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -112,7 +348,7 @@ impl StringScanner {
         if self.is_empty() {
             None
         } else if let Some(token) = self.peel_number() {
-            Some(Ok(token))
+            Some(token)
         } else if let Some(token) = self.peel_operator() {
             Some(Ok(token))
         } else if let Some(token) = self.peel_punctuation() {
@@ -125,6 +361,89 @@ impl StringScanner {
     }
 }
 
+/// Scans `input` like `StringScanner`, keeping where each token was read
+/// from, for tooling such as highlighters.
+pub fn tokenize_spans(input: &str) -> Result<Vec<TokenSpan>> {
+    let mut scanner = StringScanner::new(input.into());
+    let mut spans = Vec::new();
+    loop {
+        let start = scanner.index;
+        let Some(token) = scanner.peel() else {
+            return Ok(spans);
+        };
+        spans.push(TokenSpan {token: token?, start, end: scanner.index});
+        scanner.skip_whitespace();
+    }
+}
+
+/// Scans all of `input` as the parser would, for highlighters. Unlike
+/// `tokenize_spans`, an invalid character does not end the scan: it is
+/// reported in its place and scanning goes on after it.
+pub fn tokenize(input: &str) -> Vec<core::result::Result<SpannedToken, SpannedError>> {
+    let mut scanner = StringScanner::new(input.into());
+    let mut tokens = Vec::new();
+    loop {
+        let start = scanner.index;
+        let item = match scanner.peel() {
+            None => return tokens,
+            Some(Ok(token)) => Ok(SpannedToken {
+                kind: TokenClass::of(&token),
+                text: token.content.into_owned(),
+                span: start..scanner.index,
+            }),
+            Some(Err(error)) => {
+                // a malformed number is passed over whole, an invalid character alone
+                if scanner.index == start {
+                    scanner.index += scanner.view().chars().next().map_or(0, char::len_utf8);
+                }
+                Err(SpannedError {error, span: start..scanner.index})
+            },
+        };
+        tokens.push(item);
+        scanner.skip_whitespace();
+    }
+}
+
+/// Splits `input` at each `;` outside parentheses into the statements it
+/// holds, reading it token by token so that a `;` within a call, where it
+/// separates arguments when `grouping`, does not end a statement.
+pub fn split_statements(input: &str, grouping: bool) -> Vec<&str> {
+    let mut scanner = StringScanner {string: input.into(), index: 0, grouping, percentages: false};
+    let mut statements = Vec::new();
+    let (mut start, mut depth) = (0, 0usize);
+    loop {
+        scanner.skip_whitespace();
+        let at = scanner.index;
+        let content = match scanner.peel() {
+            None => break,
+            Some(Ok(token)) if token.kind == TokenKind::punctuation => token.content,
+            Some(Ok(_)) => continue,
+            // a malformed number has been passed over already
+            Some(Err(_)) if scanner.index > at => continue,
+            // without grouping `;` is not a token, but still ends a statement
+            Some(Err(_)) => {
+                let character = scanner.view().chars().next().unwrap();
+                scanner.index += character.len_utf8();
+                if character != ';' {
+                    continue;
+                }
+                Cow::Borrowed(";")
+            },
+        };
+        match &*content {
+            "(" => depth += 1,
+            ")" => depth = depth.saturating_sub(1),
+            ";" if depth == 0 => {
+                statements.push(&input[start..at]);
+                start = scanner.index;
+            },
+            _ => (),
+        }
+    }
+    statements.push(&input[start..]);
+    statements
+}
+
 impl Iterator for StringScanner {
     type Item = Result<Token>;
 
@@ -0,0 +1,669 @@
+use crate::aliasing::*;
+use crate::error_handling::*;
+use crate::evaluating::*;
+use crate::formatting::*;
+use crate::history::*;
+use crate::linking::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::registry::*;
+use crate::scanning::*;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+use std::time::Instant;
+
+/// A host's rewriting of the text of each statement, see
+/// `Session::set_preprocessor`.
+type Preprocessor = dyn Fn(&str) -> Cow<'_, str>;
+
+/// How many assignments `Session::undo` can take back.
+const undo_depth: usize = 100;
+
+/// What the REPL does when it reads a blank line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EmptyLine {
+    skip, quit,
+}
+
+impl EmptyLine {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(Self::skip),
+            "quit" => Some(Self::quit),
+            _ => None
+        }
+    }
+}
+
+/// What `show_line` evaluates in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Arithmetic {
+    /// `Number`, rounding as floating point does.
+    float,
+    /// `Rational`, so that `1/3 + 1/3 + 1/3` is exactly `1`.
+    exact,
+}
+
+impl Arithmetic {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "float" => Some(Self::float),
+            "exact" => Some(Self::exact),
+            _ => None
+        }
+    }
+}
+
+/// Everything a calculator session accumulates between lines, so starting
+/// afresh is a matter of constructing a new one.
+pub struct Session {
+    pub variables: HashMap<String, Number>,
+    pub history: History,
+    pub aliases: Aliases,
+    pub limits: Limits,
+    pub empty_line: EmptyLine,
+    /// Whether numbers may be grouped like `1,234`, see `StringScanner::with_grouping`.
+    pub grouping: bool,
+    /// Whether `25%` is read as `0.25`, see `StringScanner::with_percentages`.
+    pub percentages: bool,
+    /// Whether `show_line` writes truth values as `true` and `false`.
+    pub bools: bool,
+    /// Where lines may assign to variables.
+    pub assignment: Assignment,
+    /// Whether unknown identifiers are read as products of their letters,
+    /// see `Parser::split_identifiers`.
+    pub split_identifiers: bool,
+    /// How `show_line` writes values.
+    pub display: DisplayOptions,
+    pub arithmetic: Arithmetic,
+    /// Whether `show_line` writes exact values as decimals with their
+    /// repeating digits in parentheses, as `0.(3)`, rather than fractions.
+    pub repeating: bool,
+    /// Whether `show_line` writes each operation it reduces, as `2 + 3 = 5`,
+    /// on a line of its own before the value, in float arithmetic.
+    pub tracing: bool,
+    /// How `min`, `max`, `avg` and `percentile` treat NaN arguments.
+    pub missing: Missing,
+    /// The expressions whose values a REPL shows after each line, see
+    /// `Session::watched`.
+    pub watches: Vec<String>,
+    registry: Registry,
+    preprocessor: Option<Rc<Preprocessor>>,
+    parser: Parser,
+    formulas: HashMap<String, Formula>,
+    /// The exact values `exact_line` last gave variables, for as long as
+    /// the variables keep the `Number`s nearest them.
+    exact_values: HashMap<String, Rational>,
+    /// The names set or defined since the last `recalculate`.
+    changed: HashSet<String>,
+    /// The latest assignments of float lines, with the values they replaced,
+    /// for `undo` to take back, the newest last.
+    undone: VecDeque<(String, Option<Number>)>,
+}
+
+/// The state a session has built up, for a host to keep between runs and
+/// give back to `Session::restore`. The registered functions and constants
+/// and the limits are the host's to set up again, and are not kept.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionSnapshot {
+    /// The variables, including the previous result, `_`.
+    pub variables: BTreeMap<String, Number>,
+    pub exact_values: BTreeMap<String, Rational>,
+    /// The text of each formula, see `Session::define_formula`.
+    pub formulas: BTreeMap<String, String>,
+    pub history: Vec<String>,
+    pub aliases: BTreeMap<String, String>,
+    pub empty_line: EmptyLine,
+    pub grouping: bool,
+    pub percentages: bool,
+    pub bools: bool,
+    pub assignment: Assignment,
+    pub split_identifiers: bool,
+    pub display: DisplayOptions,
+    pub arithmetic: Arithmetic,
+    pub repeating: bool,
+    pub tracing: bool,
+    pub missing: Missing,
+    pub watches: Vec<String>,
+}
+
+/// A variable defined by an expression, which reads the variables it uses
+/// whenever it is recomputed.
+struct Formula {
+    text: String,
+    expression: CompiledExpr,
+    reads: Vec<String>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            variables: HashMap::new(),
+            history: History::new(),
+            aliases: Aliases::new(),
+            limits: Limits::default(),
+            empty_line: EmptyLine::skip,
+            grouping: false,
+            percentages: false,
+            bools: false,
+            assignment: Assignment::statement,
+            split_identifiers: false,
+            display: DisplayOptions::default(),
+            arithmetic: Arithmetic::float,
+            repeating: false,
+            tracing: false,
+            missing: Missing::keep,
+            watches: Vec::new(),
+            registry: Registry::default(),
+            preprocessor: None,
+            parser: Parser::new(),
+            formulas: HashMap::new(),
+            exact_values: HashMap::new(),
+            changed: HashSet::new(),
+            undone: VecDeque::new(),
+        }
+    }
+
+    pub fn builder() -> SessionBuilder {
+        SessionBuilder::new()
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Every function the session knows, the built-in ones first and then
+    /// those of the registry.
+    pub fn functions(&self) -> Vec<FunctionInfo> {
+        let mut functions = crate::parsing::functions();
+        functions.extend(self.registry.functions());
+        functions
+    }
+
+    /// Every constant the session knows, built-in and registered, by name.
+    pub fn constants(&self) -> Vec<(String, Number)> {
+        let mut constants = builtin_constants.iter()
+            .map(|(name, value)| (name.to_string(), Number::from_f64(*value)))
+            .chain(self.registry.constants().map(|(name, value)| (name.clone(), *value)))
+            .collect::<Vec<_>>();
+        constants.sort_by(|a, b| a.0.cmp(&b.0));
+        constants
+    }
+
+    /// Evaluates a line, binding its value to `_` when it succeeds.
+    pub fn eval_line(&mut self, line: &str) -> Result<Number> {
+        self.run_line(line, false).map(|(value, _, _)| value)
+    }
+
+    /// Evaluates a line like `eval_line`, writing its value as `display`
+    /// says, or as `true` or `false` when `bools` is set and the line ends
+    /// in a comparison or a logical operator. In exact arithmetic, the value
+    /// is evaluated by `exact_line` and written as a fraction, or as
+    /// `format_repeating` writes it when `repeating` is set.
+    pub fn show_line(&mut self, line: &str) -> Result<String> {
+        if self.arithmetic == Arithmetic::exact {
+            let value = self.exact_line(line)?;
+            return Ok(if self.repeating { format_repeating(value) } else { value.to_string() });
+        }
+        let (value, is_truth, steps) = self.run_line(line, self.tracing)?;
+        let mut shown = steps.iter()
+            .map(|step| format_step(step, &self.display) + "\n")
+            .collect::<String>();
+        shown += &match self.bools && is_truth {
+            true => (value != 0.0).to_string(),
+            false => format_value(value, &self.display),
+        };
+        Ok(shown)
+    }
+
+    /// Shows each statement of a line, as `split_statements` separates them,
+    /// like `show_line`, stopping at the first that fails. Blank statements
+    /// are skipped.
+    pub fn show_statements(&mut self, line: &str) -> Result<Vec<String>> {
+        split_statements(line, self.grouping).into_iter()
+            .filter(|statement| !statement.trim().is_empty())
+            .map(|statement| self.show_line(statement))
+            .collect()
+    }
+
+    /// Evaluates a line in exact fractions, binding its value to `_` when
+    /// it succeeds. Variables are assigned the nearest `Number`, but keep
+    /// their exact value for later exact lines until set otherwise. Those
+    /// set by other lines are read as the exact value of their `Number`.
+    /// Registered functions and constants are unavailable.
+    pub fn exact_line(&mut self, line: &str) -> Result<Rational> {
+        let mut variables = self.variables.iter()
+            .map(|(name, value)| {
+                let exact = self.exact_values.get(name).copied()
+                    .filter(|exact| Number::from_f64(exact.to_f64()) == *value);
+                (name.clone(), exact.unwrap_or_else(|| Rational::from_f64(value.to_f64())))
+            })
+            .collect::<HashMap<_, _>>();
+        let scanner = self.scanner(line);
+        let mut parser = Parser::new();
+        parser.assignment = self.assignment;
+        parser.split_identifiers = self.split_identifiers;
+        (parser.max_nesting, parser.max_tokens) = (self.limits.max_nesting, self.limits.max_tokens);
+        let expression = parser.parse(scanner, &mut variables, &Registry::default())?;
+        let value = evaluate_limited(expression, &mut variables, &self.limits)?;
+        variables.insert(previous_result.into(), value);
+        for (name, assigned) in variables {
+            self.variables.insert(name.clone(), Number::from_f64(assigned.to_f64()));
+            self.exact_values.insert(name, assigned);
+        }
+        Ok(value)
+    }
+
+    /// Parses a line without evaluating it, writing it back out as the
+    /// session reads it, simplified if `simplified` is set.
+    pub fn format_line(&mut self, line: &str, simplified: bool) -> Result<String> {
+        let mut compiled = self.compile_line(line, false)?;
+        if simplified {
+            compiled = compiled.simplified();
+        }
+        let options = FormatOptions {
+            values: self.display,
+            ..FormatOptions::default()
+        };
+        Ok(format_expression(&compiled, options))
+    }
+
+    /// Has `preprocessor` rewrite the text of each statement, formula and
+    /// watch before it is scanned, for syntax of the host's own. Errors are
+    /// about the rewritten text, which `preprocess` gives for tools such as
+    /// `tokenize` to find spans in.
+    pub fn set_preprocessor(&mut self, preprocessor: impl Fn(&str) -> Cow<'_, str> + 'static) {
+        self.preprocessor = Some(Rc::new(preprocessor));
+    }
+
+    /// `line` as the session scans it, after any preprocessor.
+    pub fn preprocess<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        match &self.preprocessor {
+            Some(preprocessor) => preprocessor(line),
+            None => Cow::Borrowed(line),
+        }
+    }
+
+    /// A scanner over `line`, reading numbers as the settings say.
+    fn scanner(&self, line: &str) -> StringScanner {
+        let mut scanner = StringScanner::new(self.preprocess(line).into_owned());
+        if self.grouping {
+            scanner = scanner.with_grouping();
+        }
+        if self.percentages {
+            scanner = scanner.with_percentages();
+        }
+        scanner
+    }
+
+    fn compile_line(&mut self, line: &str, late_binding: bool) -> Result<CompiledExpr> {
+        let scanner = self.scanner(line);
+        self.compile_tokens(scanner, late_binding)
+    }
+
+    fn compile_tokens(&mut self, scanner: impl Iterator<Item = Result<Token>>, late_binding: bool) -> Result<CompiledExpr> {
+        self.parser.assignment = self.assignment;
+        self.parser.late_binding = late_binding;
+        self.parser.split_identifiers = self.split_identifiers;
+        (self.parser.max_nesting, self.parser.max_tokens) = (self.limits.max_nesting, self.limits.max_tokens);
+        self.registry.set_missing(self.missing);
+        let expression = self.parser.parse(scanner, &mut self.variables, &self.registry)?;
+        link(expression, &self.registry)
+    }
+
+    /// Evaluates a line, also telling whether its value is a truth value
+    /// and, when `traced`, the operations it reduced.
+    fn run_line(&mut self, line: &str, traced: bool) -> Result<(Number, bool, Vec<Step>)> {
+        let compiled = self.compile_line(line, false)?;
+        self.run_compiled(&compiled, traced)
+    }
+
+    fn run_compiled(&mut self, compiled: &CompiledExpr, traced: bool) -> Result<(Number, bool, Vec<Step>)> {
+        // an assignment gives the value it assigns
+        let is_truth = compiled.nodes().iter().rev()
+            .find(|node| !matches!(node, ExprNode::assign(_) | ExprNode::assign_all(_) | ExprNode::unary(Function::group)))
+            .is_some_and(ExprNode::gives_truth);
+        let (value, steps, assigned) = evaluate_recorded(compiled, &mut self.variables, &self.limits, traced)?;
+        for (name, _, replaced) in assigned {
+            if self.undone.len() == undo_depth {
+                self.undone.pop_front();
+            }
+            self.undone.push_back((name, replaced));
+        }
+        self.variables.insert(previous_result.into(), value);
+        Ok((value, is_truth, steps))
+    }
+
+    /// Evaluates a line like `eval_line`, but scans it up front so that
+    /// each phase can be timed separately.
+    pub fn time_line(&mut self, line: &str) -> Result<(Number, Timings)> {
+        let start = Instant::now();
+        let tokens = self.scanner(line).collect::<Result<Vec<_>>>()?;
+        let scanned = Instant::now();
+        let compiled = self.compile_tokens(tokens.into_iter().map(Ok), false)?;
+        let parsed = Instant::now();
+        let (value, _, _) = self.run_compiled(&compiled, false)?;
+        let timings = Timings {
+            scan: scanned - start,
+            parse: parsed - scanned,
+            eval: parsed.elapsed(),
+        };
+        Ok((value, timings))
+    }
+
+    /// Defines `name` as the value of `text`, which is not evaluated until
+    /// `recalculate`, and is evaluated again whenever a variable it reads
+    /// changes.
+    pub fn define_formula(&mut self, name: &str, text: &str) -> Result<()> {
+        let expression = self.compile_line(text, true)?;
+        let reads = variables_used(&expression.nodes());
+        self.formulas.insert(name.into(), Formula {text: text.into(), expression, reads});
+        self.changed.insert(name.into());
+        Ok(())
+    }
+
+    /// Sets `name` to `value`, replacing any formula defining it, for the
+    /// next `recalculate` to pass on to the formulas reading it.
+    pub fn set(&mut self, name: &str, value: Number) {
+        self.variables.insert(name.into(), value);
+        self.formulas.remove(name);
+        self.changed.insert(name.into());
+    }
+
+    /// The values of the variables `names`, in their order, for a host to
+    /// read back after running a script. Fails on the first that is not set.
+    pub fn values_of(&self, names: &[&str]) -> Result<Vec<(String, Number)>> {
+        names.iter()
+            .map(|&name| match self.variables.get(name) {
+                Some(&value) => Ok((name.into(), value)),
+                None => Err(CalcError::undefined(name.into())),
+            })
+            .collect()
+    }
+
+    /// The value of each of the `watches`, or the error it gives, read with
+    /// late binding so that it follows the variables as they are now. The
+    /// variables, `_` included, are left as they were.
+    pub fn watched(&mut self) -> Vec<(String, Result<Number>)> {
+        let mut variables = self.variables.clone();
+        let watches = std::mem::take(&mut self.watches);
+        let values = watches.iter()
+            .map(|text| {
+                let value = self.compile_line(text, true)
+                    .and_then(|expression| evaluate_compiled(&expression, &mut variables, &self.limits));
+                (text.clone(), value)
+            })
+            .collect();
+        self.watches = watches;
+        values
+    }
+
+    /// Takes back the latest assignment a float line made that has not been
+    /// undone, setting the variable to the value it replaced, or unsetting
+    /// it if it was new. Gives the name and the value it now has.
+    pub fn undo(&mut self) -> Result<(String, Option<Number>)> {
+        let (name, replaced) = self.undone.pop_back().ok_or(CalcError::nothing_to_undo)?;
+        match replaced {
+            Some(value) => self.variables.insert(name.clone(), value),
+            None => self.variables.remove(&name),
+        };
+        self.exact_values.remove(&name);
+        self.changed.insert(name.clone());
+        Ok((name, replaced))
+    }
+
+    /// Recomputes each formula that reads a variable set or defined since the
+    /// last call, directly or through other formulas, after those it reads.
+    /// Gives the names and new values of the formulas in that order.
+    pub fn recalculate(&mut self) -> Result<Vec<(String, Number)>> {
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        let mut names = self.formulas.keys().collect::<Vec<_>>();
+        // sorted so that independent formulas are recomputed in a fixed order
+        names.sort();
+        for name in names {
+            self.order_formula(name, &mut Vec::new(), &mut done, &mut order)?;
+        }
+
+        let mut affected = HashSet::new();
+        let mut results = Vec::new();
+        for name in order {
+            let formula = &self.formulas[&name];
+            let stale = self.changed.contains(&name)
+                || formula.reads.iter().any(|read| self.changed.contains(read) || affected.contains(read));
+            if stale {
+                let value = evaluate_compiled(&formula.expression, &mut self.variables, &self.limits)?;
+                self.variables.insert(name.clone(), value);
+                results.push((name.clone(), value));
+                affected.insert(name);
+            }
+        }
+        self.changed.clear();
+        Ok(results)
+    }
+
+    /// Appends `name` to `order` after the formulas it reads, with `path`
+    /// holding the formulas being ordered that lead to it.
+    fn order_formula(&self, name: &str, path: &mut Vec<String>, done: &mut HashSet<String>, order: &mut Vec<String>) -> Result<()> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|each| each == name) {
+            let cycle = path[start..].iter().map(String::as_str).chain([name]).collect::<Vec<_>>();
+            return Err(CalcError::dependency_cycle(cycle.join(" -> ")));
+        }
+        let Some(formula) = self.formulas.get(name) else {
+            return Ok(());
+        };
+        path.push(name.into());
+        for read in &formula.reads {
+            self.order_formula(read, path, done, order)?;
+        }
+        path.pop();
+        done.insert(name.into());
+        order.push(name.into());
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.undone.clear();
+        self.formulas.clear();
+        self.exact_values.clear();
+        self.changed.clear();
+    }
+
+    /// The state the session has built up, for `restore` to bring back.
+    pub fn snapshot(&self) -> SessionSnapshot {
+        SessionSnapshot {
+            variables: self.variables.iter().map(|(name, value)| (name.clone(), *value)).collect(),
+            exact_values: self.exact_values.iter().map(|(name, value)| (name.clone(), *value)).collect(),
+            formulas: self.formulas.iter().map(|(name, formula)| (name.clone(), formula.text.clone())).collect(),
+            history: self.history.entries().map(|(_, entry)| entry.clone()).collect(),
+            aliases: self.aliases.definitions().map(|(name, text)| (name.clone(), text.clone())).collect(),
+            empty_line: self.empty_line,
+            grouping: self.grouping,
+            percentages: self.percentages,
+            bools: self.bools,
+            assignment: self.assignment,
+            split_identifiers: self.split_identifiers,
+            display: self.display,
+            arithmetic: self.arithmetic,
+            repeating: self.repeating,
+            tracing: self.tracing,
+            missing: self.missing,
+            watches: self.watches.clone(),
+        }
+    }
+
+    /// Replaces the state of the session with `snapshot`, keeping the limits
+    /// and functions it was configured with. Every formula is recalculated
+    /// at the next `recalculate`. Fails, leaving the session reset, if a
+    /// formula or alias does not read with the functions now registered.
+    pub fn restore(&mut self, snapshot: SessionSnapshot) -> Result<()> {
+        self.reset();
+        let restored = self.restore_state(snapshot);
+        if restored.is_err() {
+            self.reset();
+        }
+        restored
+    }
+
+    fn restore_state(&mut self, snapshot: SessionSnapshot) -> Result<()> {
+        // formulas are read with the settings they were defined under
+        self.empty_line = snapshot.empty_line;
+        self.grouping = snapshot.grouping;
+        self.percentages = snapshot.percentages;
+        self.bools = snapshot.bools;
+        self.assignment = snapshot.assignment;
+        self.split_identifiers = snapshot.split_identifiers;
+        self.display = snapshot.display;
+        self.arithmetic = snapshot.arithmetic;
+        self.repeating = snapshot.repeating;
+        self.tracing = snapshot.tracing;
+        self.missing = snapshot.missing;
+        self.watches = snapshot.watches;
+        for (name, text) in &snapshot.aliases {
+            self.aliases.define(name, text)?;
+        }
+        for (name, text) in &snapshot.formulas {
+            self.define_formula(name, text)?;
+        }
+        for entry in snapshot.history {
+            self.history.record(entry);
+        }
+        self.variables = snapshot.variables.into_iter().collect();
+        self.exact_values = snapshot.exact_values.into_iter().collect();
+        Ok(())
+    }
+
+    /// Restores the state the session started with, keeping the limits,
+    /// functions and preprocessor it was configured with.
+    pub fn reset(&mut self) {
+        *self = Self {
+            limits: std::mem::take(&mut self.limits),
+            registry: std::mem::take(&mut self.registry),
+            preprocessor: self.preprocessor.take(),
+            ..Self::new()
+        };
+    }
+}
+
+/// How `format_result` writes the value of a line, as the settings of the
+/// same names on a `Session` do.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResultFormat {
+    pub display: DisplayOptions,
+    /// Whether a line ending in a comparison or a logical operator is
+    /// written as `true` or `false`.
+    pub bools: bool,
+}
+
+/// Evaluates `input` over `variables` and writes its value just as the
+/// REPL would with `format`, binding `_` as it does. Input is read grouped
+/// when `format.display` writes it so, as `:mode grouping` has the REPL do.
+pub fn format_result(input: &str, variables: &mut HashMap<String, Number>, format: &ResultFormat) -> Result<String> {
+    let mut session = Session::new();
+    session.display = format.display;
+    session.grouping = format.display.grouping;
+    session.bools = format.bools;
+    session.variables = std::mem::take(variables);
+    let shown = session.show_line(input);
+    *variables = session.variables;
+    shown
+}
+
+/// The variables named by environment variables beginning with `prefix`, so
+/// that `CALC_x=3` gives `x` with a prefix of `CALC_`. Each value that is not
+/// a number is passed to `skipped` instead, with the name it would have had.
+pub fn variables_from_env(prefix: &str, mut skipped: impl FnMut(&str, CalcError)) -> HashMap<String, Number> {
+    let mut variables = HashMap::new();
+    for (key, value) in std::env::vars_os() {
+        let (Some(key), Some(value)) = (key.to_str(), value.to_str()) else {
+            continue;
+        };
+        let Some(name) = key.strip_prefix(prefix).filter(|name| !name.is_empty()) else {
+            continue;
+        };
+        match value.trim().parse() {
+            Ok(number) => _ = variables.insert(name.into(), number),
+            Err(_) => skipped(name, CalcError::invalid_number(value.into())),
+        }
+    }
+    variables
+}
+
+/// Configures a `Session` with functions and constants of the embedder's own. Registration
+/// errors, such as reusing a built-in name, surface from `build`.
+pub struct SessionBuilder {
+    registry: Registry,
+    error: Option<CalcError>,
+}
+
+impl SessionBuilder {
+    fn new() -> Self {
+        Self {
+            registry: Registry::default(),
+            error: None,
+        }
+    }
+
+    fn register(mut self, name: &str, callable: Callable) -> Self {
+        if self.error.is_none() {
+            self.error = self.registry.register(name, callable).err();
+        }
+        self
+    }
+
+    pub fn provider(mut self, provider: impl FunctionProvider + 'static) -> Self {
+        self.registry.provide(provider);
+        self
+    }
+
+    pub fn constant(mut self, name: &str, value: Number) -> Self {
+        if self.error.is_none() {
+            self.error = self.registry.register_constant(name, value).err();
+        }
+        self
+    }
+
+    pub fn unary(self, name: &str, action: impl Fn(Number) -> Number + 'static) -> Self {
+        self.register(name, Callable::unary(Rc::new(action)))
+    }
+
+    pub fn binary_fn(self, name: &str, action: impl Fn(Number, Number) -> Number + 'static) -> Self {
+        self.register(name, Callable::binary(Rc::new(action)))
+    }
+
+    /// Looks up the variables the session has no value for with `resolver`,
+    /// whose answers may change over the session.
+    pub fn resolver(mut self, resolver: impl Fn(&str) -> Option<Number> + 'static) -> Self {
+        self.registry.resolve_with(resolver);
+        self
+    }
+
+    pub fn variadic(self, name: &str, least: u32, action: impl Fn(&[Number]) -> Number + 'static) -> Self {
+        self.register(name, Callable::variadic(least, Rc::new(action)))
+    }
+
+    pub fn build(self) -> Result<Session> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(Session {
+                registry: self.registry,
+                ..Session::new()
+            }),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::prelude::*;
+
+/// An operand of a node, as the part of the output that computes it.
+struct Operand {
+    start: usize,
+    /// Whether the operand is a single value node.
+    literal: bool,
+}
+
+/// Rewrites `expression` to be cheaper to evaluate, folding operations on
+/// literals into their values and dropping those that give their operand
+/// back unchanged: `x * 1`, `1 * x`, `x / 1`, `x ^ 1`, `x - 0`, `x + -0`,
+/// `+x` and `--x`.
+///
+/// The result evaluates to exactly what the original did, bit for bit, so
+/// nothing is reordered, `x + 0` is kept since it turns `-0` into `0`, and
+/// `x * 0` is kept since it is NaN for infinite `x`. Calls to registered
+/// functions are never folded, as they need not give the same value twice.
+pub fn simplify<N: CalcNum>(expression: &[ExprNode<N>]) -> Vec<ExprNode<N>> {
+    let mut output = Vec::new();
+    let mut operands = Vec::<Operand>::new();
+    for node in expression {
+        let takes = match node {
+            ExprNode::value(_) | ExprNode::load(_) => 0,
+            ExprNode::unary(_) | ExprNode::assign(_) | ExprNode::dup => 1,
+            ExprNode::binary(_) | ExprNode::series(_) => 2,
+            ExprNode::call(_, count) | ExprNode::nderiv(_, count) | ExprNode::integrate(_, count) => *count as usize,
+            ExprNode::under(_) => 3,
+            ExprNode::assign_all(names) => names.len(),
+        };
+        let taken = operands.split_off(operands.len().saturating_sub(takes));
+        let start = taken.first().map_or(output.len(), |operand| operand.start);
+
+        if taken.len() == takes && taken.iter().all(|operand| operand.literal) {
+            let values = taken.iter().map(|operand| literal(&output, operand)).collect::<Vec<_>>();
+            if let Some(value) = fold(node, &values) {
+                output.truncate(start);
+                output.push(ExprNode::value(value));
+                operands.push(Operand {start, literal: true});
+                continue;
+            }
+        }
+
+        if let [left, right] = taken.as_slice() {
+            match kept(node, left.literal.then(|| literal(&output, left)), right.literal.then(|| literal(&output, right))) {
+                Some(Side::left) => {
+                    output.truncate(right.start);
+                    operands.push(Operand {start, literal: left.literal});
+                    continue;
+                },
+                Some(Side::right) => {
+                    output.drain(left.start..right.start);
+                    operands.push(Operand {start, literal: right.literal});
+                    continue;
+                },
+                None => (),
+            }
+        }
+
+        match node {
+            ExprNode::unary(Function::positive) => {
+                operands.extend(taken);
+                continue;
+            },
+            // the operand's last node is the one giving it
+            ExprNode::unary(Function::negative) if output.last() == Some(&ExprNode::unary(Function::negative)) => {
+                output.pop();
+                operands.extend(taken);
+                continue;
+            },
+            ExprNode::series(series) => output.push(ExprNode::series(Series {
+                body: simplify(&series.body),
+                ..series.clone()
+            })),
+            node => output.push(node.clone()),
+        }
+
+        match node {
+            ExprNode::dup | ExprNode::under(_) => {
+                operands.push(Operand {start, literal: false});
+                operands.push(Operand {start: output.len() - 1, literal: false});
+            },
+            _ => operands.push(Operand {start, literal: matches!(node, ExprNode::value(_))}),
+        }
+    }
+    output
+}
+
+fn literal<N: CalcNum>(output: &[ExprNode<N>], operand: &Operand) -> N {
+    match output[operand.start] {
+        ExprNode::value(value) => value,
+        _ => unreachable!("literal operands are single values"),
+    }
+}
+
+/// The value of `node` applied to the literals `values`, if it is a built-in
+/// that always gives the same value for them.
+fn fold<N: CalcNum>(node: &ExprNode<N>, values: &[N]) -> Option<N> {
+    match node {
+        // its search is left to evaluating, which limits how long it runs
+        ExprNode::unary(function) if function.searches_primes() => None,
+        // as is whether strict evaluating refuses the value
+        ExprNode::unary(function) if !function.is_defined_for(values[0]) => None,
+        ExprNode::unary(function) => Some(function.clone().call()(values[0])),
+        // a division by zero is left for evaluating to report
+        ExprNode::binary(function) => function.apply(values[0], values[1]).ok(),
+        ExprNode::call(name, count) => {
+            let function = VariedFunction::from_identifier(name)?;
+            // whether NaN is a value or missing data is left to linking
+            if function.aggregates_from().is_some() && values.iter().any(|value| value.is_nan()) {
+                return None;
+            }
+            // a wrong count is left for linking to report
+            check_arity(name, function.arity(), *count).ok()?;
+            // as is any error it gives
+            function.call()(values).ok()
+        },
+        _ => None,
+    }
+}
+
+enum Side {
+    left, right,
+}
+
+/// Which operand `node` gives back unchanged, given whichever of `left` and
+/// `right` are literals.
+fn kept<N: CalcNum>(node: &ExprNode<N>, left: Option<N>, right: Option<N>) -> Option<Side> {
+    use BinaryFunction::*;
+
+    let is_one = |value: Option<N>| value == Some(N::one());
+    let is_zero = |value: Option<N>, negative: bool| value.is_some_and(|value|
+        value == N::zero() && value.to_f64().is_sign_negative() == negative);
+    match node {
+        ExprNode::binary(multiplication) if is_one(right) => Some(Side::left),
+        ExprNode::binary(multiplication) if is_one(left) => Some(Side::right),
+        ExprNode::binary(division | exponentiation) if is_one(right) => Some(Side::left),
+        ExprNode::binary(subtraction) if is_zero(right, false) => Some(Side::left),
+        ExprNode::binary(addition) if is_zero(right, true) => Some(Side::left),
+        ExprNode::binary(addition) if is_zero(left, true) => Some(Side::right),
+        _ => None,
+    }
+}
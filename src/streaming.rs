@@ -0,0 +1,160 @@
+//! Evaluates programs too large to hold in memory, statement by statement,
+//! handing each node to evaluation as soon as the parser lets it go.
+
+use crate::error_handling::*;
+use crate::evaluating::*;
+use crate::linking::*;
+use crate::numbers::*;
+use crate::parsing::*;
+use crate::registry::*;
+use crate::scanning::*;
+use crate::Map;
+
+use std::io::{self, BufRead};
+use std::mem;
+use std::time::Instant;
+
+/// Evaluates the statements `input` holds, separated by newlines or `;`,
+/// calling `each` with the result of every one that is not blank as soon
+/// as it ends. A statement that fails is read to its end and skipped.
+///
+/// Only the operand stack, the parser's own stack and a buffer's worth of
+/// text are held at once, so the input may be of any length. The stacks
+/// are bounded by `limits.max_depth` and each statement by `limits.timeout`.
+pub fn evaluate_stream<N: CalcNum, R: BufRead>(mut input: R, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits, mut each: impl FnMut(Result<N>)) -> io::Result<()> {
+    let mut statement = Statement::new();
+    let mut pending = Vec::new();
+    loop {
+        let buffer = input.fill_buf()?;
+        if buffer.is_empty() {
+            statement.feed(&decode(mem::take(&mut pending))?, variables, registry, limits);
+            if let Some(result) = statement.end(variables, registry, limits) {
+                each(result);
+            }
+            return Ok(());
+        }
+
+        if let Some(end) = buffer.iter().position(|byte| matches!(byte, b'\n' | b';')) {
+            pending.extend_from_slice(&buffer[..end]);
+            input.consume(end + 1);
+            statement.feed(&decode(mem::take(&mut pending))?, variables, registry, limits);
+            if let Some(result) = statement.end(variables, registry, limits) {
+                each(result);
+            }
+        } else {
+            let (read, length) = (pending.len(), buffer.len());
+            pending.extend_from_slice(buffer);
+            input.consume(length);
+            // text up to a separator cannot end partway through a token
+            if let Some(cut) = pending[read..].iter().rposition(|byte| separates(*byte)) {
+                let rest = pending.split_off(read + cut + 1);
+                statement.feed(&decode(mem::replace(&mut pending, rest))?, variables, registry, limits);
+            }
+        }
+    }
+}
+
+/// Whether `byte` always falls between tokens, ending any token before it.
+fn separates(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || matches!(byte, b'+' | b'-' | b'*' | b'/' | b'^' | b'(' | b')' | b',')
+}
+
+/// Separators are ASCII, so text cut at them is cut between characters.
+fn decode(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The statement being read, with the values its settled nodes gave.
+struct Statement<N> {
+    parser: Parser<N>,
+    slots: Vec<N>,
+    settled: Vec<ExprNode<N>>,
+    blank: bool,
+    /// When the statement must be done by, set by its first token.
+    deadline: Option<Instant>,
+    failure: Option<CalcError>,
+}
+
+impl<N: CalcNum> Statement<N> {
+    fn new() -> Self {
+        let mut parser = Parser::new();
+        // a streamed statement may be as long as it likes, its depth bounded
+        // by `limits.max_depth` instead
+        (parser.max_nesting, parser.max_tokens) = (None, None);
+        parser.begin(None);
+        Self {
+            parser,
+            slots: Vec::new(),
+            settled: Vec::new(),
+            blank: true,
+            deadline: None,
+            failure: None,
+        }
+    }
+
+    fn feed(&mut self, text: &str, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits) {
+        if self.failure.is_none() {
+            if let Err(e) = self.try_feed(text, variables, registry, limits) {
+                self.failure = Some(e);
+            }
+        }
+    }
+
+    fn try_feed(&mut self, text: &str, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits) -> Result<()> {
+        for token in StringScanner::new(text.into()) {
+            if mem::take(&mut self.blank) {
+                self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+            }
+            self.parser.feed(token?, variables, registry)?;
+            if let Some(most) = limits.max_depth {
+                if self.parser.depth() > most {
+                    return Err(CalcError::depth_limit(most));
+                }
+            }
+            self.settle(variables, registry, limits)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluates whatever nodes the parser has let go of.
+    fn settle(&mut self, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits) -> Result<()> {
+        let Some(nodes) = self.parser.take_settled() else {
+            return Ok(());
+        };
+        self.settled.extend(nodes);
+        if self.settled.is_empty() {
+            return Ok(());
+        }
+
+        // the time limit covers the whole statement, not each part of it
+        let mut limits = limits.clone();
+        if let (Some(timeout), Some(deadline)) = (limits.timeout, self.deadline) {
+            limits.timeout = Some(deadline.checked_duration_since(Instant::now()).ok_or(CalcError::timeout(timeout))?);
+        }
+        let compiled = link_part(&self.settled, registry)?;
+        self.settled.clear();
+        evaluate_onto(&compiled, &mut self.slots, variables, &limits)
+    }
+
+    /// Ends the statement, giving its result unless it was blank.
+    fn end(&mut self, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits) -> Option<Result<N>> {
+        let result = (!self.blank).then(|| match self.failure.take() {
+            Some(e) => Err(e),
+            None => self.conclude(variables, registry, limits),
+        });
+        self.parser.begin(None);
+        self.blank = true;
+        self.deadline = None;
+        self.failure = None;
+        self.slots.clear();
+        self.settled.clear();
+        result
+    }
+
+    fn conclude(&mut self, variables: &mut Map<String, N>, registry: &Registry<N>, limits: &Limits) -> Result<N> {
+        self.parser.finish(variables, registry)?;
+        self.settle(variables, registry, limits)?;
+        self.slots.first().copied()
+            .ok_or_else(|| CalcError::internal("a statement left no result".into()))
+    }
+}
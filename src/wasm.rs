@@ -0,0 +1,26 @@
+//! Bindings for running a calculator session from JavaScript.
+
+use crate::formatting::*;
+use crate::session::*;
+
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+pub struct WasmSession {
+    session: Session,
+}
+
+#[wasm_bindgen]
+pub fn new_session() -> WasmSession {
+    WasmSession {session: Session::new()}
+}
+
+/// Evaluates a line in `session`, returning the formatted result, or a JSON
+/// object like `{"error": "..."}` describing why it failed.
+#[wasm_bindgen]
+pub fn eval_line(session: &mut WasmSession, input: &str) -> String {
+    match session.session.eval_line(input) {
+        Ok(value) => format_value(value, &session.session.display),
+        Err(e) => format!("{{\"error\": \"{}\"}}", escape_json(&e.to_string())),
+    }
+}
@@ -0,0 +1,30 @@
+//! Every shape of malformed assignment target is reported as one, whether
+//! or not the variables in it are defined.
+
+use calc_rs::{ErrorKind, Session};
+
+#[test]
+fn malformed_targets_are_reported() {
+    let mut session = Session::new();
+    for defined in [false, true] {
+        if defined {
+            session.eval_line("x = 1").unwrap();
+        }
+        for line in ["2 = 3", "(x) = 3", "x + 1 = 2", "(z = 1) + 1", "pi = 3", "min(x, 2) = 1", "x = y = 2"] {
+            let error = session.eval_line(line).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::syntax, "reading {}", line);
+            assert_eq!(error.code(), "invalid_assignment_target", "reading {} with x defined: {}", line, defined);
+        }
+    }
+}
+
+#[test]
+fn undefined_names_are_reported_otherwise() {
+    let mut session = Session::new();
+    for line in ["nope + 1", "nope +", "(nope", "x = nope * 2", "1 + nope + other"] {
+        let error = session.eval_line(line).unwrap_err();
+        assert_eq!(error.code(), "undefined", "reading {}", line);
+        assert_eq!(error.offending_text(), Some("nope"));
+    }
+    assert!(!session.variables.contains_key("x"));
+}
@@ -0,0 +1,46 @@
+//! A session built with functions and constants of the embedder's own, and
+//! the names it refuses.
+
+use calc_rs::Session;
+
+#[test]
+fn registered_functions_and_constants_are_used() {
+    let mut session = Session::builder()
+        .unary("double", |x| x * 2.0)
+        .binary_fn("hyp", |a, b| a.hypot(b))
+        .variadic("total", 1, |values| values.iter().sum())
+        .constant("g", 9.81)
+        .build()
+        .unwrap();
+    assert_eq!(session.eval_line("double 3").unwrap(), 6.0);
+    assert_eq!(session.eval_line("hyp(3, 4)").unwrap(), 5.0);
+    assert_eq!(session.eval_line("total(1, 2, 3, 4)").unwrap(), 10.0);
+    assert_eq!(session.eval_line("g * 2").unwrap(), 19.62);
+    assert_eq!(session.eval_line("hyp(1)").unwrap_err().code(), "wrong_argument_count");
+    assert_eq!(session.eval_line("hyp(1, 2, 3)").unwrap_err().code(), "wrong_argument_count");
+}
+
+#[test]
+fn names_in_use_are_refused() {
+    for name in ["sin", "pi", "sum", "double"] {
+        let built = Session::builder().unary("double", |x| x * 2.0).constant(name, 1.0).build();
+        assert_eq!(built.err().map(|error| error.code()), Some("already_defined"), "registering {}", name);
+    }
+    let built = Session::builder().unary("_", |x| x).build();
+    assert_eq!(built.err().map(|error| error.code()), Some("already_defined"));
+}
+
+#[test]
+fn names_must_be_identifiers() {
+    for name in ["", "2x", "_2", "a b", "x+1", "(f)"] {
+        let built = Session::builder().binary_fn(name, |a, _| a).build();
+        assert_eq!(built.err().map(|error| error.code()), Some("did_not_expect"), "registering {:?}", name);
+    }
+    assert!(Session::builder().unary("_half", |x| x / 2.0).unary("f2", |x| x).build().is_ok());
+}
+
+#[test]
+fn the_first_error_is_reported() {
+    let error = Session::builder().unary("sin", |x| x).unary("2x", |x| x).build().err().unwrap();
+    assert_eq!(error.to_string(), "'sin' is already defined");
+}
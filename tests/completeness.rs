@@ -0,0 +1,28 @@
+//! How complete partial input is, as an editor or a prompt asks.
+
+use calc_rs::parsing::{analyze_completeness, Completeness, Status};
+
+fn completeness(depth: usize, status: Status) -> Completeness {
+    Completeness {depth, status}
+}
+
+#[test]
+fn open_brackets_are_counted() {
+    assert_eq!(analyze_completeness("min(1, (2+"), completeness(2, Status::incomplete));
+    assert_eq!(analyze_completeness("min(1, (2+3)"), completeness(1, Status::incomplete));
+    assert_eq!(analyze_completeness("min(1, (2+3))"), completeness(0, Status::complete));
+}
+
+#[test]
+fn unfinished_operations_are_incomplete() {
+    assert_eq!(analyze_completeness("1 +"), completeness(0, Status::incomplete));
+    assert_eq!(analyze_completeness(""), completeness(0, Status::incomplete));
+    assert_eq!(analyze_completeness("x = 2 * y"), completeness(0, Status::complete));
+}
+
+#[test]
+fn input_nothing_can_finish_is_erroneous() {
+    for input in [")", "()", "1 + 2)", "1 2"] {
+        assert_eq!(analyze_completeness(input).status, Status::erroneous, "reading {}", input);
+    }
+}
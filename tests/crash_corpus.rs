@@ -0,0 +1,121 @@
+//! Inputs that once crashed the calculator. Every line of every file in
+//! `crash_corpus/` is evaluated, with and without grouping, and may succeed
+//! or fail but must not panic.
+
+use calc_rs::parsing::*;
+use calc_rs::evaluating::evaluate_limited;
+use calc_rs::{evaluate, ErrorKind, Limits, Number, Registry, Session};
+
+use std::collections::HashMap;
+
+#[test]
+fn corpus_does_not_panic() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/crash_corpus");
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let text = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        let mut session = Session::new();
+        session.variables.insert("x".into(), 2.0);
+        for line in text.lines() {
+            let _ = session.eval_line(line);
+            session.grouping = true;
+            let _ = session.eval_line(line);
+            session.grouping = false;
+        }
+    }
+}
+
+#[test]
+fn unclosed_call_is_an_error() {
+    let error = Session::new().eval_line("min(1, 2").unwrap_err();
+    assert_eq!(error.to_string(), "the parenthesis opened by 'min(' was never closed");
+}
+
+#[test]
+fn every_unclosed_parenthesis_is_reported() {
+    let error = Session::new().eval_line("min(1, (2 + max(3").unwrap_err();
+    assert_eq!(error.code(), "unclosed_parentheses");
+    assert_eq!(error.to_string(), "3 parentheses, opened by 'min(', '(' and 'max(', were never closed");
+    assert_eq!(find_unclosed("min(1, (2 + max(3"), [0..4, 7..8, 12..16]);
+    assert_eq!(find_unclosed("((2 + 3) * (4"), [0..1, 11..12]);
+    assert!(find_unclosed("min(1, (2))").is_empty());
+}
+
+#[test]
+fn unbalanced_nodes_are_an_error() {
+    let expression = [ExprNode::value(1.0), ExprNode::binary(BinaryFunction::addition)];
+    assert_eq!(evaluate(&expression, &mut HashMap::new()).unwrap_err().kind(), ErrorKind::syntax);
+
+    let error = evaluate::<Number>(&[ExprNode::binary(BinaryFunction::addition)], &mut HashMap::new()).unwrap_err();
+    assert_eq!(error.code(), "stack_underflow");
+    let error = evaluate::<Number>(&[ExprNode::unary(Function::negative)], &mut HashMap::new()).unwrap_err();
+    assert_eq!(error.code(), "stack_underflow");
+    let error = evaluate::<Number>(&[], &mut HashMap::new()).unwrap_err();
+    assert_eq!((error.kind(), error.code()), (ErrorKind::syntax, "empty_expression"));
+    assert_eq!(Session::new().eval_line("").unwrap_err().kind(), ErrorKind::syntax);
+}
+
+#[test]
+fn extra_values_are_an_error_when_strict() {
+    // as `1 2` would give, were it read as two numbers
+    let expression = [ExprNode::value(1.0), ExprNode::value(2.0)];
+    assert_eq!(evaluate(&expression, &mut HashMap::new()).unwrap(), 1.0);
+    let strict = Limits {strict: true, ..Limits::default()};
+    let error = evaluate_limited(&expression, &mut HashMap::new(), &strict).unwrap_err();
+    assert_eq!(error.code(), "extra_values");
+    assert_eq!(error.to_string(), "expression left 2 values rather than one");
+}
+
+#[test]
+fn deeply_nested_series_are_an_error() {
+    let nested = |depth: usize| "sum(k, 1, 1, ".repeat(depth) + "k" + &")".repeat(depth);
+    // as deep as the default allows fits on a test thread's stack
+    assert_eq!(Session::new().eval_line(&nested(128)).unwrap(), 1.0);
+    let error = Session::new().eval_line(&nested(300)).unwrap_err();
+    assert_eq!(error.code(), "recursion_limit");
+
+    let mut session = Session::new();
+    session.limits.max_recursion = Some(10);
+    assert_eq!(session.eval_line(&nested(10)).unwrap(), 1.0);
+    assert_eq!(session.eval_line(&nested(11)).unwrap_err().to_string(), "evaluation may not be more than 10 series deep at once");
+}
+
+#[test]
+fn pathological_nesting_is_an_error() {
+    let nested = |depth: usize| "(".repeat(depth) + "1" + &")".repeat(depth);
+    let error = Session::new().eval_line(&nested(100_000)).unwrap_err();
+    assert_eq!(error.code(), "nesting_limit");
+    assert_eq!(Session::new().eval_line(&("-".repeat(100_000) + "1")).unwrap_err().code(), "nesting_limit");
+
+    let mut session = Session::new();
+    session.limits.max_nesting = Some(50);
+    assert_eq!(session.eval_line(&nested(50)).unwrap(), 1.0);
+    assert_eq!(session.eval_line(&nested(51)).unwrap_err().to_string(), "expression too deeply nested, more than 50 deep");
+    assert_eq!(session.eval_line(&("-".repeat(50) + "1")).unwrap(), 1.0);
+    assert_eq!(session.eval_line(&("-".repeat(51) + "1")).unwrap_err().code(), "nesting_limit");
+}
+
+#[test]
+fn deeply_nested_derivatives_are_an_error() {
+    let sines = |depth: usize| "diff(".to_string() + &"sin(".repeat(depth) + "x" + &")".repeat(depth) + ", x)";
+    let mut session = Session::new();
+    session.eval_line("x = 0").unwrap();
+    assert_eq!(session.eval_line(&sines(100)).unwrap(), 1.0);
+    assert_eq!(session.eval_line(&sines(3000)).unwrap_err().code(), "nesting_limit");
+    assert_eq!(session.eval_line(&("diff(".to_string() + &"-".repeat(3000) + "x, x)")).unwrap_err().code(), "nesting_limit");
+    assert_eq!(session.eval_line(&("diff(".to_string() + &"-".repeat(100) + "x, x)")).unwrap(), 1.0);
+}
+
+#[test]
+fn overlong_lines_are_an_error() {
+    let terms = |count: usize| vec!["1"; count].join(" + ");
+    let mut session = Session::new();
+    session.limits.max_tokens = Some(99);
+    assert_eq!(session.eval_line(&terms(50)).unwrap(), 50.0);
+    assert_eq!(session.eval_line(&terms(51)).unwrap_err().to_string(), "expression too long, more than 99 tokens");
+
+    let mut parser = Parser::<Number>::new();
+    parser.max_tokens = Some(3);
+    let registry = Registry::default();
+    assert!(parser.parse_line("1 + 2", &mut HashMap::new(), &registry).is_ok());
+    assert_eq!(parser.parse_line("1 + 2 + 3", &mut HashMap::new(), &registry).unwrap_err().code(), "token_limit");
+}
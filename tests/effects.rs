@@ -0,0 +1,42 @@
+//! Evaluating with effects reports each assignment a line makes, in order,
+//! and nothing else it binds along the way.
+
+use calc_rs::parsing::{parse, ExprNode};
+use calc_rs::scanning::StringScanner;
+use calc_rs::{evaluate_with_effects, Number};
+
+use std::collections::HashMap;
+
+fn parsed(text: &str, variables: &mut HashMap<String, Number>) -> Vec<ExprNode> {
+    parse(StringScanner::new(text.into()), variables).unwrap()
+}
+
+#[test]
+fn assignments_are_reported() {
+    let mut variables = HashMap::new();
+    let expression = parsed("x = 3", &mut variables);
+    assert_eq!(evaluate_with_effects(&expression, &mut variables).unwrap(), (3.0, vec![("x".into(), 3.0)]));
+    assert_eq!(variables["x"], 3.0);
+}
+
+#[test]
+fn several_assignments_are_reported_in_order() {
+    let mut variables = HashMap::from([("x".to_string(), 1.0)]);
+    let expression = parsed("a, b, c = x + 1, 5, x", &mut variables);
+    let (value, effects) = evaluate_with_effects(&expression, &mut variables).unwrap();
+    assert_eq!(value, 1.0);
+    assert_eq!(effects, [("a".into(), 2.0), ("b".into(), 5.0), ("c".into(), 1.0)]);
+}
+
+#[test]
+fn series_indices_are_not_effects() {
+    let mut variables = HashMap::new();
+    let expression = parsed("total = sum(k, 1, 4, k)", &mut variables);
+    let (value, effects) = evaluate_with_effects(&expression, &mut variables).unwrap();
+    assert_eq!(value, 10.0);
+    assert_eq!(effects, [("total".into(), 10.0)]);
+    assert!(!variables.contains_key("k"));
+
+    let expression = parsed("sum(k, 1, 4, k * 2)", &mut variables);
+    assert_eq!(evaluate_with_effects(&expression, &mut variables).unwrap(), (20.0, Vec::new()));
+}
@@ -0,0 +1,55 @@
+//! Encoding writes an expression out and decoding reads it back unchanged,
+//! while bytes that did not come from `encode` give an error.
+
+use calc_rs::encoding::*;
+use calc_rs::parsing::{parse, ExprNode};
+use calc_rs::scanning::StringScanner;
+use calc_rs::{evaluate, Number};
+
+use std::collections::HashMap;
+
+fn parsed(text: &str) -> Vec<ExprNode> {
+    parse(StringScanner::new(text.into()), &mut HashMap::<String, Number>::new()).unwrap()
+}
+
+#[test]
+fn decodes_what_was_encoded() {
+    let cases = [
+        "1 + 2 * 3",
+        "x = 2",
+        "a, b, c = 1, 2, 3",
+        "sum(k, 1, 3, prod(j, 1, k, j + k))",
+        "integrate(sin, 0, 1) + nderiv(cos, 1) + max(1, 2, 3)",
+        "1 < 2 < 3",
+    ];
+    for text in cases {
+        let expression = parsed(text);
+        assert_eq!(decode(&encode(&expression)).unwrap(), expression, "encoding {}", text);
+    }
+    let expression = parsed("sum(k, 1, 4, k * 2) - 1");
+    assert_eq!(evaluate(&decode(&encode(&expression)).unwrap(), &mut HashMap::new()).unwrap(), 19.0);
+}
+
+#[test]
+fn other_versions_are_refused() {
+    let mut bytes = encode(&parsed("1 + 2"));
+    bytes[0] = encoding_version + 1;
+    let error = decode(&bytes).unwrap_err();
+    assert_eq!(error.code(), "unknown_encoding_version");
+}
+
+#[test]
+fn cut_encodings_are_truncated() {
+    let bytes = encode(&parsed("y = max(2, 3) + sum(k, 1, 3, k)"));
+    for end in 0..bytes.len() {
+        let error = decode(&bytes[..end]).unwrap_err();
+        assert_eq!(error.code(), "truncated_encoding", "cut after {} bytes", end);
+    }
+}
+
+#[test]
+fn trailing_bytes_are_malformed() {
+    let mut bytes = encode(&parsed("1 + 2"));
+    bytes.push(0);
+    assert_eq!(decode(&bytes).unwrap_err().code(), "malformed_encoding");
+}
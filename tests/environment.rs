@@ -0,0 +1,33 @@
+//! Environment variables with a prefix become variables, skipping those
+//! that are not numbers.
+
+use calc_rs::session::{variables_from_env, Session};
+use calc_rs::ErrorKind;
+
+#[test]
+fn prefixed_variables_are_read() {
+    std::env::set_var("CALC_rate", "0.05");
+    std::env::set_var("CALC_count", " 12 ");
+    std::env::set_var("CALC_label", "twelve");
+    std::env::set_var("OTHER_rate", "7");
+
+    let mut skipped = Vec::new();
+    let mut session = Session::new();
+    session.variables = variables_from_env("CALC_", |name, error| skipped.push((name.to_string(), error.to_string())));
+    assert_eq!(session.eval_line("rate * 100").unwrap(), 5.0);
+    assert_eq!(session.eval_line("count").unwrap(), 12.0);
+    let error = session.eval_line("label").unwrap_err();
+    assert_eq!((error.kind(), error.offending_text()), (ErrorKind::undefined, Some("label")));
+    assert_eq!(skipped, vec![("label".to_string(), "'twelve' is not a valid number".to_string())]);
+}
+
+#[test]
+fn skipped_variables_are_warned_of_on_stderr() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_calc_rs"))
+        .args(["eval", "rate * 100", "--env", "CALC_"])
+        .env("CALC_rate", "0.05")
+        .env("CALC_label", "twelve")
+        .output().unwrap();
+    assert_eq!(std::str::from_utf8(&output.stdout).unwrap(), "5\n");
+    assert_eq!(std::str::from_utf8(&output.stderr).unwrap(), "Warning, label is not set: 'twelve' is not a valid number\n");
+}
@@ -0,0 +1,40 @@
+//! `calc_rs eval` evaluates its statements, separated by `;`, and writes
+//! the last value, or every one with `--all`.
+
+use std::process::{Command, Output};
+
+fn eval(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calc_rs")).arg("eval").args(arguments).output().unwrap()
+}
+
+fn written(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn writes_the_last_value() {
+    let output = eval(&["a=1; b=2; a+b"]);
+    assert!(output.status.success());
+    assert_eq!(written(&output), "3\n");
+}
+
+#[test]
+fn writes_every_value_when_asked() {
+    let output = eval(&["a=1; b=2;; a+b;", "--all"]);
+    assert!(output.status.success());
+    assert_eq!(written(&output), "1\n2\n3\n");
+}
+
+#[test]
+fn splits_on_tokens_rather_than_characters() {
+    let output = eval(&["max(1; 2); 4", "--all"]);
+    assert_eq!(written(&output), "Error, invalid character, ';', enountered\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn stops_at_the_first_error() {
+    let output = eval(&["1 +; 2", "--all"]);
+    assert_eq!(written(&output), "Error, expression ended abruptly\n");
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,33 @@
+//! Each family of functions is there exactly when its feature is, and
+//! naming one that is left out says which feature it needs. Run with
+//! `--no-default-features --features std` to check the smallest build.
+
+use calc_rs::{ErrorKind, Number, Session};
+
+fn check(line: &str, feature: &str, enabled: bool, value: Number) {
+    match Session::new().eval_line(line) {
+        Ok(result) => assert!(enabled && result == value, "{} gave {}", line, result),
+        Err(error) => {
+            assert!(!enabled, "{} failed: {}", line, error);
+            assert_eq!(error.kind(), ErrorKind::undefined);
+            assert_eq!(error.code(), "not_available");
+            assert!(error.to_string().contains(&format!("'{}' feature", feature)), "{}", error);
+        },
+    }
+}
+
+#[test]
+fn families_follow_features() {
+    let trig = cfg!(feature = "trig");
+    check("sin(0)", "trig", trig, 0.0);
+    check("0 |> cos", "trig", trig, 1.0);
+    check("integrate(sin, 0, 0)", "trig", trig, 0.0);
+    check("todeg(0)", "trig", trig, 0.0);
+
+    let stats = cfg!(feature = "stats");
+    check("max(1, 2)", "stats", stats, 2.0);
+    check("percentile(50, 1, 3)", "stats", stats, 2.0);
+
+    // the arithmetic functions are always there
+    assert_eq!(Session::new().eval_line("sqrt(4) + floor(1.5) + fmod(7, 3)").unwrap(), 4.0);
+}
@@ -0,0 +1,64 @@
+//! Drives the C interface as a foreign caller would, through raw pointers,
+//! freeing every string it is handed.
+
+#![cfg(feature = "ffi")]
+
+use calc_rs::ffi::*;
+
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// Evaluates `input`, returning the status and whichever string was set.
+unsafe fn eval(session: *mut calc_rs::Session, input: &[u8]) -> (i32, Option<String>) {
+    let input = CString::new(input).unwrap();
+    let mut result: *mut c_char = ptr::null_mut();
+    let mut error: *mut c_char = ptr::null_mut();
+    let status = calc_eval(session, input.as_ptr(), &mut result, &mut error);
+    let text = [result, error].into_iter().find(|text| !text.is_null()).map(|text| {
+        let owned = CStr::from_ptr(text).to_str().unwrap().to_owned();
+        calc_string_free(text);
+        owned
+    });
+    (status, text)
+}
+
+#[test]
+fn assignments_last_across_calls() {
+    unsafe {
+        let session = calc_session_new();
+        assert!(!session.is_null());
+        assert_eq!(eval(session, b"x = 2"), (CALC_OK, Some("2".into())));
+        assert_eq!(eval(session, b"x * 3"), (CALC_OK, Some("6".into())));
+        calc_session_free(session);
+    }
+}
+
+#[test]
+fn errors_come_through_out_error() {
+    unsafe {
+        let session = calc_session_new();
+        let (status, message) = eval(session, b"y + 1");
+        assert_eq!(status, CALC_ERROR);
+        assert!(message.unwrap().contains("'y'"));
+        calc_session_free(session);
+    }
+}
+
+#[test]
+fn bad_arguments_are_refused() {
+    unsafe {
+        let session = calc_session_new();
+        let input = CString::new("1 + 1").unwrap();
+        let mut result: *mut c_char = ptr::null_mut();
+        let mut error: *mut c_char = ptr::null_mut();
+        assert_eq!(calc_eval(ptr::null_mut(), input.as_ptr(), &mut result, &mut error), CALC_INVALID_ARGUMENT);
+        assert_eq!(calc_eval(session, ptr::null(), &mut result, &mut error), CALC_INVALID_ARGUMENT);
+        assert_eq!(calc_eval(session, input.as_ptr(), ptr::null_mut(), &mut error), CALC_INVALID_ARGUMENT);
+        assert_eq!(calc_eval(session, input.as_ptr(), &mut result, ptr::null_mut()), CALC_INVALID_ARGUMENT);
+
+        assert_eq!(eval(session, b"1 + \xff"), (CALC_INVALID_ARGUMENT, None));
+        calc_session_free(session);
+        calc_session_free(ptr::null_mut());
+        calc_string_free(ptr::null_mut());
+    }
+}
@@ -0,0 +1,155 @@
+//! How values and expressions are written, including the values with no
+//! literal of their own and expressions that need exactly some parentheses.
+
+use calc_rs::formatting::*;
+use calc_rs::parsing::parse;
+use calc_rs::scanning::StringScanner;
+use calc_rs::{evaluate, format_result, link, Number, Rational, Registry, ResultFormat};
+
+use std::collections::HashMap;
+
+fn value(value: Number) -> String {
+    format_value(value, &DisplayOptions::default())
+}
+
+#[test]
+fn tricky_values() {
+    assert_eq!(value(0.1), "0.1");
+    assert_eq!(value(1.0 / 3.0), "0.3333333333333333");
+    assert_eq!(value(0.1 + 0.2), "0.30000000000000004");
+    assert_eq!(value(1e20), "1e20");
+    assert_eq!(value(-1.5e-7), "-1.5e-7");
+    assert_eq!(value(123456789.0), "123456789");
+    assert_eq!(value(9007199254740993.0), "9007199254740992");
+    assert_eq!(value(0.0), "0");
+    assert_eq!(value(-0.0), "-0");
+    assert_eq!(value(Number::NAN), "NaN");
+    assert_eq!(value(Number::INFINITY), "inf");
+    assert_eq!(value(Number::NEG_INFINITY), "-inf");
+}
+
+#[test]
+fn display_options() {
+    let options = |precision, grouping, notation| DisplayOptions {precision, grouping, notation};
+    assert_eq!(format_value(2.0 / 3.0, &options(Some(3), false, Notation::automatic)), "0.667");
+    assert_eq!(format_value(-1234567.5, &options(None, true, Notation::automatic)), "-1,234,567.5");
+    assert_eq!(format_value(999.0, &options(Some(2), true, Notation::automatic)), "999.00");
+    assert_eq!(format_value(1234.5, &options(None, false, Notation::scientific)), "1.2345e3");
+    assert_eq!(format_value(1234.5, &options(Some(1), true, Notation::scientific)), "1.2e3");
+    assert_eq!(format_value(1e20, &options(None, false, Notation::positional)), "100000000000000000000");
+    assert_eq!(format_value(Number::NAN, &options(Some(2), true, Notation::scientific)), "NaN");
+}
+
+#[test]
+fn results_are_written_as_the_repl_writes_them() {
+    let scientific = ResultFormat {
+        display: DisplayOptions {precision: Some(2), grouping: false, notation: Notation::scientific},
+        bools: false,
+    };
+    let mut variables = HashMap::new();
+    assert_eq!(format_result("1/3", &mut variables, &scientific).unwrap(), "3.33e-1");
+    assert_eq!(format_result("x = 1234.5", &mut variables, &scientific).unwrap(), "1.23e3");
+    assert_eq!(variables["x"], 1234.5);
+    assert_eq!(variables["_"], 1234.5);
+
+    let grouped = ResultFormat {
+        display: DisplayOptions {grouping: true, ..DisplayOptions::default()},
+        bools: true,
+    };
+    assert_eq!(format_result("x * 1000", &mut variables, &grouped).unwrap(), "1,234,500");
+    assert_eq!(format_result("1,000 + 1", &mut variables, &grouped).unwrap(), "1,001");
+    assert_eq!(format_result("max(x; 2) > 2", &mut variables, &grouped).unwrap(), "true");
+    assert_eq!(format_result("1 +", &mut variables, &grouped).unwrap_err().code(), "abrupt_end");
+    assert_eq!(format_result("x > 2", &mut variables, &ResultFormat::default()).unwrap(), "1");
+}
+
+#[test]
+fn values_read_back_as_the_same_bits() {
+    let values = [
+        0.1, 0.1 + 0.2, 1.0 / 3.0, 2.0 / 3.0,
+        // the first whole number the default number type cannot hold
+        9_007_199_254_740_993u64 as Number,
+        Number::MIN_POSITIVE, Number::MIN_POSITIVE / 3.0, Number::from_bits(1),
+        Number::MAX, -Number::MAX, 1e20, -1.5e-7, 1e-5, 123456789.0, 0.0, -0.0,
+    ];
+    for value in values {
+        for notation in [Notation::automatic, Notation::positional, Notation::scientific] {
+            for grouping in [false, true] {
+                let text = format_value(value, &DisplayOptions {precision: None, grouping, notation});
+                let scanner = StringScanner::new(text.clone());
+                let scanner = if grouping { scanner.with_grouping() } else { scanner };
+                let expression = parse(scanner, &mut HashMap::<String, Number>::new()).unwrap();
+                let read = evaluate(&expression, &mut HashMap::new()).unwrap();
+                assert_eq!(read.to_bits(), value.to_bits(), "reading back {}", text);
+            }
+        }
+    }
+}
+
+fn formatted(text: &str, options: FormatOptions) -> String {
+    let expression = parse(StringScanner::new(text.into()), &mut HashMap::<String, Number>::new()).unwrap();
+    format_expression(&link(&expression, &Registry::default()).unwrap(), options)
+}
+
+#[test]
+fn expressions_read_back_alike() {
+    let cases = [
+        ("1+2*3", "1 + 2 * 3"),
+        ("(1+2)*3", "(1 + 2) * 3"),
+        ("1-(2-3)", "1 - (2 - 3)"),
+        ("(1-2)-3", "1 - 2 - 3"),
+        ("-2^2", "-2 ^ 2"),
+        ("(-2)^2", "(-2) ^ 2"),
+        ("2^-1*4", "2 ^ -1 * 4"),
+        ("(2^3)^2", "(2 ^ 3) ^ 2"),
+        ("2^(3^2)", "2 ^ 3 ^ 2"),
+        ("1<2<3<4", "1 < 2 < 3 < 4"),
+        ("(1<2)<3", "(1 < 2) < 3"),
+        ("1==2&&3<4||0", "1 == 2 && 3 < 4 || 0"),
+        ("min(1,2+3,-4)", "min(1, 2 + 3, -4)"),
+        ("sum(k,1,10,k^2)", "sum(k, 1, 10, k ^ 2)"),
+        ("integrate(sin,0,1)", "integrate(sin, 0, 1)"),
+        ("4 |> sqrt", "sqrt(4)"),
+        ("x = 2*3", "x = 2 * 3"),
+        ("x,y=1,2*3", "x, y = 1, 2 * 3"),
+        ("group(2+3)*4", "(2 + 3) * 4"),
+    ];
+    for (input, expected) in cases {
+        let text = formatted(input, FormatOptions::default());
+        assert_eq!(text, expected, "formatting {}", input);
+        assert_eq!(formatted(&text, FormatOptions::default()), text, "reading back {}", text);
+    }
+}
+
+#[test]
+fn format_options() {
+    let tight = FormatOptions {spaced: false, ..FormatOptions::default()};
+    assert_eq!(formatted("x = min(1, 2 - -3)", tight), "x=min(1,2--3)");
+    let grouped = FormatOptions {
+        values: DisplayOptions {grouping: true, ..DisplayOptions::default()},
+        ..FormatOptions::default()
+    };
+    assert_eq!(formatted("max(1234, 5)", grouped), "max(1,234; 5)");
+    // parentheses the expression does not need are kept where `group` asks for them
+    assert_eq!(formatted("group(2*3) + group(4)", FormatOptions::default()), "(2 * 3) + (4)");
+}
+
+#[test]
+fn repeating_decimals() {
+    let repeating = |numerator, denominator| format_repeating(Rational::new(numerator, denominator));
+    assert_eq!(repeating(1, 3), "0.(3)");
+    assert_eq!(repeating(1, 6), "0.1(6)");
+    assert_eq!(repeating(-22, 7), "-3.(142857)");
+    assert_eq!(repeating(1, 8), "1/8");
+    assert_eq!(repeating(4, 2), "2");
+    assert_eq!(repeating(1, 0), "inf");
+    // a period of 108 digits is too long to write out
+    assert_eq!(repeating(1, 109), "1/109");
+}
+
+#[test]
+fn json_strings_are_escaped() {
+    assert_eq!(escape_json("plain"), "plain");
+    assert_eq!(escape_json("a \"b\" \\ c"), "a \\\"b\\\" \\\\ c");
+    assert_eq!(escape_json("one\ntwo\u{1}"), "one\\u000atwo\\u0001");
+}
@@ -0,0 +1,60 @@
+//! Formulas follow the variables they read, are recomputed after whatever
+//! they read, and may not read themselves.
+
+use calc_rs::session::Session;
+use calc_rs::Number;
+
+fn named(values: &[(&str, Number)]) -> Vec<(String, Number)> {
+    values.iter().map(|(name, value)| (name.to_string(), *value)).collect()
+}
+
+#[test]
+fn chains() {
+    let mut session = Session::new();
+    session.set("a", 1.0);
+    session.define_formula("c", "b + 1").unwrap();
+    session.define_formula("b", "a * 2").unwrap();
+    assert_eq!(session.recalculate().unwrap(), named(&[("b", 2.0), ("c", 3.0)]));
+
+    session.set("a", 3.0);
+    assert_eq!(session.recalculate().unwrap(), named(&[("b", 6.0), ("c", 7.0)]));
+    assert_eq!(session.eval_line("c").unwrap(), 7.0);
+    assert_eq!(session.recalculate().unwrap(), named(&[]));
+
+    // a formula is recomputed alone when only it changes
+    session.define_formula("c", "b - 1").unwrap();
+    assert_eq!(session.recalculate().unwrap(), named(&[("c", 5.0)]));
+}
+
+#[test]
+fn diamonds() {
+    let mut session = Session::new();
+    session.set("a", 2.0);
+    session.set("other", 0.0);
+    session.define_formula("d", "b + c").unwrap();
+    session.define_formula("b", "a ^ 2").unwrap();
+    session.define_formula("c", "sum(k, 1, a, k)").unwrap();
+    session.define_formula("unrelated", "other + pi").unwrap();
+    session.recalculate().unwrap();
+
+    session.set("a", 4.0);
+    // `d` comes once, after both of the formulas it reads
+    assert_eq!(session.recalculate().unwrap(), named(&[("b", 16.0), ("c", 10.0), ("d", 26.0)]));
+}
+
+#[test]
+fn cycles() {
+    let mut session = Session::new();
+    session.define_formula("a", "b + 1").unwrap();
+    session.define_formula("b", "a * 2").unwrap();
+    assert_eq!(session.recalculate().unwrap_err().to_string(),
+        "formulas depend on themselves, as in a -> b -> a");
+
+    session.define_formula("b", "b").unwrap();
+    assert_eq!(session.recalculate().unwrap_err().to_string(),
+        "formulas depend on themselves, as in b -> b");
+
+    // setting a variable breaks the cycle through it
+    session.set("b", 5.0);
+    assert_eq!(session.recalculate().unwrap(), named(&[("a", 6.0)]));
+}
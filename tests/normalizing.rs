@@ -0,0 +1,59 @@
+//! Expressions that are the same hash alike, however they were written, and
+//! normalizing makes those differing only in the order of commutative
+//! operands the same.
+
+use calc_rs::normalizing::normalize;
+use calc_rs::parsing::{canonical_hash, parse, Assignment, ExprNode};
+use calc_rs::scanning::StringScanner;
+use calc_rs::{evaluate, Number, Parser, Registry};
+
+use std::collections::HashMap;
+
+fn parsed(text: &str) -> Vec<ExprNode> {
+    parse(StringScanner::new(text.into()), &mut HashMap::<String, Number>::new()).unwrap()
+}
+
+#[test]
+fn spacing_does_not_change_the_hash() {
+    assert_eq!(canonical_hash(&parsed("2+3")), canonical_hash(&parsed("2 +  3")));
+    assert_eq!(canonical_hash(&parsed("min(1,2)*4")), canonical_hash(&parsed("min( 1, 2 ) * 4")));
+}
+
+#[test]
+fn order_changes_the_hash() {
+    assert_ne!(canonical_hash(&parsed("2+3")), canonical_hash(&parsed("3+2")));
+    assert_ne!(canonical_hash(&parsed("2-3")), canonical_hash(&parsed("2+3")));
+}
+
+#[test]
+fn commutative_operands_are_ordered() {
+    let cases = [("3+2", "2+3"), ("(1+4)*2", "2*(4+1)"), ("min(3, 1) + max(4, 2)", "max(2, 4) + min(1, 3)")];
+    for (left, right) in cases {
+        let (left, right) = (normalize(&parsed(left)), normalize(&parsed(right)));
+        assert_eq!(left, right);
+        assert_eq!(canonical_hash(&left), canonical_hash(&right));
+    }
+}
+
+#[test]
+fn other_operands_keep_their_order() {
+    let (left, right) = (normalize(&parsed("5-3")), normalize(&parsed("3-5")));
+    assert_ne!(left, right);
+    assert_ne!(canonical_hash(&left), canonical_hash(&right));
+    assert_eq!(evaluate(&left, &mut HashMap::new()).unwrap(), 2.0);
+    assert_eq!(evaluate(&right, &mut HashMap::new()).unwrap(), -2.0);
+    assert_ne!(normalize(&parsed("8/2")), normalize(&parsed("2/8")));
+}
+
+#[test]
+fn operands_assigning_keep_their_place() {
+    let mut parser = Parser::<Number>::new();
+    parser.assignment = Assignment::expression;
+    let registry = Registry::default();
+    for (line, value) in [("(x = 5) + x", 10.0), ("x + (x = 5)", 6.0), ("(x = 5) * x * 2", 50.0), ("sum(k, 1, 2, (x = k)) + x", 5.0)] {
+        let mut variables = HashMap::from([("x".to_string(), 1.0)]);
+        let expression = parser.parse_line(line, &mut variables.clone(), &registry).unwrap().to_vec();
+        assert_eq!(evaluate(&expression, &mut variables.clone()).unwrap(), value, "evaluating {}", line);
+        assert_eq!(evaluate(&normalize(&expression), &mut variables).unwrap(), value, "evaluating {} normalized", line);
+    }
+}
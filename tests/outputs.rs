@@ -0,0 +1,59 @@
+//! `--output-vars` writes only the variables asked for once a script has
+//! run, as `name=value` lines or, with `--json`, a JSON object.
+
+#![allow(nonstandard_style)]
+
+use std::process::{Command, Output};
+
+const script: &str = "width = 3\nheight = 4\nwidth * height\narea = width * height\n";
+
+fn run(name: &str, arguments: &[&str]) -> Output {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, script).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_calc_rs")).arg("--file").arg(&path).args(arguments).output().unwrap()
+}
+
+fn written(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn writes_the_variables_asked_for() {
+    let output = run("lines.calc", &["--output-vars", "area,width"]);
+    assert!(output.status.success());
+    assert_eq!(written(&output), "area=12\nwidth=3\n");
+}
+
+#[test]
+fn writes_json_when_asked() {
+    let output = run("json.calc", &["--output-vars", "width, height", "--json"]);
+    assert!(output.status.success());
+    let object: serde_json::Value = serde_json::from_str(written(&output)).unwrap();
+    assert_eq!(object, serde_json::json!({"width": 3, "height": 4}));
+}
+
+#[test]
+fn fails_on_variables_never_set() {
+    let output = run("missing.calc", &["--output-vars", "area,depth,volume"]);
+    assert_eq!(written(&output), "");
+    assert_eq!(std::str::from_utf8(&output.stderr).unwrap(), "Error, identifier, 'depth', is not defined\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn json_names_are_escaped() {
+    let output = Command::new(env!("CARGO_BIN_EXE_calc_rs"))
+        .args(["eval", "1", "--env", "CALC_", "--output-vars", "a\"b\\c", "--json"])
+        .env("CALC_a\"b\\c", "2")
+        .output().unwrap();
+    let object: serde_json::Value = serde_json::from_str(written(&output)).unwrap();
+    assert_eq!(object, serde_json::json!({"a\"b\\c": 2}));
+}
+
+#[test]
+fn works_with_eval() {
+    let output = Command::new(env!("CARGO_BIN_EXE_calc_rs"))
+        .args(["eval", "a = 2; b = a ^ 10; b + 1", "--output-vars", "b,_"])
+        .output().unwrap();
+    assert_eq!(written(&output), "b=1024\n_=1025\n");
+}
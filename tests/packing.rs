@@ -0,0 +1,55 @@
+//! Packing lays an expression out without changing it: each name is held
+//! once, and series bodies, however deep, unpack to what they were.
+
+use calc_rs::packing::*;
+use calc_rs::parsing::{parse, ExprNode};
+use calc_rs::scanning::StringScanner;
+use calc_rs::{evaluate, Number};
+
+use std::collections::HashMap;
+
+fn parsed(text: &str) -> Vec<ExprNode> {
+    parse(StringScanner::new(text.into()), &mut HashMap::<String, Number>::new()).unwrap()
+}
+
+#[test]
+fn unpacks_alike() {
+    let cases = [
+        "1 + 2 * 3",
+        "x = 2",
+        "a, b, c = 1, 2, 3",
+        "x, x = 1, 2",
+        "sum(k, 1, 3, prod(j, 1, k, j + k)) + sum(k, 1, 2, k)",
+        "integrate(sin, 0, 1) + nderiv(cos, 1) + max(1, 2, 3)",
+        "1 < 2 < 3",
+    ];
+    for text in cases {
+        let expression = parsed(text);
+        assert_eq!(pack(&expression).unpack(), expression, "packing {}", text);
+    }
+}
+
+#[test]
+fn names_are_held_once() {
+    let packed = pack(&parsed("max(1, 2) + min(3, 4) * max(5, 6)"));
+    let names = packed.root().iter().filter_map(|node| match node {
+        PackedNode::call(name, _) => Some(*name),
+        _ => None,
+    }).collect::<Vec<_>>();
+    assert_eq!(names.iter().map(|name| packed.name(*name)).collect::<Vec<_>>(), ["max", "min", "max"]);
+    assert_eq!(names[0], names[2]);
+
+    let packed = pack(&parsed("a, b, a = 1, 2, 3"));
+    let [.., PackedNode::assign_all(assigned)] = packed.root() else {
+        panic!("no assignment in {:?}", packed);
+    };
+    assert_eq!(packed.names(*assigned).collect::<Vec<_>>(), ["a", "b", "a"]);
+}
+
+#[test]
+fn linked_expressions_evaluate_alike() {
+    let expression = parsed("sum(k, 1, 4, k * prod(j, 1, 2, j)) - 1");
+    let mut variables = HashMap::new();
+    assert_eq!(evaluate(&expression, &mut variables).unwrap(), 19.0);
+    assert_eq!(evaluate(&pack(&expression).unpack(), &mut variables).unwrap(), 19.0);
+}
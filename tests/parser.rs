@@ -0,0 +1,59 @@
+//! A parser reused from line to line reads each line just as a new one
+//! would, whatever the line before it left behind.
+
+use calc_rs::parsing::{Assignment, ExprNode};
+use calc_rs::{Number, Parser, Registry, Result};
+
+use std::collections::HashMap;
+
+fn fresh(line: &str, assignment: Assignment) -> Result<Vec<ExprNode>> {
+    let mut parser = Parser::new();
+    parser.assignment = assignment;
+    let mut variables = HashMap::from([("x".to_string(), 2.0)]);
+    parser.parse_line(line, &mut variables, &Registry::default()).map(<[_]>::to_vec)
+}
+
+#[test]
+fn lines_parse_as_they_would_alone() {
+    let lines = [
+        "1 + 2 * x",
+        "sum(k, 1, 3, k +",
+        "min(1, 2",
+        "diff(x ^ 2, x) + (",
+        "x + 1 = 2",
+        "nope * 3",
+        "a = 1 + (b = 2)",
+        "1 2",
+        ")",
+        "x, y = 1, 2",
+        "sum(k, 1, 3, k * x)",
+        "diff(x ^ 3, x)",
+        "max(x, 4) - 1",
+    ];
+    for assignment in [Assignment::statement, Assignment::expression] {
+        let mut parser = Parser::<Number>::new();
+        parser.assignment = assignment;
+        let registry = Registry::default();
+        let mut variables = HashMap::from([("x".to_string(), 2.0)]);
+        for line in lines {
+            let reused = parser.parse_line(line, &mut variables, &registry).map(<[_]>::to_vec);
+            let expected = fresh(line, assignment);
+            match (reused, expected) {
+                (Ok(reused), Ok(expected)) => assert_eq!(reused, expected, "reading {}", line),
+                (Err(reused), Err(expected)) => assert_eq!(reused.to_string(), expected.to_string(), "reading {}", line),
+                (reused, expected) => panic!("reading {} gave {:?} rather than {:?}", line, reused, expected),
+            }
+        }
+    }
+}
+
+#[test]
+fn a_good_line_follows_a_failed_one() {
+    let mut parser = Parser::<Number>::new();
+    let registry = Registry::default();
+    let mut variables = HashMap::new();
+    assert!(parser.parse_line("sum(k, 1, 3, (k", &mut variables, &registry).is_err());
+    let error = parser.parse_line("k", &mut variables, &registry).unwrap_err();
+    assert_eq!(error.code(), "undefined");
+    assert_eq!(parser.parse_line("2 * 3", &mut variables, &registry).unwrap().len(), 3);
+}
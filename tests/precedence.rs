@@ -0,0 +1,84 @@
+//! The precedence levels operators are reported at agree with how
+//! expressions parse, as does the metadata on operators and functions.
+
+use calc_rs::session::Session;
+use calc_rs::{binary_op, evaluate_str, functions, operators, precedence_of, unary_op, Associativity, Number};
+
+use std::collections::HashMap;
+
+#[test]
+fn levels_order_the_operators() {
+    assert!(precedence_of("+") < precedence_of("*"));
+    assert!(precedence_of("*") < precedence_of("^"));
+    assert_eq!(precedence_of("-"), precedence_of("+"));
+    assert_eq!(precedence_of("×"), precedence_of("*"));
+    assert_eq!(precedence_of("−"), precedence_of("-"));
+    assert!(precedence_of("||") < precedence_of("&&"));
+    assert!(precedence_of("==") < precedence_of("<"));
+    assert!(precedence_of("|>") < precedence_of("+"));
+    assert_eq!(precedence_of("("), None);
+    assert_eq!(precedence_of("%"), None);
+}
+
+#[test]
+fn tighter_operators_apply_first() {
+    let mut variables = HashMap::<String, Number>::new();
+    assert_eq!(evaluate_str("1 + 2 * 3 ^ 2", &mut variables).unwrap(), 19.0);
+    assert_eq!(evaluate_str("1 < 2 == 1", &mut variables).unwrap(), 1.0);
+}
+
+#[test]
+fn every_operator_is_described_once() {
+    let characters = "+-*/^=<>!&|%(),;×÷−".chars().map(String::from).collect::<Vec<_>>();
+    let pairs = characters.iter().flat_map(|a| characters.iter().map(move |b| a.clone() + b));
+    for symbol in characters.iter().cloned().chain(pairs) {
+        let described = |arity| operators().iter().filter(|operator| operator.symbol == symbol && operator.arity == arity).count();
+        assert_eq!(described(2), binary_op::<Number>(&symbol).is_some() as usize + (symbol == "|>") as usize, "binary {}", symbol);
+        assert_eq!(described(1), unary_op::<Number>(&symbol).is_some() as usize, "prefix {}", symbol);
+    }
+    for operator in operators().iter().filter(|operator| operator.arity == 2) {
+        assert_eq!(Some(operator.precedence), precedence_of(operator.symbol), "{}", operator.symbol);
+    }
+    let power = operators().iter().find(|operator| operator.symbol == "^").unwrap();
+    assert_eq!(power.associativity, Associativity::right);
+    let mut variables = HashMap::<String, Number>::new();
+    assert_eq!(evaluate_str("2 ^ 3 ^ 2", &mut variables).unwrap(), 512.0);
+    assert_eq!(evaluate_str("2 - 3 - 4", &mut variables).unwrap(), -5.0);
+}
+
+#[test]
+fn functions_take_the_arguments_they_are_described_with() {
+    let mut session = Session::new();
+    for function in functions() {
+        let (least, most) = function.arity;
+        // the first argument of a functional names a function, that of a series its index
+        let argument = |at: u32| match (function.name.as_str(), at) {
+            ("nderiv" | "integrate", 0) => "sq".to_string(),
+            ("sum" | "prod", 0) | ("diff", 1) => "k".to_string(),
+            _ => "1".to_string(),
+        };
+        let call = |count: u32| format!("{}({})", function.name, (0..count).map(argument).collect::<Vec<_>>().join(", "));
+        assert!(session.eval_line(&call(least)).is_ok(), "{}", call(least));
+        if least > 1 {
+            assert_eq!(session.eval_line(&call(least - 1)).unwrap_err().code(), "wrong_argument_count", "{}", call(least - 1));
+        }
+        if let Some(most) = most {
+            assert!(session.eval_line(&call(most)).is_ok(), "{}", call(most));
+            // a function of one argument reads a second as a stray `,`
+            let code = session.eval_line(&call(most + 1)).unwrap_err().code();
+            assert!(["wrong_argument_count", "did_not_expect"].contains(&code), "{} gave {}", call(most + 1), code);
+        }
+    }
+    assert!(functions().iter().any(|function| function.name == "smoothstep" && function.arity == (3, Some(3))));
+
+    let session = Session::builder()
+        .variadic("total", 1, |values| values.iter().sum())
+        .binary_fn("hypot", |a, b| (a * a + b * b).sqrt())
+        .build()
+        .unwrap();
+    let registered = session.functions().into_iter()
+        .filter(|function| ["total", "hypot"].contains(&function.name.as_str()))
+        .map(|function| (function.name, function.arity))
+        .collect::<Vec<_>>();
+    assert_eq!(registered, [("hypot".to_string(), (2, Some(2))), ("total".to_string(), (1, None))]);
+}
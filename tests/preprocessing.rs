@@ -0,0 +1,49 @@
+//! A host's preprocessor rewrites each statement before it is scanned, and
+//! the spans of what went wrong are found in the rewritten text.
+
+use calc_rs::scanning::tokenize;
+use calc_rs::session::Session;
+
+use std::borrow::Cow;
+
+/// Reads `$name` as the variable `field_name`.
+fn fields(line: &str) -> Cow<'_, str> {
+    match line.contains('$') {
+        true => Cow::Owned(line.replace('$', "field_")),
+        false => Cow::Borrowed(line),
+    }
+}
+
+fn session() -> Session {
+    let mut session = Session::new();
+    session.set_preprocessor(fields);
+    session.set("field_price", 4.0);
+    session
+}
+
+#[test]
+fn statements_are_rewritten_before_scanning() {
+    let mut session = session();
+    assert_eq!(session.eval_line("$price * 2").unwrap(), 8.0);
+    assert_eq!(session.show_statements("$quantity = 3; $price * $quantity").unwrap(), ["3", "12"]);
+    assert_eq!(session.variables["field_quantity"], 3.0);
+    session.define_formula("total", "$price * $quantity").unwrap();
+    assert_eq!(session.recalculate().unwrap(), vec![("total".to_string(), 12.0)]);
+    assert_eq!(session.eval_line("$missing").unwrap_err().to_string(), "identifier, 'field_missing', is not defined");
+
+    // the preprocessor is the host's, so it outlasts a reset
+    session.reset();
+    session.set("field_price", 1.0);
+    assert_eq!(session.eval_line("$price").unwrap(), 1.0);
+}
+
+#[test]
+fn spans_are_found_in_the_rewritten_text() {
+    let session = session();
+    let line = "$price * € + 1";
+    let rewritten = session.preprocess(line);
+    assert_eq!(rewritten, "field_price * € + 1");
+    let error = tokenize(&rewritten).into_iter().find_map(Result::err).unwrap();
+    assert_eq!(&rewritten[error.span], "€");
+    assert!(matches!(Session::new().preprocess(line), Cow::Borrowed(_)));
+}
@@ -0,0 +1,285 @@
+//! Random expressions from a small grammar, written out as text and run
+//! through scanning, parsing and evaluation, must give what evaluating
+//! their tree directly gives, and simplifying their nodes must not change
+//! what those give, nor must encoding and decoding them. Failures shrink to
+//! a minimal expression.
+
+#![allow(nonstandard_style)]
+
+use calc_rs::encoding::{decode, encode};
+use calc_rs::evaluating::{evaluate, evaluate_str};
+use calc_rs::parsing::{self, BinaryFunction, ExprNode};
+use calc_rs::simplifying::simplify;
+use calc_rs::Number;
+
+use proptest::prelude::*;
+
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug)]
+enum Operator {
+    add, sub, mul, div, pow,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Function {
+    sqrt, abs, sin,
+}
+
+#[derive(Clone, Debug)]
+enum Tree {
+    literal(Number),
+    variable,
+    negate(Box<Tree>),
+    binary(Operator, Box<Tree>, Box<Tree>),
+    call(Function, Box<Tree>),
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Self::add => "+",
+            Self::sub => "-",
+            Self::mul => "*",
+            Self::div => "/",
+            Self::pow => "^",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            Self::add | Self::sub => 1,
+            Self::mul | Self::div => 2,
+            Self::pow => 4,
+        }
+    }
+
+    fn node(self) -> BinaryFunction {
+        match self {
+            Self::add => BinaryFunction::addition,
+            Self::sub => BinaryFunction::subtraction,
+            Self::mul => BinaryFunction::multiplication,
+            Self::div => BinaryFunction::division,
+            Self::pow => BinaryFunction::exponentiation,
+        }
+    }
+}
+
+impl Function {
+    fn name(self) -> &'static str {
+        match self {
+            Self::sqrt => "sqrt",
+            Self::abs => "abs",
+            Self::sin => "sin",
+        }
+    }
+
+    fn node(self) -> parsing::Function {
+        match self {
+            Self::sqrt => parsing::Function::sqrt,
+            Self::abs => parsing::Function::abs,
+            Self::sin => parsing::Function::sin,
+        }
+    }
+}
+
+impl Tree {
+    /// How tightly the tree binds when written out, by the usual rules:
+    /// a sign binds tighter than `*` but looser than `^`.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::binary(operator, ..) => operator.precedence(),
+            Self::negate(_) => 3,
+            Self::literal(_) | Self::variable | Self::call(..) => 5,
+        }
+    }
+
+    /// The value of the tree with `x` standing for the variable, or `None`
+    /// if it divides by zero.
+    fn evaluate(&self, x: Number) -> Option<Number> {
+        Some(match self {
+            Self::literal(value) => *value,
+            Self::variable => x,
+            Self::negate(operand) => -operand.evaluate(x)?,
+            Self::binary(operator, left, right) => {
+                let (left, right) = (left.evaluate(x)?, right.evaluate(x)?);
+                match operator {
+                    Operator::add => left + right,
+                    Operator::sub => left - right,
+                    Operator::mul => left * right,
+                    Operator::div if right == 0.0 => return None,
+                    Operator::div => left / right,
+                    Operator::pow => left.powf(right),
+                }
+            },
+            Self::call(function, operand) => {
+                let operand = operand.evaluate(x)?;
+                match function {
+                    Function::sqrt => operand.sqrt(),
+                    Function::abs => operand.abs(),
+                    Function::sin => operand.sin(),
+                }
+            },
+        })
+    }
+
+    /// Writes the tree with only the parentheses the usual rules need.
+    fn render(&self) -> String {
+        match self {
+            Self::literal(value) => value.to_string(),
+            Self::variable => "x".into(),
+            Self::negate(operand) => format!("-{}", operand.render_above(3, false)),
+            Self::binary(operator, left, right) => {
+                let precedence = operator.precedence();
+                let is_power = matches!(operator, Operator::pow);
+                // `^` groups to the right and the others to the left, and a
+                // sign may begin any right operand, as in `2^-1` or `2*-1`
+                let left = left.render_above(precedence, is_power);
+                let right = match **right {
+                    Self::negate(_) => right.render(),
+                    _ => right.render_above(precedence, !is_power),
+                };
+                format!("{} {} {}", left, operator.symbol(), right)
+            },
+            Self::call(function, operand) => format!("{}({})", function.name(), operand.render()),
+        }
+    }
+
+    /// Writes the tree, in parentheses unless it binds tighter than
+    /// `precedence`, or as tightly when `strictly` is not set.
+    fn render_above(&self, precedence: u8, strictly: bool) -> String {
+        let own = self.precedence();
+        if own < precedence || (strictly && own == precedence) {
+            format!("({})", self.render())
+        } else {
+            self.render()
+        }
+    }
+
+    /// Writes the tree with every operation in parentheses.
+    fn render_grouped(&self) -> String {
+        match self {
+            Self::literal(value) => value.to_string(),
+            Self::variable => "x".into(),
+            Self::negate(operand) => format!("(-{})", operand.render_grouped()),
+            Self::binary(operator, left, right) =>
+                format!("({} {} {})", left.render_grouped(), operator.symbol(), right.render_grouped()),
+            Self::call(function, operand) => format!("{}({})", function.name(), operand.render_grouped()),
+        }
+    }
+
+    /// Writes the tree as nodes, reading the variable when evaluated.
+    fn emit(&self, output: &mut Vec<ExprNode>) {
+        match self {
+            Self::literal(value) => output.push(ExprNode::value(*value)),
+            Self::variable => output.push(ExprNode::load("x".into())),
+            Self::negate(operand) => {
+                operand.emit(output);
+                output.push(ExprNode::unary(parsing::Function::negative));
+            },
+            Self::binary(operator, left, right) => {
+                left.emit(output);
+                right.emit(output);
+                output.push(ExprNode::binary(operator.node()));
+            },
+            Self::call(function, operand) => {
+                operand.emit(output);
+                output.push(ExprNode::unary(function.node()));
+            },
+        }
+    }
+}
+
+fn tree() -> impl Strategy<Value = Tree> {
+    // quarters from 0 to 5 are exact, so text and tree start from the same values
+    // zero and one come up often, being where rewrites could go wrong
+    let literal = prop_oneof![quarter(), Just(0.0), Just(1.0)].prop_map(Tree::literal);
+    let leaf = prop_oneof![3 => literal, 1 => Just(Tree::variable)];
+    leaf.prop_recursive(5, 32, 2, |inner| {
+        let operator = prop_oneof![
+            Just(Operator::add), Just(Operator::sub),
+            Just(Operator::mul), Just(Operator::div),
+            Just(Operator::pow),
+        ];
+        let function = prop_oneof![Just(Function::sqrt), Just(Function::abs), Just(Function::sin)];
+        prop_oneof![
+            inner.clone().prop_map(|operand| Tree::negate(Box::new(operand))),
+            (operator, inner.clone(), inner.clone())
+                .prop_map(|(operator, left, right)| Tree::binary(operator, Box::new(left), Box::new(right))),
+            (function, inner).prop_map(|(function, operand)| Tree::call(function, Box::new(operand))),
+        ]
+    })
+}
+
+fn quarter() -> impl Strategy<Value = Number> {
+    (0u8..=20).prop_map(|quarters| quarters as Number / 4.0)
+}
+
+/// Values for the variable, including those that expose unsafe rewrites.
+fn any_value() -> impl Strategy<Value = Number> {
+    prop_oneof![
+        quarter(),
+        Just(-0.0), Just(Number::INFINITY), Just(Number::NEG_INFINITY), Just(Number::NAN),
+    ]
+}
+
+fn same(left: Option<Number>, right: Option<Number>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left == right || (left.is_nan() && right.is_nan()),
+        (left, right) => left == right,
+    }
+}
+
+fn identical(left: Option<Number>, right: Option<Number>) -> bool {
+    match (left, right) {
+        (Some(left), Some(right)) => left.to_bits() == right.to_bits() || (left.is_nan() && right.is_nan()),
+        (left, right) => left == right,
+    }
+}
+
+/// The value evaluating gave, or `None` if it divided by zero.
+fn divided(result: calc_rs::Result<Number>) -> Option<Number> {
+    result.map_err(|error| assert_eq!(error.code(), "division_by_zero")).ok()
+}
+
+proptest! {
+    #[test]
+    fn text_evaluates_like_its_tree(tree in tree(), x in quarter()) {
+        let text = tree.render();
+        let value = divided(evaluate_str(&text, &mut HashMap::from([("x".into(), x)])));
+        let expected = tree.evaluate(x);
+        prop_assert!(same(value, expected), "{} gave {:?} rather than {:?}", text, value, expected);
+    }
+
+    #[test]
+    fn grouped_text_evaluates_like_its_tree(tree in tree(), x in quarter()) {
+        let text = tree.render_grouped();
+        let value = divided(evaluate_str(&text, &mut HashMap::from([("x".into(), x)])));
+        let expected = tree.evaluate(x);
+        prop_assert!(same(value, expected), "{} gave {:?} rather than {:?}", text, value, expected);
+    }
+
+    #[test]
+    fn decoding_undoes_encoding(tree in tree()) {
+        let mut expression = Vec::new();
+        tree.emit(&mut expression);
+        prop_assert_eq!(decode(&encode(&expression)).unwrap(), expression);
+    }
+}
+
+proptest! {
+    // unsafe rewrites only show for a few values of x, so look harder
+    #![proptest_config(ProptestConfig::with_cases(4096))]
+
+    #[test]
+    fn simplifying_changes_no_value(tree in tree(), x in any_value()) {
+        let mut expression = Vec::new();
+        tree.emit(&mut expression);
+        let simplified = simplify(&expression);
+        let mut variables = HashMap::from([("x".into(), x)]);
+        let value = divided(evaluate(&simplified, &mut variables));
+        let expected = divided(evaluate(&expression, &mut variables));
+        prop_assert!(identical(value, expected),
+            "{} with x = {} gave {:?} rather than {:?}", tree.render(), x, value, expected);
+    }
+}
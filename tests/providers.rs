@@ -0,0 +1,73 @@
+//! A function provider supplies a pack of functions by name, consulted
+//! after the built-in and registered ones.
+
+use calc_rs::registry::{Callable, FunctionProvider};
+use calc_rs::{FunctionInfo, Number, Session};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Each name looked up, with the hint it was looked up with.
+type Hints = Rc<RefCell<Vec<(String, Option<u32>)>>>;
+
+/// Conversions between units, noting the hint of every lookup.
+struct Units {
+    hints: Hints,
+}
+
+impl FunctionProvider for Units {
+    fn lookup(&self, name: &str, arity_hint: Option<u32>) -> Option<Callable> {
+        self.hints.borrow_mut().push((name.into(), arity_hint));
+        match name {
+            "ftom" => Some(Callable::unary(Rc::new(|feet| feet * 0.3048))),
+            "hyp" => Some(Callable::binary(Rc::new(|a: Number, b: Number| a.hypot(b)))),
+            "sum_all" => Some(Callable::variadic(1, Rc::new(|values| values.iter().sum()))),
+            // hidden by the built-in of the same name
+            "sqrt" => Some(Callable::unary(Rc::new(|_| -1.0))),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> Vec<(String, String)> {
+        ["ftom", "hyp", "sum_all", "sqrt", "nothing"].into_iter()
+            .map(|name| (name.to_string(), format!("the {} conversion", name)))
+            .collect()
+    }
+}
+
+fn session() -> (Session, Hints) {
+    let hints = Rc::default();
+    let session = Session::builder().provider(Units {hints: Rc::clone(&hints)}).build().unwrap();
+    (session, hints)
+}
+
+#[test]
+fn provided_functions_are_called() {
+    let (mut session, _) = session();
+    assert_eq!(session.eval_line("ftom(10)").unwrap(), 3.048);
+    assert_eq!(session.eval_line("hyp(3, 4)").unwrap(), 5.0);
+    assert_eq!(session.eval_line("sum_all(1, 2, 3)").unwrap(), 6.0);
+    assert_eq!(session.eval_line("sqrt(4)").unwrap(), 2.0);
+    assert_eq!(session.eval_line("hyp(3)").unwrap_err().code(), "wrong_argument_count");
+    assert_eq!(session.eval_line("nothing(1)").unwrap_err().code(), "undefined");
+}
+
+#[test]
+fn linking_hints_the_argument_count() {
+    let (mut session, hints) = session();
+    session.eval_line("sum_all(1, 2, 3) + hyp(3, 4)").unwrap();
+    let hints = hints.borrow();
+    assert!(hints.contains(&("sum_all".into(), None)));
+    assert!(hints.contains(&("sum_all".into(), Some(3))));
+    assert!(hints.contains(&("hyp".into(), Some(2))));
+}
+
+#[test]
+fn only_reachable_functions_are_listed() {
+    let (session, _) = session();
+    let provided = session.registry().functions();
+    let names = provided.iter().map(|function| function.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["ftom", "hyp", "sum_all"]);
+    assert_eq!(provided[1], FunctionInfo {name: "hyp".into(), arity: (2, Some(2)), description: "the hyp conversion".into()});
+    assert_eq!(session.functions().iter().filter(|function| function.name == "sqrt").count(), 1);
+}
@@ -0,0 +1,45 @@
+"""Tests for the Python module, `calc_rs`.
+
+Build the module and run these from the repository root with
+
+    cargo build --lib --features python,pyo3/extension-module
+    cp target/debug/libcalc_rs.so target/debug/calc_rs.so
+    PYTHONPATH=target/debug python3 -m unittest discover tests/python
+
+or, with maturin, `maturin develop --features python` and then
+`python3 -m unittest discover tests/python`.
+"""
+
+import unittest
+
+import calc_rs
+
+
+class SessionTest(unittest.TestCase):
+    def test_assignments_last_across_calls(self):
+        session = calc_rs.Session()
+        self.assertEqual(session.eval("x = 2"), 2.0)
+        self.assertEqual(session.eval("x * 3"), 6.0)
+        self.assertEqual(session.vars["x"], 2.0)
+
+    def test_defined_values_are_used(self):
+        session = calc_rs.Session()
+        session.define("rate", 0.5)
+        self.assertEqual(session.eval("rate * 4"), 2.0)
+
+    def test_syntax_errors_are_parse_errors(self):
+        with self.assertRaises(calc_rs.ParseError):
+            calc_rs.Session().eval("1 +")
+
+    def test_other_errors_are_eval_errors(self):
+        with self.assertRaises(calc_rs.EvalError):
+            calc_rs.Session().eval("undefined_name + 1")
+
+    def test_errors_share_a_base(self):
+        self.assertTrue(issubclass(calc_rs.ParseError, calc_rs.CalcException))
+        self.assertTrue(issubclass(calc_rs.EvalError, calc_rs.CalcException))
+        self.assertFalse(issubclass(calc_rs.EvalError, calc_rs.ParseError))
+
+
+if __name__ == "__main__":
+    unittest.main()
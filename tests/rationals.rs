@@ -0,0 +1,41 @@
+//! Exact arithmetic keeps fractions exact through the basic operations and
+//! falls back to floating point past them.
+
+use calc_rs::session::{Arithmetic, Session};
+use calc_rs::{evaluate_str, Rational};
+
+use std::collections::HashMap;
+
+fn exact(text: &str) -> Rational {
+    evaluate_str(text, &mut HashMap::new()).unwrap()
+}
+
+#[test]
+fn thirds_add_to_exactly_one() {
+    assert_eq!(exact("1/3 + 1/3 + 1/3"), Rational::new(1, 1));
+    assert_eq!(exact("0.1 + 0.2"), Rational::new(3, 10));
+    assert_eq!(exact("(2/3)^-2 - 9/4"), Rational::new(0, 1));
+    assert_eq!(exact("fmod(7/2, 1) + floor(-5/2)"), Rational::new(-5, 2));
+    assert_eq!(exact("1/3 < 0.3334 && 1/3 > 0.3333"), Rational::new(1, 1));
+}
+
+#[test]
+fn other_functions_go_through_floats() {
+    assert_eq!(exact("sqrt(9/4)"), Rational::new(3, 2));
+    assert_eq!(exact("2^0.5").to_string(), "6369051672525773/4503599627370496");
+    assert_eq!(exact("10^400").to_string(), "inf");
+    assert_ne!(exact("sqrt(-1)"), exact("sqrt(-1)"));
+    let error = evaluate_str::<Rational>("1/0", &mut HashMap::new()).unwrap_err();
+    assert_eq!(error.code(), "division_by_zero");
+}
+
+#[test]
+fn sessions_keep_exact_values() {
+    let mut session = Session::new();
+    session.arithmetic = Arithmetic::exact;
+    assert_eq!(session.show_line("x = 1/3").unwrap(), "1/3");
+    assert_eq!(session.show_line("x * 3").unwrap(), "1");
+    assert_eq!(session.eval_line("x").unwrap(), 1.0 / 3.0);
+    session.eval_line("x = 0.5").unwrap();
+    assert_eq!(session.show_line("x + 1/3").unwrap(), "5/6");
+}
@@ -0,0 +1,136 @@
+//! The loop as a whole, over in-memory input and output.
+
+use calc_rs::repl::*;
+use calc_rs::Session;
+
+/// What `run_repl` writes to its output and its errors for `input`.
+fn run(input: &str, options: ReplOptions) -> (String, String, SessionSummary) {
+    let (mut output, mut errors) = (Vec::new(), Vec::new());
+    let summary = run_repl(&mut Session::new(), input.as_bytes(), &mut output, &mut errors, &options).unwrap();
+    (String::from_utf8(output).unwrap(), String::from_utf8(errors).unwrap(), summary)
+}
+
+#[test]
+fn values_and_errors_go_to_their_writers() {
+    let options = ReplOptions {keep_going: true, ..ReplOptions::default()};
+    let (output, errors, summary) = run("x = 2\nx * 3\nnope\n:bools on\n1 < 2\n:history\n", options);
+    assert_eq!(output, "2\n6\ntrue\n   1  x = 2\n   2  x * 3\n   3  nope\n   4  1 < 2\n");
+    assert_eq!(errors, "line 3: Error, identifier, 'nope', is not defined\n");
+    assert_eq!(summary, SessionSummary {lines: 6, errors: 1, stopped: false});
+}
+
+#[test]
+fn stops_at_the_first_error_unless_told_to_keep_going() {
+    let options = ReplOptions {source: Some("sums.txt".into()), ..ReplOptions::default()};
+    let (output, errors, summary) = run("1 + 1\n1 +\n2 + 2\n", options);
+    assert_eq!(output, "2\n");
+    assert_eq!(errors, "sums.txt: line 2: Error, expression ended abruptly\n");
+    assert_eq!(summary, SessionSummary {lines: 2, errors: 1, stopped: true});
+}
+
+#[test]
+fn prompts_and_continues_when_interactive() {
+    let options = ReplOptions {interactive: true, prompting: true, ..ReplOptions::default()};
+    let (output, errors, summary) = run("min(1,\n2)\n!1\n:wat\n:quit\n3\n", options);
+    assert_eq!(output, "> ... 1\n> min(1, 2)\n1\n> > ");
+    assert_eq!(errors, "Error, command, ':wat', is not recognized\n");
+    assert_eq!(summary, SessionSummary {lines: 5, errors: 1, stopped: false});
+}
+
+#[test]
+fn an_empty_line_may_quit() {
+    let (output, _, summary) = run(":mode emptyline quit\n1\n\n2\n", ReplOptions::default());
+    assert_eq!(output, "1\n");
+    assert_eq!(summary.lines, 3);
+}
+
+#[test]
+fn watches_follow_the_variables() {
+    let input = "x = 2\n:watch x y*2\ny = x + 1\nx = 10\n_\n:unwatch y*2\n1\n:unwatch\n2\n";
+    let (output, errors, _) = run(input, ReplOptions::default());
+    assert_eq!(output, concat!(
+        "2\n",
+        "3\nwatch: x = 2, y*2 = 6\n",
+        "10\nwatch: x = 10, y*2 = 6\n",
+        // watching leaves the previous result alone
+        "10\nwatch: x = 10, y*2 = 6\n",
+        "1\nwatch: x = 10\n",
+        "2\n",
+    ));
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn failing_watches_leave_a_note() {
+    let (output, errors, summary) = run(":watch 1/z z\nz = 0\nz = 4\n", ReplOptions::default());
+    assert_eq!(output, "0\nwatch: 1/z = (division_by_zero), z = 0\n4\nwatch: 1/z = 0.25, z = 4\n");
+    assert!(errors.is_empty());
+    assert_eq!(summary.errors, 0);
+}
+
+#[test]
+fn undo_takes_back_assignments_one_at_a_time() {
+    let options = ReplOptions {keep_going: true, ..ReplOptions::default()};
+    let (output, errors, _) = run("x = 1\nx = 2\n:undo\nx == 1\na, b = 3, 4\n:undo\n:undo\nb\n:undo\n:undo\n", options);
+    assert_eq!(output, "1\n2\nx = 1\n1\n4\nb is no longer set\na is no longer set\nx is no longer set\n");
+    assert_eq!(errors, "line 8: Error, identifier, 'b', is not defined\nline 10: Error, there is no assignment to undo\n");
+}
+
+/// Many independent lines, some of them empty and some failing.
+fn large_fixture() -> String {
+    (0..3000).map(|line| match line % 97 {
+        0 => String::new(),
+        13 => format!("{} / nope", line),
+        50 => format!("min({},", line),
+        _ => format!("sum(k, 1, {}, k) * {} + sqrt({})", line % 20, line, line),
+    }).collect::<Vec<_>>().join("\n")
+}
+
+#[test]
+fn parallel_runs_write_what_sequential_runs_write() {
+    let input = large_fixture();
+    assert!(independent_lines(input.lines()));
+    for keep_going in [true, false] {
+        let options = ReplOptions {keep_going, source: Some("large.txt".into()), ..ReplOptions::default()};
+        let sequential = run(&input, options.clone());
+        for threads in [1, 4, 7] {
+            let (mut output, mut errors) = (Vec::new(), Vec::new());
+            let threads = std::num::NonZeroUsize::new(threads).unwrap();
+            let summary = run_parallel(Session::new, &input, threads, &mut output, &mut errors, &options).unwrap();
+            let parallel = (String::from_utf8(output).unwrap(), String::from_utf8(errors).unwrap(), summary);
+            assert_eq!(parallel, sequential, "keep_going {}, {} threads", keep_going, threads);
+        }
+    }
+}
+
+#[test]
+fn dependent_lines_are_told_apart() {
+    assert!(independent_lines(["1 + 2", "", "max(3, 4)"].into_iter()));
+    assert!(!independent_lines(["x = 2", "x"].into_iter()));
+    assert!(!independent_lines(["1", "_ + 1"].into_iter()));
+    assert!(!independent_lines(["1", ":bools on"].into_iter()));
+}
+
+#[test]
+fn resetting_restores_every_setting() {
+    let input = concat!(
+        "x = 2\n:alias double 2 * x\n:watch x\n",
+        ":mode emptyline quit\n:mode assignment expression\n:mode missing skip\n:mode split on\n",
+        ":mode grouping on\n:mode percent on\n:mode notation scientific\n:precision 3\n",
+        ":bools on\n:repeating on\n:trace on\n:mode arithmetic exact\n",
+        ":reset\n",
+    );
+    let mut session = Session::new();
+    let (mut output, mut errors) = (Vec::new(), Vec::new());
+    run_repl(&mut session, input.as_bytes(), &mut output, &mut errors, &ReplOptions::default()).unwrap();
+    assert!(errors.is_empty(), "{}", String::from_utf8(errors).unwrap());
+    assert_eq!(session.snapshot(), Session::new().snapshot());
+    assert!(session.variables.is_empty());
+    assert!(session.undo().is_err());
+}
+
+#[test]
+fn resetting_takes_no_arguments() {
+    let (_, errors, _) = run(":reset --init\n", ReplOptions::default());
+    assert_eq!(errors, "line 1: Error, argument, '--init', is not recognized\n");
+}
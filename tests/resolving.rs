@@ -0,0 +1,66 @@
+//! A resolver answers for the names that are neither variables nor
+//! constants, both as lines are read and as formulas are recomputed. A
+//! default does the same for every such name.
+
+use calc_rs::session::Session;
+use calc_rs::{evaluate_str_with_default, ErrorKind, Number};
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+fn session(online: Rc<Cell<bool>>, reading: Rc<Cell<Number>>) -> Session {
+    Session::builder()
+        .constant("rate", 0.5)
+        .resolver(move |name| match name {
+            "port" => Some(8080.0),
+            "rate" | "x" => Some(-1.0),
+            "reading" if online.get() => Some(reading.get()),
+            _ => None,
+        })
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn resolved_names_follow_variables_and_constants() {
+    let mut session = session(Rc::default(), Rc::default());
+    assert_eq!(session.eval_line("port + 1").unwrap(), 8081.0);
+    assert_eq!(session.eval_line("rate").unwrap(), 0.5);
+    assert_eq!(session.eval_line("x").unwrap(), -1.0);
+    session.eval_line("x = 3").unwrap();
+    assert_eq!(session.eval_line("x").unwrap(), 3.0);
+    assert_eq!(session.eval_line("missing").unwrap_err().to_string(), "identifier, 'missing', is not defined");
+}
+
+#[test]
+fn resolved_names_may_appear_mid_session() {
+    let online = Rc::new(Cell::new(false));
+    let reading = Rc::new(Cell::new(20.0));
+    let mut session = session(online.clone(), reading.clone());
+    assert_eq!(session.eval_line("reading * 2").unwrap_err().kind(), ErrorKind::undefined);
+    session.define_formula("doubled", "reading * 2").unwrap();
+    assert_eq!(session.recalculate().unwrap_err().code(), "undefined");
+
+    online.set(true);
+    assert_eq!(session.eval_line("reading * 2").unwrap(), 40.0);
+    assert_eq!(session.recalculate().unwrap(), vec![("doubled".to_string(), 40.0)]);
+
+    // formulas read the resolver again whenever they are recomputed
+    reading.set(21.5);
+    session.define_formula("doubled", "reading * 2").unwrap();
+    assert_eq!(session.recalculate().unwrap(), vec![("doubled".to_string(), 43.0)]);
+}
+
+#[test]
+fn undefined_names_read_as_the_default() {
+    let mut variables = HashMap::<String, Number>::new();
+    assert_eq!(evaluate_str_with_default("x + 1", &mut variables, 0.0).unwrap(), 1.0);
+    assert!(!variables.contains_key("x"));
+
+    variables.insert("y".into(), 5.0);
+    assert_eq!(evaluate_str_with_default("y * z", &mut variables, 2.0).unwrap(), 10.0);
+    // as a derivative is evaluated, its variable reads as the default too
+    assert_eq!(evaluate_str_with_default("diff(x ^ 2, x)", &mut variables, 3.0).unwrap(), 6.0);
+    assert_eq!(evaluate_str_with_default("diff(w * x, x)", &mut variables, 3.0).unwrap(), 3.0);
+}
@@ -0,0 +1,167 @@
+//! Token spans cover exactly the text each token was read from, for the
+//! parser and for highlighters alike.
+
+use calc_rs::scanning::*;
+
+fn spans(input: &str) -> Vec<(String, usize, usize)> {
+    tokenize_spans(input).unwrap().into_iter()
+        .map(|span| (span.token.content.into_owned(), span.start, span.end))
+        .collect()
+}
+
+#[test]
+fn spans_cover_the_tokens() {
+    let input = "2 + sin(x)";
+    let expected = [("2", 0, 1), ("+", 2, 3), ("sin", 4, 7), ("(", 7, 8), ("x", 8, 9), (")", 9, 10)];
+    assert_eq!(spans(input), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+
+    let mut end = 0;
+    for (text, start, next) in spans(input) {
+        assert!(input[end..start].trim().is_empty(), "only whitespace comes between tokens");
+        assert_eq!(&input[start..next], text);
+        end = next;
+    }
+    assert_eq!(end, input.len());
+}
+
+#[test]
+fn numbers_take_exponents() {
+    let expected = [("1.5e-7", 0, 6), ("*", 7, 8), ("2E+3", 9, 13), ("-", 14, 15), ("2e10", 16, 20)];
+    assert_eq!(spans("1.5e-7 * 2E+3 - 2e10"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    let expected = [("1e-3", 0, 4), ("-", 4, 5), ("2", 5, 6)];
+    assert_eq!(spans("1e-3-2"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    for literal in ["2e10", "1.5e-3", "6.02E+23"] {
+        assert_eq!(spans(literal)[0].0.parse::<f32>().unwrap(), literal.parse::<f32>().unwrap());
+    }
+}
+
+#[test]
+fn unfinished_exponents_are_invalid_numbers() {
+    for (input, number) in [("1e", "1e"), ("1e+", "1e+"), ("2.5E-", "2.5E-"), ("(3e) + 1", "3e"), ("1e+x", "1e+")] {
+        let error = tokenize_spans(input).err().unwrap();
+        assert_eq!(error.to_string(), format!("'{}' is not a valid number", number), "scanning {}", input);
+    }
+    // a name may follow a number, if only for the parser to reject it
+    assert_eq!(spans("2ex").len(), 2);
+    assert_eq!(classes("1e+ 2"), [Err(("invalid_number", 0, 3)), Ok((TokenClass::number, "2", 4, 5))]);
+}
+
+#[test]
+fn prefixed_literals_are_numbers() {
+    let expected = [("0x10", 0, 4), ("*", 4, 5), ("2", 5, 6)];
+    assert_eq!(spans("0x10*2"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    let expected = [("f", 0, 1), ("(", 1, 2), ("0XaB", 2, 6), (",", 6, 7), ("2", 8, 9), (")", 9, 10)];
+    assert_eq!(spans("f(0XaB, 2)"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    let expected = [("0b1010", 0, 6), ("+", 7, 8), ("0B1", 9, 12)];
+    assert_eq!(spans("0b1010 + 0B1"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    for (input, number) in [("0xZZ", "0xZZ"), ("0x + 1", "0x"), ("2 * 0xFG", "0xFG"), ("0b102", "0b102"), ("0b2", "0b2"), ("(0b)", "0b")] {
+        let error = tokenize_spans(input).err().unwrap();
+        assert_eq!(error.to_string(), format!("'{}' is not a valid number", number), "scanning {}", input);
+    }
+}
+
+#[test]
+fn underscores_separate_digits() {
+    let expected = [("1_000", 0, 5), ("+", 6, 7), ("2_5.5", 8, 13), ("*", 14, 15), ("0xFF_FF", 16, 23)];
+    assert_eq!(spans("1_000 + 2_5.5 * 0xFF_FF"), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+    for (input, number) in [("100_", "100_"), ("1__000", "1__000"), ("1_.5", "1_.5"), ("1._5", "1._5"), ("2 * 3_e2", "3_"), ("0x_FF", "0x_FF"), ("0b1_", "0b1_"), ("_100", "_100"), ("2 + _1_000", "_1_000")] {
+        let error = tokenize_spans(input).err().unwrap();
+        assert_eq!(error.to_string(), format!("'{}' is not a valid number", number), "scanning {}", input);
+    }
+    // a leading underscore before anything but a digit begins a name, as `_` does
+    assert_eq!(spans("_x100 + _"), [("_x100".to_string(), 0, 5), ("+".to_string(), 6, 7), ("_".to_string(), 8, 9)]);
+}
+
+#[test]
+fn spans_count_bytes() {
+    let expected = [("π", 2, 4), ("*", 5, 6), ("2", 8, 9)];
+    assert_eq!(spans("  π *  2 "), expected.map(|(text, start, end)| (text.to_string(), start, end)));
+}
+
+type Classed<'a> = Result<(TokenClass, &'a str, usize, usize), (&'static str, usize, usize)>;
+
+fn classes(input: &str) -> Vec<Classed<'_>> {
+    tokenize(input).into_iter()
+        .map(|item| match item {
+            Ok(token) => {
+                assert_eq!(&input[token.span.clone()], token.text);
+                Ok((token.kind, &input[token.span.clone()], token.span.start, token.span.end))
+            },
+            Err(error) => Err((error.error.code(), error.span.start, error.span.end)),
+        })
+        .collect()
+}
+
+#[test]
+fn tokenize_classes_symbols() {
+    use TokenClass::*;
+    assert_eq!(classes("x<=2|>sqrt!=y||(1,2)"), [
+        Ok((identifier, "x", 0, 1)), Ok((less_equal, "<=", 1, 3)), Ok((number, "2", 3, 4)),
+        Ok((pipe, "|>", 4, 6)), Ok((identifier, "sqrt", 6, 10)), Ok((not_equal, "!=", 10, 12)),
+        Ok((identifier, "y", 12, 13)), Ok((or, "||", 13, 15)), Ok((open_paren, "(", 15, 16)),
+        Ok((number, "1", 16, 17)), Ok((comma, ",", 17, 18)), Ok((number, "2", 18, 19)),
+        Ok((close_paren, ")", 19, 20)),
+    ]);
+}
+
+#[test]
+fn typographic_operators_are_operators() {
+    use TokenClass::*;
+    assert_eq!(classes("3 × 4 ÷ 2 − 1"), [
+        Ok((number, "3", 0, 1)), Ok((times, "×", 2, 4)), Ok((number, "4", 5, 6)), Ok((divide, "÷", 7, 9)),
+        Ok((number, "2", 10, 11)), Ok((minus, "−", 12, 15)), Ok((number, "1", 16, 17)),
+    ]);
+}
+
+#[test]
+fn tokenize_goes_on_past_errors() {
+    use TokenClass::*;
+    assert_eq!(classes("\t2 € \n  π ! ; x  "), [
+        Ok((number, "2", 1, 2)),
+        Err(("invalid_character", 3, 6)),
+        Ok((identifier, "π", 10, 12)),
+        Err(("invalid_character", 13, 14)),
+        Err(("invalid_character", 15, 16)),
+        Ok((identifier, "x", 17, 18)),
+    ]);
+    assert!(tokenize("").is_empty());
+    assert!(tokenize(" \n\t ").is_empty());
+}
+
+#[test]
+fn tokenize_reads_what_the_parser_reads() {
+    for input in ["1 + 2 * sin(x)", "a = 1..2 |> f", "min(1, 2) >= 3 && 0", "2 $ 3"] {
+        let scanned = StringScanner::new(input.into()).map(|token| token.map(|token| token.content.into_owned()));
+        let tokenized = tokenize(input).into_iter().map(|item| item.map(|token| token.text));
+        for (scanned, tokenized) in scanned.zip(tokenized) {
+            match (scanned, tokenized) {
+                (Ok(scanned), Ok(tokenized)) => assert_eq!(scanned, tokenized),
+                (Err(scanned), Err(tokenized)) => {
+                    assert_eq!(scanned.code(), tokenized.error.code());
+                    break;
+                },
+                _ => panic!("tokenize disagrees with the scanner on {}", input),
+            }
+        }
+    }
+}
+
+#[test]
+fn statements_split_outside_parentheses() {
+    assert_eq!(split_statements("a = 1; b = 2;; a + b", false), ["a = 1", " b = 2", "", " a + b"]);
+    assert_eq!(split_statements("max(1;2); 1,000", true), ["max(1;2)", " 1,000"]);
+    assert_eq!(split_statements("max(1;2", true), ["max(1;2"]);
+    assert_eq!(split_statements("$; 1", false), ["$", " 1"]);
+}
+
+#[test]
+fn percentages_are_hundredths() {
+    let contents = |input: &str| StringScanner::new(input.into()).with_percentages()
+        .map(|token| token.map(|token| token.content.into_owned()).map_err(|error| error.code()))
+        .take(3)
+        .collect::<Vec<_>>();
+    assert_eq!(contents("25% + 1.5e3%"), [Ok("25e-2".into()), Ok("+".into()), Ok("1.5e1".into())]);
+    // the scanner does not pass over an error, leaving the parser to report it
+    assert_eq!(contents("25 % 7")[..2], [Ok("25".into()), Err("invalid_character")]);
+    assert_eq!(contents("25%")[0].as_ref().unwrap().parse::<f32>(), Ok(0.25));
+}
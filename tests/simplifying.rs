@@ -0,0 +1,56 @@
+//! Each rewrite `simplify` makes, and those it must not make, on small
+//! expressions written in postfix.
+
+use calc_rs::parsing::{BinaryFunction, ExprNode, Function};
+use calc_rs::simplifying::simplify;
+
+/// Reads space separated postfix, where `x` is loaded when evaluated and
+/// `min/2` calls `min` with two arguments.
+fn postfix(text: &str) -> Vec<ExprNode> {
+    text.split_whitespace().map(|word| match word {
+        "+" => ExprNode::binary(BinaryFunction::addition),
+        "-" => ExprNode::binary(BinaryFunction::subtraction),
+        "*" => ExprNode::binary(BinaryFunction::multiplication),
+        "/" => ExprNode::binary(BinaryFunction::division),
+        "^" => ExprNode::binary(BinaryFunction::exponentiation),
+        "neg" => ExprNode::unary(Function::negative),
+        "pos" => ExprNode::unary(Function::positive),
+        "sqrt" => ExprNode::unary(Function::sqrt),
+        "x" => ExprNode::load("x".into()),
+        _ => match word.split_once('/') {
+            Some((name, count)) => ExprNode::call(name.into(), count.parse().unwrap()),
+            None => ExprNode::value(word.parse().unwrap()),
+        },
+    }).collect()
+}
+
+#[test]
+fn rewrites() {
+    let cases = [
+        // constant folding, including of built-in calls
+        ("2 3 * 4 +", "10"),
+        ("16 sqrt x *", "4 x *"),
+        ("1 5 2 min/3", "1"),
+        ("x 2 3 ^ +", "x 8 +"),
+        // operations giving their operand back
+        ("x 1 *", "x"),
+        ("1 x *", "x"),
+        ("x 1 /", "x"),
+        ("x 1 ^", "x"),
+        ("x 0 -", "x"),
+        ("x 0 neg +", "x"),
+        ("x pos", "x"),
+        ("x neg neg", "x"),
+        ("x 2 1 * 1 - ^", "x"),
+        // those that would change some result
+        ("x 0 +", "x 0 +"),
+        ("x 0 *", "x 0 *"),
+        ("x 0 neg -", "x -0 -"),
+        ("x 2 + 3 +", "x 2 + 3 +"),
+        // calls to registered functions, which may not be pure
+        ("2 double/1", "2 double/1"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(simplify(&postfix(input)), postfix(expected), "simplifying {}", input);
+    }
+}
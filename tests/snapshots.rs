@@ -0,0 +1,84 @@
+//! A session snapshot, written out and read back, gives a session that
+//! evaluates just as the one it was taken from, and parsed expressions read
+//! back evaluate as they did before.
+#![cfg(feature = "serde")]
+
+use calc_rs::formatting::Notation;
+use calc_rs::parsing::{parse_with, ExprNode};
+use calc_rs::registry::{Callable, Missing, Registry};
+use calc_rs::scanning::StringScanner;
+use calc_rs::session::{Arithmetic, Session, SessionSnapshot};
+use calc_rs::{evaluate_compiled, link, Limits, Number};
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+fn round_trip(session: &Session) -> Session {
+    let text = serde_json::to_string(&session.snapshot()).unwrap();
+    let snapshot: SessionSnapshot = serde_json::from_str(&text).unwrap();
+    let mut restored = Session::new();
+    restored.restore(snapshot).unwrap();
+    restored
+}
+
+#[test]
+fn restored_sessions_evaluate_alike() {
+    let mut session = Session::new();
+    session.arithmetic = Arithmetic::exact;
+    session.repeating = true;
+    session.display.notation = Notation::scientific;
+    session.missing = Missing::skip;
+    session.watches = vec!["x * 2".into(), "y".into()];
+    session.aliases.define("double", "2 * y").unwrap();
+    for line in ["x = 1/3", "y = 2.5", "x + y"] {
+        session.history.record(line.into());
+        session.show_line(line).unwrap();
+    }
+    session.set("a", 2.0);
+    session.define_formula("b", "a ^ 2").unwrap();
+    session.recalculate().unwrap();
+
+    let mut restored = round_trip(&session);
+    assert_eq!(restored.snapshot(), session.snapshot());
+    assert_eq!(restored.history.entries().count(), 3);
+    for line in ["_", "x * 3", "double + 1", "b"] {
+        let line = session.aliases.expand(line).unwrap();
+        assert_eq!(restored.show_line(&line).unwrap(), session.show_line(&line).unwrap(), "showing {}", line);
+    }
+
+    // formulas go on following what they read
+    restored.set("a", 3.0);
+    assert_eq!(restored.recalculate().unwrap(), vec![("b".to_string(), 9.0)]);
+}
+
+#[test]
+fn formulas_are_read_again() {
+    let mut snapshot = Session::new().snapshot();
+    snapshot.formulas.insert("b".into(), "1 +".into());
+    snapshot.variables.insert("kept".into(), 1.0);
+
+    // a formula that no longer reads leaves the session as it started
+    let mut restored = Session::new();
+    assert!(restored.restore(snapshot).is_err());
+    assert!(restored.eval_line("kept").is_err());
+}
+
+#[test]
+fn read_back_expressions_link_alike() {
+    let mut registry = Registry::default();
+    registry.register("myfn", Callable::binary(Rc::new(|x: Number, y: Number| x * 10.0 + y))).unwrap();
+    let mut variables = HashMap::from([("x".to_string(), 4.0)]);
+    let text = "myfn(min(x, 2, 3), 5) + 1";
+    let expression = parse_with(StringScanner::new(text.into()), &mut variables, &registry).unwrap();
+    assert!(expression.contains(&ExprNode::call("myfn".into(), 2)));
+    assert!(expression.contains(&ExprNode::call("min".into(), 3)));
+
+    let json = serde_json::to_string(&expression).unwrap();
+    let read: Vec<ExprNode> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, expression);
+    let limits = Limits::default();
+    let value = evaluate_compiled(&link(&read, &registry).unwrap(), &mut variables, &limits).unwrap();
+    assert_eq!(value, evaluate_compiled(&link(&expression, &registry).unwrap(), &mut variables, &limits).unwrap());
+    assert_eq!(value, 26.0);
+    assert_eq!(link(&read, &Registry::default()).err().map(|error| error.code()), Some("undefined"));
+}
@@ -0,0 +1,22 @@
+//! Unknown identifiers read as products of their letters only when asked
+//! to, and never in place of a name that is known.
+
+use calc_rs::session::Session;
+use calc_rs::ErrorKind;
+
+#[test]
+fn letters_multiply() {
+    let mut session = Session::new();
+    session.eval_line("x = 2").unwrap();
+    session.eval_line("y = 3").unwrap();
+    assert_eq!(session.eval_line("xy").unwrap_err().kind(), ErrorKind::undefined);
+
+    session.split_identifiers = true;
+    assert_eq!(session.eval_line("xy == 6").unwrap(), 1.0);
+    assert_eq!(session.eval_line("yxy").unwrap(), 18.0);
+    assert_eq!(session.eval_line("xpi").unwrap_err().to_string(), "identifier, 'xpi', is not defined");
+    assert_eq!(session.eval_line("xz").unwrap_err().to_string(), "identifier, 'xz', is not defined");
+
+    session.eval_line("xy = 10").unwrap();
+    assert_eq!(session.eval_line("xy").unwrap(), 10.0);
+}
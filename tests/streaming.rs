@@ -0,0 +1,84 @@
+//! A generated program of several megabytes, evaluated as it is read, must
+//! never hold more than a small fraction of itself in memory.
+
+#![allow(nonstandard_style)]
+
+use calc_rs::streaming::*;
+use calc_rs::{Limits, Number, Registry};
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Measuring;
+
+static live: AtomicUsize = AtomicUsize::new(0);
+static peak: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Measuring {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let now = live.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        peak.fetch_max(now, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        live.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(pointer, layout)
+    }
+}
+
+#[global_allocator]
+static allocator: Measuring = Measuring;
+
+/// Reads as `1+` written out `left` times, without holding the text.
+struct Terms {
+    left: usize,
+}
+
+impl Read for Terms {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let pairs = self.left.min(buffer.len() / 2);
+        for pair in buffer[..pairs * 2].chunks_mut(2) {
+            pair.copy_from_slice(b"1+");
+        }
+        self.left -= pairs;
+        Ok(pairs * 2)
+    }
+}
+
+#[test]
+fn megabytes_evaluate_in_kilobytes() {
+    // the sum stays exact in a Number, and the text is three megabytes
+    let terms = 1_500_000;
+    let program = b"x = ".chain(Terms {left: terms})
+        .chain(&b"1\nx * 2; 2 + (\n\nsq(3)"[..]);
+
+    let mut variables = HashMap::new();
+    let mut results = Vec::new();
+    let before = live.load(Ordering::Relaxed);
+    peak.store(before, Ordering::Relaxed);
+    evaluate_stream(BufReader::new(program), &mut variables, &Registry::default(), &Limits::default(),
+        |result| results.push(result.map_err(|e| e.to_string()))).unwrap();
+    let used = peak.load(Ordering::Relaxed) - before;
+
+    let expected: Vec<Result<Number, String>> = vec![
+        Ok(terms as Number + 1.0),
+        Ok(2.0 * (terms as Number + 1.0)),
+        Err("expression ended abruptly".into()),
+        Ok(9.0),
+    ];
+    assert_eq!(results, expected);
+    assert!(used < 64 * 1024, "streaming held {} bytes at once", used);
+}
+
+#[test]
+fn nesting_past_the_limit_is_an_error() {
+    let limits = Limits {max_depth: Some(100), ..Limits::default()};
+    let program = "(".repeat(200) + "1" + &")".repeat(200) + "\n1 + 1";
+    let mut results = Vec::new();
+    evaluate_stream(program.as_bytes(), &mut HashMap::<String, Number>::new(), &Registry::default(), &limits,
+        |result| results.push(result.map_err(|e| e.to_string()))).unwrap();
+    assert_eq!(results, vec![Err("an expression may not nest more than 100 deep".into()), Ok(2.0)]);
+}
@@ -0,0 +1,42 @@
+//! `--timeout` takes a duration with a unit, and stops any evaluation that
+//! runs longer than that.
+
+#![allow(nonstandard_style)]
+
+use std::process::{Command, Output};
+
+const endless: &str = "sum(i, 1, 999999, sum(j, 1, 999999, i * j))";
+
+fn run(timeout: &str, line: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calc_rs")).args(["--timeout", timeout, "eval", line]).output().unwrap()
+}
+
+fn written(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn long_evaluations_are_stopped() {
+    let output = run("1ms", endless);
+    assert_eq!(written(&output), "Error, evaluation exceeded the 1ms time limit\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run("500ms", endless);
+    assert_eq!(written(&output), "Error, evaluation exceeded the 500ms time limit\n");
+}
+
+#[test]
+fn fractions_of_units_are_allowed() {
+    let output = run("1.5m", "1 + 1");
+    assert!(output.status.success());
+    assert_eq!(written(&output), "2\n");
+}
+
+#[test]
+fn invalid_durations_are_refused() {
+    for timeout in ["-1s", "5h", "10", "s"] {
+        let output = run(timeout, "1 + 1");
+        assert_eq!(written(&output), format!("Error, '{}' is not a valid duration\n", timeout));
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
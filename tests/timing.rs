@@ -0,0 +1,34 @@
+//! Timed evaluation gives what untimed evaluation does, along with how long
+//! each phase took.
+
+use calc_rs::{evaluate_str, evaluate_str_timed, Number, Session};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[test]
+fn timed_values_match_untimed_ones() {
+    let text = "x = sum(i, 1, 20000, i) / 2";
+    let mut timed_variables = HashMap::<String, Number>::new();
+    let (value, timings) = evaluate_str_timed(text, &mut timed_variables).unwrap();
+    let mut variables = HashMap::new();
+    assert_eq!(value, evaluate_str(text, &mut variables).unwrap());
+    assert_eq!(timed_variables, variables);
+    assert!(timings.eval > Duration::ZERO);
+    assert!(timings.scan + timings.parse + timings.eval < Duration::from_secs(60));
+}
+
+#[test]
+fn sessions_time_lines_as_they_evaluate_them() {
+    let mut session = Session::builder().constant("rate", 0.5).build().unwrap();
+    session.set_preprocessor(|line| Cow::Owned(line.replace("twice", "2 *")));
+    session.eval_line("x = 3").unwrap();
+    let (value, timings) = session.time_line("twice x * rate").unwrap();
+    assert_eq!(value, 3.0);
+    assert!(timings.eval > Duration::ZERO);
+    assert_eq!(session.eval_line("_ + 1").unwrap(), 4.0);
+
+    session.limits.max_iterations = Some(10);
+    assert_eq!(session.time_line("sum(i, 1, 100, i)").unwrap_err().code(), "iteration_limit");
+}
@@ -0,0 +1,110 @@
+//! With the `trace` feature, parsing and evaluating are spans holding an
+//! event for each rule applied and each node evaluated.
+
+#![cfg(feature = "trace")]
+
+use calc_rs::{evaluate_str, Number};
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Write};
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Writes down every span and event as a line of text.
+#[derive(Clone, Default)]
+struct Collector {
+    lines: Arc<Mutex<Vec<String>>>,
+    spans: Arc<Mutex<Vec<&'static str>>>,
+}
+
+impl Collector {
+    fn write(&self, line: String) {
+        self.lines.lock().unwrap().push(line);
+    }
+
+    fn name(&self, span: &Id) -> &'static str {
+        self.spans.lock().unwrap()[span.into_u64() as usize - 1]
+    }
+}
+
+/// The fields of a span or event, as ` name=value` each.
+#[derive(Default)]
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        write!(self.0, " {}={}", field, value).unwrap();
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        write!(self.0, " {}={:?}", field, value).unwrap();
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(attributes.metadata().name());
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        self.write(format!("new {}{}", attributes.metadata().name(), fields.0));
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        self.write(format!("record {}{}", self.name(span), fields.0));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.write(format!("event{}", fields.0));
+    }
+
+    fn enter(&self, span: &Id) {
+        self.write(format!("enter {}", self.name(span)));
+    }
+
+    fn exit(&self, span: &Id) {
+        self.write(format!("exit {}", self.name(span)));
+    }
+}
+
+#[test]
+fn spans_and_events() {
+    let collector = Collector::default();
+    let value = tracing::subscriber::with_default(collector.clone(), || {
+        evaluate_str("2 * -3", &mut HashMap::<String, Number>::new())
+    });
+    assert_eq!(value.unwrap(), -6.0);
+    let lines = collector.lines.lock().unwrap();
+    assert_eq!(*lines, [
+        "new parse",
+        "enter parse",
+        "event message=applying rule rule=value_placing token=2",
+        "event message=applying rule rule=operator_binding token=*",
+        "event message=applying rule rule=operator_placing token=-",
+        "event message=applying rule rule=value_placing token=3",
+        "record parse tokens=4",
+        "record parse nodes=4",
+        "exit parse",
+        "new evaluate nodes=4",
+        "enter evaluate",
+        "event message=evaluating node node=value(2.0) depth=0",
+        "event message=evaluating node node=value(3.0) depth=1",
+        "event message=evaluating node node=unary(negative) depth=2",
+        "event message=evaluating node node=binary(multiplication) depth=2",
+        "exit evaluate",
+    ]);
+}
@@ -0,0 +1,64 @@
+//! Transcripts of sessions, one scenario to a file in `transcripts/`. Each
+//! line beginning `> ` is read by the REPL, as typed interactively, and the
+//! lines up to the next `> ` are what it writes back: the value, or `Error, `
+//! and the error. Commands such as `:bools on` often write nothing. Lines
+//! before the first `> ` are kept as they are, for comments.
+//!
+//! Write a new scenario as its `> ` lines alone and run the tests with
+//! `UPDATE_TRANSCRIPTS=1` to fill in what the session writes, then review
+//! the file, as any later change to it is reviewed in its diff.
+
+use calc_rs::repl::{run_repl, ReplOptions};
+use calc_rs::session::Session;
+
+use std::fs;
+use std::path::Path;
+
+/// What the REPL writes for `line`, values and errors alike.
+fn respond(session: &mut Session, line: &str) -> String {
+    let options = ReplOptions {interactive: true, ..ReplOptions::default()};
+    let mut written = Vec::new();
+    let mut errors = Vec::new();
+    run_repl(session, line.as_bytes(), &mut written, &mut errors, &options).unwrap();
+    written.extend(errors);
+    String::from_utf8(written).unwrap()
+}
+
+/// The transcript `text` should be, read against a new session.
+fn replay(text: &str) -> String {
+    let mut session = Session::new();
+    let mut transcript = String::new();
+    let mut lines = text.lines().peekable();
+    while let Some(line) = lines.next_if(|line| !line.starts_with("> ")) {
+        transcript += line;
+        transcript += "\n";
+    }
+    for line in lines.filter(|line| line.starts_with("> ")) {
+        transcript += line;
+        transcript += "\n";
+        transcript += &respond(&mut session, &line[2..]);
+    }
+    transcript
+}
+
+#[test]
+fn transcripts() {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/transcripts");
+    let updating = std::env::var_os("UPDATE_TRANSCRIPTS").is_some();
+    let mut changed = Vec::new();
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+        let transcript = replay(&text);
+        if transcript != text {
+            if updating {
+                fs::write(&path, &transcript).unwrap();
+            } else {
+                eprintln!("--- {}\n{}", path.display(), transcript);
+                changed.push(path.file_name().unwrap().to_string_lossy().into_owned());
+            }
+        }
+    }
+    changed.sort();
+    assert!(changed.is_empty(), "transcripts differ, written out above: {:?}", changed);
+}
@@ -0,0 +1,19 @@
+//! The JavaScript bindings, called from Rust as a script would call them.
+
+#![cfg(feature = "wasm")]
+
+use calc_rs::wasm::*;
+
+#[test]
+fn assignments_last_across_calls() {
+    let mut session = new_session();
+    assert_eq!(eval_line(&mut session, "x = 1/4"), "0.25");
+    assert_eq!(eval_line(&mut session, "x * 2"), "0.5");
+    assert_eq!(eval_line(&mut session, "10^20"), "1e20");
+}
+
+#[test]
+fn errors_are_json() {
+    let mut session = new_session();
+    assert_eq!(eval_line(&mut session, "y"), "{\"error\": \"identifier, 'y', is not defined\"}");
+}